@@ -8,6 +8,9 @@ pub enum AnsiSequence {
     ESC(ESCSequence),
 }
 
+/// One parameter of SGR with the sub parameters that follow it after colons, like `4:3`
+pub type SgrParameter = Vec<u8, 6>;
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum CSISequence {
     CursorPos(u32, u32),
@@ -25,7 +28,8 @@ pub enum CSISequence {
     EraseDisplay(u8),
     EraseCharacters(u32),
     EraseInLine(u32),
-    SetGraphicsMode(Vec<u8, 5>),
+    // A list of any length, on the heap to keep the other sequences small
+    SetGraphicsMode(std::vec::Vec<SgrParameter>),
     SetMode(u8),
     ResetMode(u8),
     SetNewLineMode,
@@ -35,7 +39,12 @@ pub enum CSISequence {
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum OSCSequence {
+    // OSC 0 sets the icon name too, we have none so OSC 2 does the same
+    SetTitle(String),
     ResetCursorColor,
+    SetCursorColor(String),
+    // OSC 701 from urxvt, which sets the locale. Only the encoding part is used.
+    SetEncoding(String),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -56,6 +65,9 @@ pub enum ESCSequence {
     SetG0AltAndSpecialGraph,
     SetG1AltAndSpecialGraph,
     ReverseIndex,
+    // S7C1T and S8C1T, whether our replies use 7-bit `ESC [` or 8-bit CSI and so on
+    SevenBitControls,
+    EightBitControls,
 }
 
 impl Display for AnsiSequence {
@@ -98,14 +110,20 @@ impl Display for CSISequence {
                 _ => write!(formatter, "{}K", n),
             },
             EraseCharacters(n) => write!(formatter, "{}X", n),
-            SetGraphicsMode(vec) => match vec.len() {
-                0 => write!(formatter, "m"),
-                1 => write!(formatter, "{}m", vec[0]),
-                2 => write!(formatter, "{};{}m", vec[0], vec[1]),
-                3 => write!(formatter, "{};{};{}m", vec[0], vec[1], vec[2]),
-                5 => write!(formatter, "{};{};{};{};{}m", vec[0], vec[1], vec[2], vec[3], vec[4]),
-                _ => unreachable!(),
-            },
+            SetGraphicsMode(parameters) => {
+                for (index, parameter) in parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(formatter, ";")?;
+                    }
+                    for (index, value) in parameter.iter().enumerate() {
+                        if index > 0 {
+                            write!(formatter, ":")?;
+                        }
+                        write!(formatter, "{}", value)?;
+                    }
+                }
+                write!(formatter, "m")
+            }
             SetMode(mode) => write!(formatter, "={}h", mode),
             ResetMode(mode) => write!(formatter, "={}l", mode),
             SetNewLineMode => write!(formatter, "20h"),
@@ -121,7 +139,10 @@ impl Display for OSCSequence {
 
         use OSCSequence::*;
         match self {
+            SetTitle(title) => write!(formatter, "0;{}\u{7}", title),
             ResetCursorColor => write!(formatter, "112\u{7}"),
+            SetCursorColor(color) => write!(formatter, "12;{}\u{7}", color),
+            SetEncoding(encoding) => write!(formatter, "701;{}\u{7}", encoding),
         }
     }
 }
@@ -148,6 +169,8 @@ impl Display for ESCSequence {
             SetG0AltAndSpecialGraph => write!(formatter, "(2"),
             SetG1AltAndSpecialGraph => write!(formatter, ")2"),
             ReverseIndex => write!(formatter, "M"),
+            SevenBitControls => write!(formatter, " F"),
+            EightBitControls => write!(formatter, " G"),
         }
     }
 }
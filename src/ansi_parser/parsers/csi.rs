@@ -1,8 +1,7 @@
-use crate::ansi_parser::{AnsiSequence, CSISequence};
+use crate::ansi_parser::{ansi_sequences::SgrParameter, AnsiSequence, CSISequence};
 
-use heapless::Vec;
 use winnow::ascii::{digit0, digit1};
-use winnow::combinator::{alt, delimited, opt, preceded, terminated};
+use winnow::combinator::{alt, delimited, opt, preceded, separated, terminated};
 use winnow::error::InputError;
 use winnow::token::literal;
 use winnow::{PResult, Parser};
@@ -77,56 +76,19 @@ fn cursor_style<'s>(input: &mut &'s str) -> PResult<CSISequence, InputError<&'s
         .parse_next(input)
 }
 
-fn graphics_mode1<'s>(input: &mut &'s str) -> PResult<CSISequence, InputError<&'s str>> {
-    terminated(parse_u8, "m")
-        .map(|val| {
-            let mode = Vec::from_slice(&[val]).expect("Vec::from_slice should allocate sufficient size");
-            CSISequence::SetGraphicsMode(mode)
-        })
-        .parse_next(input)
-}
-
-fn graphics_mode2<'s>(input: &mut &'s str) -> PResult<CSISequence, InputError<&'s str>> {
-    (parse_u8, ";", parse_u8, "m")
-        .map(|(val1, _, val2, _)| {
-            let mode = Vec::from_slice(&[val1, val2]).expect("Vec::from_slice should allocate sufficient size");
-            CSISequence::SetGraphicsMode(mode)
-        })
-        .parse_next(input)
-}
-
-fn graphics_mode3<'s>(input: &mut &'s str) -> PResult<CSISequence, InputError<&'s str>> {
-    (parse_u8, ";", parse_u8, ";", parse_u8, "m")
-        .map(|(val1, _, val2, _, val3, _)| {
-            let mode = Vec::from_slice(&[val1, val2, val3]).expect("Vec::from_slice should allocate sufficient size");
-            CSISequence::SetGraphicsMode(mode)
-        })
-        .parse_next(input)
-}
-
-fn graphics_mode4<'s>(input: &mut &'s str) -> PResult<CSISequence, InputError<&'s str>> {
-    "m".value(CSISequence::SetGraphicsMode(Vec::new())).parse_next(input)
-}
-
-fn graphics_mode5<'s>(input: &mut &'s str) -> PResult<CSISequence, InputError<&'s str>> {
-    (
-        parse_u8, ";", parse_u8, ";", parse_u8, ";", parse_u8, ";", parse_u8, "m",
-    )
-        .map(|(val1, _, val2, _, val3, _, val4, _, val5, _)| {
-            let mode = Vec::from_slice(&[val1, val2, val3, val4, val5])
-                .expect("Vec::from_slice should allocate sufficient size");
-            CSISequence::SetGraphicsMode(mode)
-        })
+// One SGR parameter with its colon separated sub parameters, like `4:3` for a curly underline.
+// Empty values count as 0, like the color space in `38:2::255:0:0`.
+fn graphics_parameter<'s>(input: &mut &'s str) -> PResult<SgrParameter, InputError<&'s str>> {
+    let value = digit0.try_map(|s: &str| if s.is_empty() { Ok(0) } else { s.parse::<u8>() });
+    separated(1.., value, ":")
+        .try_map(|values: Vec<u8>| SgrParameter::from_slice(&values))
         .parse_next(input)
 }
 
 fn graphics_mode<'s>(input: &mut &'s str) -> PResult<CSISequence, InputError<&'s str>> {
     alt((
-        graphics_mode1,
-        graphics_mode2,
-        graphics_mode3,
-        graphics_mode4,
-        graphics_mode5,
+        "m".value(CSISequence::SetGraphicsMode(Vec::new())),
+        terminated(separated(1.., graphics_parameter, ";"), "m").map(CSISequence::SetGraphicsMode),
     ))
     .parse_next(input)
 }
@@ -287,6 +249,10 @@ mod tests {
     test_parser!(set_video_mode_b, "\u{1b}[4;42m");
     test_parser!(set_video_mode_c, "\u{1b}[4;31;42m");
     test_parser!(set_video_mode_d, "\u{1b}[4;31;42;42;42m");
+    test_parser!(set_video_mode_e, "\u{1b}[1;4;31;42m");
+    test_parser!(set_video_mode_f, "\u{1b}[38;2;255;128;0;48;5;17m");
+    test_parser!(set_underline_style, "\u{1b}[4:3m");
+    test_parser!(set_underline_style_combined, "\u{1b}[1;4:3;58:2:0:10:20:30m");
 
     test_parser!(reset_mode, "\u{1b}[=13l");
     test_parser!(set_mode, "\u{1b}[=7h");
@@ -360,6 +326,16 @@ mod tests {
         assert_eq!(strings[4], Output::TextBlock("\x1b[a;sd7H"));
     }
 
+    #[test]
+    fn sgr_sub_parameters_stay_with_their_parameter() {
+        let sequence = parse_sequence("\x1b[1;4:3;38:2::255:0:0;;7m").unwrap().1;
+        let AnsiSequence::CSI(CSISequence::SetGraphicsMode(parameters)) = sequence else {
+            panic!("not SGR: {:?}", sequence);
+        };
+        let parameters: Vec<&[u8]> = parameters.iter().map(|parameter| &parameter[..]).collect();
+        assert_eq!(parameters, vec![&[1][..], &[4, 3], &[38, 2, 0, 255, 0, 0], &[0], &[7]]);
+    }
+
     #[test]
     fn test_cursor_pos() {
         let pos = CSISequence::CursorPos(5, 20);
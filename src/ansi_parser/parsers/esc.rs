@@ -29,6 +29,8 @@ tag_parser!(set_g1_alternate, ")1", ESCSequence::SetG1AlternateChar);
 tag_parser!(set_g0_graph, "(2", ESCSequence::SetG0AltAndSpecialGraph);
 tag_parser!(set_g1_graph, ")2", ESCSequence::SetG1AltAndSpecialGraph);
 tag_parser!(reverse_index, "M", ESCSequence::ReverseIndex);
+tag_parser!(seven_bit_controls, " F", ESCSequence::SevenBitControls);
+tag_parser!(eight_bit_controls, " G", ESCSequence::EightBitControls);
 
 fn combined<'s>(input: &mut &'s str) -> PResult<ESCSequence, InputError<&'s str>> {
     alt((
@@ -48,6 +50,8 @@ fn combined<'s>(input: &mut &'s str) -> PResult<ESCSequence, InputError<&'s str>
         set_g0_graph,
         set_g1_graph,
         reverse_index,
+        seven_bit_controls,
+        eight_bit_controls,
     ))
     .parse_next(input)
 }
@@ -128,4 +132,6 @@ mod tests {
     test_parser!(set_g1_alternate, "\u{1b})1");
     test_parser!(set_g0_graph, "\u{1b}(2");
     test_parser!(set_g1_graph, "\u{1b})2");
+    test_parser!(seven_bit_controls, "\u{1b} F");
+    test_parser!(eight_bit_controls, "\u{1b} G");
 }
@@ -1,8 +1,8 @@
 use crate::ansi_parser::{AnsiSequence, OSCSequence};
 
-use winnow::combinator::{alt, preceded};
+use winnow::combinator::{alt, delimited, preceded};
 use winnow::error::InputError;
-use winnow::token::literal;
+use winnow::token::{literal, take_till};
 use winnow::{PResult, Parser};
 
 macro_rules! tag_parser {
@@ -15,8 +15,31 @@ macro_rules! tag_parser {
 
 tag_parser!(reset_text_cursor_color, "112\u{7}", OSCSequence::ResetCursorColor);
 
+// Sequences end with BEL, or with the string terminator ESC \
+fn terminator<'s>(input: &mut &'s str) -> PResult<&'s str, InputError<&'s str>> {
+    alt(("\u{7}", "\u{1b}\\")).parse_next(input)
+}
+
+fn set_text_cursor_color<'s>(input: &mut &'s str) -> PResult<OSCSequence, InputError<&'s str>> {
+    delimited("12;", take_till(1.., ['\u{7}', '\u{1b}']), terminator)
+        .map(|color: &str| OSCSequence::SetCursorColor(color.to_string()))
+        .parse_next(input)
+}
+
+fn set_title<'s>(input: &mut &'s str) -> PResult<OSCSequence, InputError<&'s str>> {
+    delimited(alt(("0;", "2;")), take_till(0.., ['\u{7}', '\u{1b}']), terminator)
+        .map(|title: &str| OSCSequence::SetTitle(title.to_string()))
+        .parse_next(input)
+}
+
+fn set_encoding<'s>(input: &mut &'s str) -> PResult<OSCSequence, InputError<&'s str>> {
+    delimited("701;", take_till(1.., ['\u{7}', '\u{1b}']), terminator)
+        .map(|encoding: &str| OSCSequence::SetEncoding(encoding.to_string()))
+        .parse_next(input)
+}
+
 fn combined<'s>(input: &mut &'s str) -> PResult<OSCSequence, InputError<&'s str>> {
-    alt([reset_text_cursor_color]).parse_next(input)
+    alt((set_title, reset_text_cursor_color, set_text_cursor_color, set_encoding)).parse_next(input)
 }
 
 pub fn parse_osc_sequence<'s>(input: &mut &'s str) -> PResult<AnsiSequence, InputError<&'s str>> {
@@ -24,3 +47,47 @@ pub fn parse_osc_sequence<'s>(input: &mut &'s str) -> PResult<AnsiSequence, Inpu
         .map(|a| AnsiSequence::OSC(a))
         .parse_next(input)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::ansi_parser::{parser::parse_sequence, AnsiSequence, OSCSequence};
+    use std::fmt::Write;
+
+    macro_rules! test_parser {
+        ($name:ident, $string:expr) => {
+            #[test]
+            fn $name() {
+                let mut buff = String::new();
+                let ret = parse_sequence($string);
+
+                assert!(ret.is_ok());
+                let ret = ret.unwrap().1;
+
+                write!(&mut buff, "{}", ret).unwrap();
+
+                assert_eq!(buff, $string);
+            }
+        };
+    }
+
+    test_parser!(reset_cursor_color, "\u{1b}]112\u{7}");
+    test_parser!(set_cursor_color, "\u{1b}]12;#ff8800\u{7}");
+    test_parser!(set_title, "\u{1b}]0;vim main.rs\u{7}");
+    test_parser!(set_encoding, "\u{1b}]701;en_US.ISO-8859-1\u{7}");
+
+    #[test]
+    fn osc_2_sets_the_title() {
+        let ret = parse_sequence("\u{1b}]2;~/src\u{1b}\\").unwrap();
+        assert_eq!(ret.1, AnsiSequence::OSC(OSCSequence::SetTitle("~/src".to_string())));
+    }
+
+    #[test]
+    fn string_terminator_ends_sequence() {
+        let ret = parse_sequence("\u{1b}]12;rgb:ff/88/00\u{1b}\\rest").unwrap();
+        assert_eq!(ret.0, "rest");
+        assert_eq!(
+            ret.1,
+            AnsiSequence::OSC(OSCSequence::SetCursorColor("rgb:ff/88/00".to_string()))
+        );
+    }
+}
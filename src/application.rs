@@ -1,195 +1,434 @@
+use std::{collections::BTreeMap, fs, path::PathBuf};
+
 use iced::{
-    keyboard::{self, key::Named, Key},
-    widget::{container, text, Column, Container, Row},
+    keyboard,
+    widget::{horizontal_space, pane_grid::Axis},
     window::Id,
-    Element, Subscription, Task,
+    Color, Element, Subscription, Task,
 };
 
 use crate::{
-    config::Config,
-    structs::{cell::Cell, cursor::Cursor, terminalsize::TerminalSize},
-    term::{colors::TerminalColor, term},
-    terminal::Terminal,
+    bindings::{Action, Binding, KeyCombo},
+    config::{watch, Config},
+    control::{
+        self,
+        protocol::{Request, Response, ScreenText, SessionInfo},
+        server::Command,
+    },
+    keys,
+    renderer::cursor,
+    session::Session,
+    term::term::{Shell, TermMessage},
+    terminal_window::TerminalWindow,
     window::{Window, WindowFocus},
     Message,
 };
 
+// Points the font size changes by per zoom step
+const ZOOM_STEP: f32 = 1.0;
+
 pub struct Application {
-    terminal: Terminal,
+    windows: BTreeMap<Id, TerminalWindow>,
     config: Config,
-    window: Window,
+    // Id of the next shell started, each needs a subscription of its own
+    next_id: usize,
 }
 
 impl Application {
-    pub fn new(config: Config) -> (Self, Task<Message>) {
-        let (window, window_task) = Window::main_window(config.window_config.clone());
-        let cols = (window.content_width() / config.cell_size.width) as usize;
-        let rows = (window.content_height() / config.cell_size.height) as usize;
-        let size = TerminalSize { cols, rows };
-
-        (
-            Self {
-                terminal: Terminal::new(size),
-                config,
-                window,
-            },
-            window_task.map(|id| Message::WindowCreated(id)),
-        )
+    // The first window shows the session server's session `attach` if given
+    pub fn new(config: Config, attach: Option<u64>) -> (Self, Task<Message>) {
+        let mut application = Self {
+            windows: BTreeMap::new(),
+            config,
+            next_id: 0,
+        };
+        let task = application.open_window(attach);
+        (application, task)
+    }
+
+    fn open_window(&mut self, attach: Option<u64>) -> Task<Message> {
+        let (window, task) = Window::open(self.config.window_config.clone());
+        let id = self.next_id();
+        let window = TerminalWindow::new(window, id, attach, &self.config);
+        self.windows.insert(window.id(), window);
+        task.map(Message::WindowCreated)
+    }
+
+    // A window that is closing has nothing left to show
+    pub fn view(&self, window: Id) -> Element<'_, Message> {
+        match self.windows.get(&window) {
+            Some(window) => window.view(&self.config),
+            None => horizontal_space().into(),
+        }
+    }
+
+    pub fn title(&self, window: Id) -> String {
+        match self.windows.get(&window) {
+            Some(window) => window.title(&self.config),
+            None => self.config.title.clone(),
+        }
+    }
+
+    pub fn background(&self) -> Color {
+        self.config.palette.background
+    }
+
+    fn next_id(&mut self) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        id
+    }
+
+    // Actions starting shells or changing the font are handled here, the rest by the window
+    fn perform(&mut self, window: Id, action: Action) -> Task<Message> {
+        match action {
+            Action::ZoomIn => self.zoom(self.config.font_size + ZOOM_STEP),
+            Action::ZoomOut => self.zoom(self.config.font_size - ZOOM_STEP),
+            Action::ResetZoom => self.zoom(self.config.default_font_size),
+            Action::NewWindow => self.open_window(None),
+            Action::NewTab => {
+                let id = self.next_id();
+                self.with_window(window, |window, config| window.new_tab(id, None, config))
+            }
+            Action::SplitRight | Action::SplitDown => {
+                let axis = match action {
+                    Action::SplitRight => Axis::Vertical,
+                    _ => Axis::Horizontal,
+                };
+                let id = self.next_id();
+                self.with_window(window, |window, config| window.split(axis, id, None, config))
+            }
+            action => self.with_window(window, |window, config| window.perform(action, config)),
+        }
+    }
+
+    // Events can still arrive for a window that was just closed
+    fn with_window(
+        &mut self,
+        window: Id,
+        update: impl FnOnce(&mut TerminalWindow, &Config) -> Task<Message>,
+    ) -> Task<Message> {
+        match self.windows.get_mut(&window) {
+            Some(window) => update(window, &self.config),
+            None => Task::none(),
+        }
     }
 
-    pub fn view(&self, _window: Id) -> Element<'_, Message> {
-        let buffer = self.terminal.buffer();
-        Column::with_children(
-            buffer
-                .iter_rows()
-                .enumerate()
-                .map(|(y, row)| {
-                    Row::with_children(
-                        row.iter()
-                            .enumerate()
-                            .map(|(x, cell)| {
-                                cell_view(&buffer.cursor, x, y, cell, self.config.font_size)
-                                    .width(self.config.cell_size.width)
-                                    .height(self.config.cell_size.height)
-                                    .into()
-                            })
-                            .collect::<Vec<_>>(),
-                    )
-                    .into()
-                })
+    fn each_window(&mut self, update: impl Fn(&mut TerminalWindow, &Config) -> Task<Message>) -> Task<Message> {
+        Task::batch(
+            self.windows
+                .values_mut()
+                .map(|window| update(window, &self.config))
                 .collect::<Vec<_>>(),
         )
-        .padding(self.window.padding)
-        .into()
+    }
+
+    fn zoom(&mut self, font_size: f32) -> Task<Message> {
+        self.config.set_font_size(font_size);
+        self.each_window(TerminalWindow::set_shaper)
+    }
+
+    fn key_pressed(
+        &mut self,
+        window: Id,
+        key: keyboard::Key,
+        modifiers: keyboard::Modifiers,
+        text: Option<String>,
+    ) -> Task<Message> {
+        let Some(terminal_window) = self.windows.get_mut(&window) else {
+            return Task::none();
+        };
+        terminal_window.reset_blink();
+        let session = terminal_window.session();
+        // A held pane starts a new shell with Enter
+        if session.ended && key == keyboard::Key::Named(keyboard::key::Named::Enter) {
+            let id = self.next_id();
+            return self.with_window(window, |window, config| window.restart(id, config));
+        }
+        let modes = session.terminal.modes();
+        let binding = match self.config.bindings.lookup(&key, modifiers, modes) {
+            Some(Binding::Action(action)) if !terminal_window.handles(*action) => None,
+            binding => binding.cloned(),
+        };
+        if session.search.is_some() {
+            return match binding {
+                Some(Binding::Action(action)) => self.perform(window, action),
+                _ => terminal_window.search_key(&key, text.as_deref()),
+            };
+        }
+        match binding {
+            Some(Binding::Action(action)) => self.perform(window, action),
+            Some(Binding::Bytes(bytes)) => terminal_window.send_input(bytes),
+            None => match keys::encode(&key, modifiers, text.as_deref(), modes) {
+                Some(bytes) => terminal_window.send_input(bytes),
+                None => Task::none(),
+            },
+        }
+    }
+
+    // The window closed, dropping its sessions hangs up their shells. The last window takes the
+    // application with it.
+    fn window_closed(&mut self, window: Id) -> Task<Message> {
+        self.windows.remove(&window);
+        match self.windows.is_empty() {
+            true => {
+                if let Ok(path) = control::socket_path() {
+                    let _ = fs::remove_file(path);
+                }
+                iced::exit()
+            }
+            false => Task::none(),
+        }
+    }
+
+    // Requests from `term msg`, answered right away
+    fn control(&mut self, command: Command) -> Task<Message> {
+        let (response, task) = match self.control_request(command.request.clone()) {
+            Ok(handled) => handled,
+            Err(message) => (Response::Failed(message), Task::none()),
+        };
+        command.reply(response);
+        task
+    }
+
+    fn control_request(&mut self, request: Request) -> Result<(Response, Task<Message>), String> {
+        match request {
+            Request::List => Ok((Response::Sessions(self.list_sessions()), Task::none())),
+            Request::SendText { session, text } => {
+                let session = self.target(session)?;
+                session.scroll_to_bottom();
+                let task = session.terminal.send(TermMessage::Bytes(text.into_bytes()));
+                Ok((Response::Done, task))
+            }
+            Request::SendKeys { session, keys } => {
+                let session = self.target(session)?;
+                let modes = session.terminal.modes();
+                let mut bytes = Vec::new();
+                for key in &keys {
+                    let combo =
+                        KeyCombo::parse(key).map_err(|message| format!("`{}` is not a key, {}", key, message))?;
+                    bytes.extend(combo.encode(modes).ok_or_else(|| format!("`{}` sends nothing", key))?);
+                }
+                session.scroll_to_bottom();
+                Ok((Response::Done, session.terminal.send(TermMessage::Bytes(bytes))))
+            }
+            Request::SetTitle { session, title } => {
+                self.target(session)?.terminal.set_title(title);
+                Ok((Response::Done, Task::none()))
+            }
+            Request::NewTab {
+                session,
+                command,
+                working_directory,
+            } => {
+                let (window, _) = self.target_window(session)?;
+                let shell = self.shell(command, working_directory);
+                let id = self.next_id();
+                let task = self.with_window(window, |window, config| window.new_tab(id, shell, config));
+                Ok((Response::Opened(id), task))
+            }
+            Request::Split {
+                session,
+                down,
+                command,
+                working_directory,
+            } => {
+                let (window, target) = self.target_window(session)?;
+                let axis = if down { Axis::Horizontal } else { Axis::Vertical };
+                let shell = self.shell(command, working_directory);
+                let id = self.next_id();
+                let task = self.with_window(window, |window, config| {
+                    let focus = window.focus_session(target, config);
+                    Task::batch([focus, window.split(axis, id, shell, config)])
+                });
+                Ok((Response::Opened(id), task))
+            }
+            Request::GetText { session, scrollback } => {
+                let default_title = self.config.title.clone();
+                let terminal = &self.target(session)?.terminal;
+                let cursor = terminal.buffer().cursor;
+                let text = ScreenText {
+                    lines: terminal.text(scrollback),
+                    cursor: (cursor.row, cursor.col),
+                    title: terminal.title().unwrap_or(&default_title).to_string(),
+                };
+                Ok((Response::Text(text), Task::none()))
+            }
+        }
+    }
+
+    fn list_sessions(&self) -> Vec<SessionInfo> {
+        let mut sessions = Vec::new();
+        for (index, window) in self.windows.values().enumerate() {
+            let tabs = window.tabs();
+            for (tab, panes) in tabs.iter().enumerate() {
+                let focused = window.is_focused() && tab == tabs.active_index();
+                sessions.extend(panes.iter().map(|session| SessionInfo {
+                    id: session.id,
+                    window: index,
+                    tab,
+                    title: session.terminal.title().unwrap_or(&self.config.title).to_string(),
+                    focused: focused && session.id == panes.focused().id,
+                }));
+            }
+        }
+        sessions
+    }
+
+    // The window with the session `id` in it, or the focused window and its focused session
+    fn target_window(&self, session: Option<usize>) -> Result<(Id, usize), String> {
+        match session {
+            Some(id) => self
+                .windows
+                .values()
+                .find(|window| window.has_session(id))
+                .map(|window| (window.id(), id))
+                .ok_or_else(|| format!("there is no session {}", id)),
+            None => {
+                let mut windows = self.windows.values();
+                let window = windows
+                    .clone()
+                    .find(|window| window.is_focused())
+                    .or_else(|| windows.next())
+                    .ok_or_else(|| "there is no window".to_string())?;
+                Ok((window.id(), window.session().id))
+            }
+        }
+    }
+
+    fn target(&mut self, session: Option<usize>) -> Result<&mut Session, String> {
+        let (window, id) = self.target_window(session)?;
+        let window = self.windows.get_mut(&window).expect("The window was just found");
+        Ok(window.session_with_id_mut(id).expect("The session was just found"))
+    }
+
+    // The configured shell, or `command` in its place, started in `working_directory` if given
+    fn shell(&self, command: Vec<String>, working_directory: Option<PathBuf>) -> Option<Shell> {
+        if command.is_empty() && working_directory.is_none() {
+            return None;
+        }
+        let mut shell = self.config.shell();
+        let mut command = command.into_iter();
+        if let Some(program) = command.next() {
+            shell.program = program;
+            shell.args = command.collect();
+        }
+        if working_directory.is_some() {
+            shell.working_directory = working_directory;
+        }
+        Some(shell)
     }
 
     pub fn update(&mut self, message: Message) -> Task<Message> {
         match message {
-            Message::KeyboardBytes(bytes) => self.terminal.send(term::TermMessage::Bytes(bytes)),
-            Message::TerminalInput => Task::none(),
-            Message::TerminalOutput(term_event) => self.terminal.parse(term_event),
-            Message::WindowCreated(_id) => Task::none(),
-            Message::WindowResized(size) => {
-                let current_cols = (self.window.content_width() / self.config.cell_size.width) as usize;
-                let current_rows = (self.window.content_height() / self.config.cell_size.height) as usize;
-                let new_cols = ((size.width - self.window.padding.horizontal()) / self.config.cell_size.width) as usize;
-                let new_rows = ((size.height - self.window.padding.vertical()) / self.config.cell_size.height) as usize;
-                if new_cols != current_cols || new_rows != current_rows {
-                    let new_size = TerminalSize::new(new_cols, new_rows);
-                    self.window.resize(size);
-                    self.terminal.resize(new_size)
-                } else {
+            Message::KeyPressed(window, key, modifiers, text) => self.key_pressed(window, key, modifiers, text),
+            Message::Paste(window, text) => match text {
+                Some(text) => self.with_window(window, |window, _| window.paste(&text)),
+                None => Task::none(),
+            },
+            Message::Mouse(window, event) => self.with_window(window, |window, config| window.mouse(event, config)),
+            Message::ConfigChanged => match self.config.reload() {
+                Ok(config) => {
+                    self.config = config;
+                    self.each_window(TerminalWindow::apply_config)
+                }
+                Err(error) => {
+                    eprintln!("{}", error);
                     Task::none()
                 }
-            }
-            Message::WindowFocus(focus) => match focus {
-                WindowFocus::Focus => self.terminal.focus(),
-                WindowFocus::Unfocus => self.terminal.unfocus(),
             },
+            Message::TerminalInput => Task::none(),
+            Message::TerminalOutput(id, event) => {
+                match self.windows.values_mut().find(|window| window.has_session(id)) {
+                    Some(window) => window.output(id, event, &self.config),
+                    // Output still on its way from a closed pane
+                    None => Task::none(),
+                }
+            }
+            Message::SelectTab(window, index) => {
+                self.with_window(window, |window, config| window.select_tab(index, config))
+            }
+            Message::CloseTab(window, index) => {
+                self.with_window(window, |window, config| window.close_tab(index, config))
+            }
+            Message::PaneResized(window, event) => self.with_window(window, |window, config| {
+                window.set_ratio(event.split, event.ratio, config)
+            }),
+            Message::CursorBlink => {
+                for window in self.windows.values_mut() {
+                    window.toggle_blink();
+                }
+                Task::none()
+            }
+            // iced has no event for scale factor changes, but moving to another monitor
+            // resizes the window, so the scale factor is checked on every resize
+            Message::WindowCreated(id) => iced::window::get_scale_factor(id).map(Message::ScaleFactorChanged),
+            Message::WindowResized(window, size) => {
+                let scale_factor = iced::window::get_scale_factor(window).map(Message::ScaleFactorChanged);
+                let resize = self.with_window(window, |window, config| window.resize(size, config));
+                Task::batch([resize, scale_factor])
+            }
+            // The cells are as large on all windows, the one moved last decides
+            Message::ScaleFactorChanged(scale_factor) => {
+                if scale_factor == self.config.scale_factor {
+                    return Task::none();
+                }
+                self.config.set_scale_factor(scale_factor);
+                self.each_window(TerminalWindow::set_shaper)
+            }
+            Message::WindowFocus(window, focus) => self.with_window(window, |window, _| window.focus(focus)),
+            Message::WindowClosed(window) => self.window_closed(window),
+            Message::Control(command) => self.control(command),
         }
     }
 
     pub fn subscription(&self) -> Subscription<Message> {
-        let tmp = iced::event::listen_with(|event, _status, _window| match event {
+        let tmp = iced::event::listen_with(|event, _status, window| match event {
             iced::Event::Keyboard(event) => match event {
                 keyboard::Event::KeyPressed {
                     key,
                     modified_key: _,
                     physical_key: _,
                     location: _,
-                    modifiers: _,
+                    modifiers,
                     text,
-                } => {
-                    if let Some(char) = text {
-                        Some(Message::KeyboardBytes(char.as_bytes().to_vec()))
-                    } else if let Key::Named(k) = key {
-                        match k {
-                            Named::ArrowUp => Some(Message::KeyboardBytes("\x1b[A".into())),
-                            Named::ArrowDown => Some(Message::KeyboardBytes("\x1b[B".into())),
-                            Named::ArrowRight => Some(Message::KeyboardBytes("\x1b[C".into())),
-                            Named::ArrowLeft => Some(Message::KeyboardBytes("\x1b[D".into())),
-                            _ => None,
-                        }
-                    } else {
-                        None
-                    }
-                }
+                } => Some(Message::KeyPressed(
+                    window,
+                    key,
+                    modifiers,
+                    text.map(|text| text.to_string()),
+                )),
 
                 // We dont care about KeyReleased and ModifiersChanged
                 _ => None,
             },
-            iced::Event::Mouse(_event) => None,
+            iced::Event::Mouse(event) => Some(Message::Mouse(window, event)),
             iced::Event::Window(event) => match event {
-                iced::window::Event::Resized(size) => Some(Message::WindowResized(size)),
-                iced::window::Event::Focused => Some(Message::WindowFocus(WindowFocus::Focus)),
-                iced::window::Event::Unfocused => Some(Message::WindowFocus(WindowFocus::Unfocus)),
+                iced::window::Event::Resized(size) => Some(Message::WindowResized(window, size)),
+                iced::window::Event::Focused => Some(Message::WindowFocus(window, WindowFocus::Focus)),
+                iced::window::Event::Unfocused => Some(Message::WindowFocus(window, WindowFocus::Unfocus)),
+                iced::window::Event::Closed => Some(Message::WindowClosed(window)),
                 _ => None,
             },
             iced::Event::Touch(_event) => None,
         });
-        let term_sub =
-            Subscription::run_with_id(12345, term::Term::spawn(self.terminal.winsize())).map(Message::TerminalOutput);
-        iced::Subscription::batch([tmp, term_sub])
-    }
-}
-
-fn cell_view<'a>(cursor: &Cursor, x: usize, y: usize, cell: &Cell, font_size: f32) -> Container<'a, Message> {
-    let mut container_style = container::Style {
-        // TODO: Do I really need to clone here?
-        text_color: Some(cell.style.clone().foreground_color().foreground_color()),
-        background: Some(iced::Background::Color(
-            cell.style.clone().background_color().background_color(),
-        )),
-        border: iced::Border::default()
-            .width(0.0)
-            .color(TerminalColor::Cyan.foreground_color()),
-        ..Default::default()
-    };
-
-    if cursor.col == x && cursor.row == y {
-        use crate::structs::cursor::CursorStyle::*;
-        match cursor.style {
-            BlinkingBlock | SteadyBlock => {
-                container_style.text_color = Some(TerminalColor::Black.foreground_color());
-                container_style.background = Some(iced::Background::Color(TerminalColor::White.background_color()));
-            }
-            BlinkingUnderline | SteadyUnderline => {
-                let gradient = underline_cursor();
-                container_style.background = Some(iced::Background::Gradient(iced::Gradient::Linear(gradient)));
-            }
-            BlinkingBar | SteadyBar => {
-                let gradient = bar_cursor();
-                container_style.background = Some(iced::Background::Gradient(iced::Gradient::Linear(gradient)));
+        let sessions = self.windows.values().flat_map(TerminalWindow::sessions);
+        let term_subs = Subscription::batch(sessions.map(|session| session.subscription(&self.config)));
+        let blink_sub = if self.windows.values().any(TerminalWindow::cursor_blinks) {
+            Subscription::run_with_id("cursor-blink", cursor::blink_ticks()).map(|_| Message::CursorBlink)
+        } else {
+            Subscription::none()
+        };
+        let config_sub = match &self.config.path {
+            Some(path) => {
+                Subscription::run_with_id(path.clone(), watch::changes(path.clone())).map(|_| Message::ConfigChanged)
             }
+            None => Subscription::none(),
+        };
+        let control_sub = match self.config.allow_remote_control {
+            true => Subscription::run_with_id("control", control::server::listen()).map(Message::Control),
+            false => Subscription::none(),
         };
+        iced::Subscription::batch([tmp, term_subs, blink_sub, config_sub, control_sub])
     }
-    let text = text(cell.content.to_string()).size(font_size);
-
-    // TODO: Handle underline, strikethrough
-
-    container(text).style(move |_| {
-        container_style.border(
-            iced::Border::default()
-                .color(iced::Color::from_rgb(0.0, 1.0, 0.0))
-                .width(0.5),
-        )
-    })
-}
-
-// We fake underline cursor with a gradient to draw a line at the bottom of a cell
-fn underline_cursor() -> iced::gradient::Linear {
-    iced::gradient::Linear::new(0.0)
-        .add_stop(0.0, TerminalColor::White.background_color())
-        .add_stop(0.1, TerminalColor::White.background_color())
-        .add_stop(0.11, TerminalColor::Black.background_color())
-        .add_stop(1.0, TerminalColor::Black.background_color())
-}
-
-// We fake a bar cursor with a gradient to draw a line on the left side of a cell
-fn bar_cursor() -> iced::gradient::Linear {
-    iced::gradient::Linear::new(1.57079633) // PI / 2
-        .add_stop(0.0, TerminalColor::White.background_color())
-        .add_stop(0.1, TerminalColor::White.background_color())
-        .add_stop(0.1001, TerminalColor::Black.background_color())
-        .add_stop(1.0, TerminalColor::Black.background_color())
 }
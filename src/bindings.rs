@@ -0,0 +1,392 @@
+use iced::keyboard::{key::Named, Key, Modifiers};
+
+use crate::keys;
+
+/// Something a key combination can do instead of sending its bytes to the shell
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Copy,
+    Paste,
+    // Typed text is looked for in the scrollback until Escape, Enter goes to the match above
+    Search,
+    ZoomIn,
+    ZoomOut,
+    ResetZoom,
+    ScrollLineUp,
+    ScrollLineDown,
+    ScrollPageUp,
+    ScrollPageDown,
+    ScrollToTop,
+    ScrollToBottom,
+    NewWindow,
+    NewTab,
+    CloseTab,
+    NextTab,
+    PreviousTab,
+    MoveTabLeft,
+    MoveTabRight,
+    SplitRight,
+    SplitDown,
+    ClosePane,
+    FocusPaneLeft,
+    FocusPaneRight,
+    FocusPaneUp,
+    FocusPaneDown,
+    ResizePaneLeft,
+    ResizePaneRight,
+    ResizePaneUp,
+    ResizePaneDown,
+    ZoomPane,
+}
+
+impl Action {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "copy" => Some(Action::Copy),
+            "paste" => Some(Action::Paste),
+            "search" => Some(Action::Search),
+            "zoom_in" => Some(Action::ZoomIn),
+            "zoom_out" => Some(Action::ZoomOut),
+            "reset_zoom" => Some(Action::ResetZoom),
+            "scroll_line_up" => Some(Action::ScrollLineUp),
+            "scroll_line_down" => Some(Action::ScrollLineDown),
+            "scroll_page_up" => Some(Action::ScrollPageUp),
+            "scroll_page_down" => Some(Action::ScrollPageDown),
+            "scroll_to_top" => Some(Action::ScrollToTop),
+            "scroll_to_bottom" => Some(Action::ScrollToBottom),
+            "new_window" => Some(Action::NewWindow),
+            "new_tab" => Some(Action::NewTab),
+            "close_tab" => Some(Action::CloseTab),
+            "next_tab" => Some(Action::NextTab),
+            "previous_tab" => Some(Action::PreviousTab),
+            "move_tab_left" => Some(Action::MoveTabLeft),
+            "move_tab_right" => Some(Action::MoveTabRight),
+            "split_right" => Some(Action::SplitRight),
+            "split_down" => Some(Action::SplitDown),
+            "close_pane" => Some(Action::ClosePane),
+            "focus_pane_left" => Some(Action::FocusPaneLeft),
+            "focus_pane_right" => Some(Action::FocusPaneRight),
+            "focus_pane_up" => Some(Action::FocusPaneUp),
+            "focus_pane_down" => Some(Action::FocusPaneDown),
+            "resize_pane_left" => Some(Action::ResizePaneLeft),
+            "resize_pane_right" => Some(Action::ResizePaneRight),
+            "resize_pane_up" => Some(Action::ResizePaneUp),
+            "resize_pane_down" => Some(Action::ResizePaneDown),
+            "zoom_pane" => Some(Action::ZoomPane),
+            _ => None,
+        }
+    }
+}
+
+/// What a key combination is bound to
+#[derive(Debug, Clone, PartialEq)]
+pub enum Binding {
+    Action(Action),
+    // Sent to the shell as they are
+    Bytes(Vec<u8>),
+}
+
+/// Terminal modes that change what keys do
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Modes {
+    pub alt_screen: bool,
+    // Cursor keys send `ESC O` instead of `ESC [` sequences
+    pub app_cursor: bool,
+}
+
+/// Modes a binding is limited to, written like `alt_screen` or `~app_cursor` for when the
+/// mode is off. Modes left out don't matter.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct ModeFilter {
+    alt_screen: Option<bool>,
+    app_cursor: Option<bool>,
+}
+
+impl ModeFilter {
+    pub fn parse(names: &[String]) -> Result<Self, String> {
+        let mut filter = Self::default();
+        for name in names {
+            let (mode, on) = match name.strip_prefix('~') {
+                Some(mode) => (mode, false),
+                None => (name.as_str(), true),
+            };
+            match mode {
+                "alt_screen" => filter.alt_screen = Some(on),
+                "app_cursor" => filter.app_cursor = Some(on),
+                _ => return Err(format!("`{}` is not a mode", mode)),
+            }
+        }
+        Ok(filter)
+    }
+
+    fn matches(&self, modes: Modes) -> bool {
+        self.alt_screen.is_none_or(|on| on == modes.alt_screen)
+            && self.app_cursor.is_none_or(|on| on == modes.app_cursor)
+    }
+}
+
+/// A key together with the modifiers held down with it, written like `ctrl+shift+c`
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyCombo {
+    key: Key,
+    modifiers: Modifiers,
+}
+
+impl KeyCombo {
+    pub fn parse(combo: &str) -> Result<Self, String> {
+        let mut parts: Vec<&str> = combo.split('+').collect();
+        // `ctrl++` binds the plus key itself
+        if combo.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let (key, modifiers) = parts.split_last().ok_or_else(|| "it is empty".to_string())?;
+
+        let mut combined = Modifiers::empty();
+        for modifier in modifiers {
+            combined |= match modifier.to_lowercase().as_str() {
+                "ctrl" | "control" => Modifiers::CTRL,
+                "shift" => Modifiers::SHIFT,
+                "alt" | "option" => Modifiers::ALT,
+                "super" | "cmd" | "logo" => Modifiers::LOGO,
+                _ => return Err(format!("`{}` is not a modifier", modifier)),
+            };
+        }
+
+        let key = parse_key(key).ok_or_else(|| format!("`{}` is not a key", key))?;
+        Ok(Self {
+            key,
+            modifiers: combined,
+        })
+    }
+
+    // What pressing the combination sends to the shell, shift making letters capitals
+    pub fn encode(&self, modes: Modes) -> Option<Vec<u8>> {
+        let text = match &self.key {
+            Key::Character(character) if self.modifiers.shift() => Some(character.to_uppercase()),
+            _ => None,
+        };
+        keys::encode(&self.key, self.modifiers, text.as_deref(), modes)
+    }
+
+    // Characters typed with shift arrive already shifted, like `+` for shift and `=`, so shift
+    // only has to match when the combination asks for it
+    fn matches(&self, key: &Key, modifiers: Modifiers) -> bool {
+        let modifiers = match (&self.key, key) {
+            (Key::Character(_), Key::Character(_)) if !self.modifiers.shift() => modifiers - Modifiers::SHIFT,
+            _ => modifiers,
+        };
+        if modifiers != self.modifiers {
+            return false;
+        }
+        match (&self.key, key) {
+            (Key::Character(bound), Key::Character(pressed)) => bound.eq_ignore_ascii_case(pressed),
+            (bound, pressed) => bound == pressed,
+        }
+    }
+}
+
+/// Key combinations bound to actions or bytes, later bindings win over earlier ones. Keys
+/// without a binding are encoded by `keys::encode`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Bindings {
+    bindings: Vec<(KeyCombo, ModeFilter, Binding)>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        let mut bindings = Self { bindings: Vec::new() };
+        for (combo, mode, action) in [
+            ("ctrl+shift+c", "", Action::Copy),
+            ("ctrl+shift+v", "", Action::Paste),
+            ("shift+insert", "", Action::Paste),
+            ("ctrl+shift+f", "", Action::Search),
+            ("ctrl+=", "", Action::ZoomIn),
+            ("ctrl++", "", Action::ZoomIn),
+            ("ctrl+-", "", Action::ZoomOut),
+            ("ctrl+0", "", Action::ResetZoom),
+            // Full screen programs get these keys, there is no scrollback on the alternate screen
+            ("ctrl+shift+up", "~alt_screen", Action::ScrollLineUp),
+            ("ctrl+shift+down", "~alt_screen", Action::ScrollLineDown),
+            ("shift+pageup", "~alt_screen", Action::ScrollPageUp),
+            ("shift+pagedown", "~alt_screen", Action::ScrollPageDown),
+            ("shift+home", "~alt_screen", Action::ScrollToTop),
+            ("shift+end", "~alt_screen", Action::ScrollToBottom),
+            ("ctrl+shift+n", "", Action::NewWindow),
+            ("ctrl+shift+t", "", Action::NewTab),
+            ("ctrl+shift+w", "", Action::CloseTab),
+            ("ctrl+pagedown", "", Action::NextTab),
+            ("ctrl+tab", "", Action::NextTab),
+            ("ctrl+pageup", "", Action::PreviousTab),
+            ("ctrl+shift+tab", "", Action::PreviousTab),
+            ("ctrl+shift+pageup", "", Action::MoveTabLeft),
+            ("ctrl+shift+pagedown", "", Action::MoveTabRight),
+            ("ctrl+shift+e", "", Action::SplitRight),
+            ("ctrl+shift+o", "", Action::SplitDown),
+            // Closing the last pane closes the tab
+            ("ctrl+shift+x", "", Action::ClosePane),
+            // Without a pane that way, or a split to resize, these keys go to the shell
+            ("alt+left", "", Action::FocusPaneLeft),
+            ("alt+right", "", Action::FocusPaneRight),
+            ("alt+up", "", Action::FocusPaneUp),
+            ("alt+down", "", Action::FocusPaneDown),
+            ("alt+shift+left", "", Action::ResizePaneLeft),
+            ("alt+shift+right", "", Action::ResizePaneRight),
+            ("alt+shift+up", "", Action::ResizePaneUp),
+            ("alt+shift+down", "", Action::ResizePaneDown),
+            ("ctrl+shift+z", "", Action::ZoomPane),
+        ] {
+            let combo = KeyCombo::parse(combo).expect("Default key binding does not parse");
+            let modes: Vec<String> = mode.split_whitespace().map(str::to_string).collect();
+            let mode = ModeFilter::parse(&modes).expect("Default binding mode does not parse");
+            bindings.bind(combo, mode, Binding::Action(action));
+        }
+        bindings
+    }
+}
+
+impl Bindings {
+    // Replaces a binding of the same combination in the same modes
+    pub fn bind(&mut self, combo: KeyCombo, mode: ModeFilter, binding: Binding) {
+        self.bindings
+            .retain(|(bound, bound_mode, _)| *bound != combo || *bound_mode != mode);
+        self.bindings.push((combo, mode, binding));
+    }
+
+    pub fn lookup(&self, key: &Key, modifiers: Modifiers, modes: Modes) -> Option<&Binding> {
+        self.bindings
+            .iter()
+            .rev()
+            .find(|(combo, mode, _)| combo.matches(key, modifiers) && mode.matches(modes))
+            .map(|(_, _, binding)| binding)
+    }
+}
+
+fn parse_key(name: &str) -> Option<Key> {
+    let named = match name.to_lowercase().as_str() {
+        "up" => Named::ArrowUp,
+        "down" => Named::ArrowDown,
+        "left" => Named::ArrowLeft,
+        "right" => Named::ArrowRight,
+        "pageup" => Named::PageUp,
+        "pagedown" => Named::PageDown,
+        "home" => Named::Home,
+        "end" => Named::End,
+        "insert" => Named::Insert,
+        "delete" => Named::Delete,
+        "backspace" => Named::Backspace,
+        "enter" => Named::Enter,
+        "tab" => Named::Tab,
+        "escape" | "esc" => Named::Escape,
+        "space" => Named::Space,
+        "f1" => Named::F1,
+        "f2" => Named::F2,
+        "f3" => Named::F3,
+        "f4" => Named::F4,
+        "f5" => Named::F5,
+        "f6" => Named::F6,
+        "f7" => Named::F7,
+        "f8" => Named::F8,
+        "f9" => Named::F9,
+        "f10" => Named::F10,
+        "f11" => Named::F11,
+        "f12" => Named::F12,
+        _ if name.chars().count() == 1 => return Some(Key::Character(name.to_lowercase().into())),
+        _ => return None,
+    };
+    Some(Key::Named(named))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn combos_parse_modifiers_and_keys() {
+        let combo = KeyCombo::parse("Ctrl+Shift+C").unwrap();
+        assert_eq!(combo.modifiers, Modifiers::CTRL | Modifiers::SHIFT);
+        assert_eq!(combo.key, Key::Character("c".into()));
+        assert_eq!(KeyCombo::parse("ctrl++").unwrap().key, Key::Character("+".into()));
+        assert_eq!(KeyCombo::parse("alt+pageup").unwrap().key, Key::Named(Named::PageUp));
+        assert!(KeyCombo::parse("hyper+a").is_err());
+        assert!(KeyCombo::parse("ctrl+nope").is_err());
+    }
+
+    #[test]
+    fn combos_encode_like_pressed_keys() {
+        let encode = |combo: &str| KeyCombo::parse(combo).unwrap().encode(Modes::default());
+        assert_eq!(encode("ctrl+c"), Some(vec![0x03]));
+        assert_eq!(encode("shift+a"), Some(b"A".to_vec()));
+        assert_eq!(encode("alt+x"), Some(b"\x1bx".to_vec()));
+        assert_eq!(encode("enter"), Some(b"\r".to_vec()));
+    }
+
+    fn action(bindings: &Bindings, key: &Key, modifiers: Modifiers, modes: Modes) -> Option<Action> {
+        match bindings.lookup(key, modifiers, modes) {
+            Some(Binding::Action(action)) => Some(*action),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn shifted_characters_match_unshifted_combos() {
+        let bindings = Bindings::default();
+        let plus = Key::Character("+".into());
+        let modes = Modes::default();
+        assert_eq!(
+            action(&bindings, &plus, Modifiers::CTRL | Modifiers::SHIFT, modes),
+            Some(Action::ZoomIn)
+        );
+        assert_eq!(action(&bindings, &plus, Modifiers::empty(), modes), None);
+    }
+
+    #[test]
+    fn later_bindings_replace_earlier_ones() {
+        let mut bindings = Bindings::default();
+        let combo = KeyCombo::parse("ctrl+0").unwrap();
+        bindings.bind(combo, ModeFilter::default(), Binding::Action(Action::ZoomIn));
+        assert_eq!(
+            action(
+                &bindings,
+                &Key::Character("0".into()),
+                Modifiers::CTRL,
+                Modes::default()
+            ),
+            Some(Action::ZoomIn)
+        );
+    }
+
+    #[test]
+    fn tabs_and_panes_close_with_their_own_keys() {
+        let bindings = Bindings::default();
+        let shifted = Modifiers::CTRL | Modifiers::SHIFT;
+        let close = |key: &str| action(&bindings, &Key::Character(key.into()), shifted, Modes::default());
+        assert_eq!(close("w"), Some(Action::CloseTab));
+        assert_eq!(close("x"), Some(Action::ClosePane));
+        assert_eq!(close("q"), None);
+    }
+
+    #[test]
+    fn bindings_apply_in_their_modes_only() {
+        let mut bindings = Bindings::default();
+        let page_up = Key::Named(Named::PageUp);
+        let alt_screen = Modes {
+            alt_screen: true,
+            ..Modes::default()
+        };
+        assert_eq!(
+            action(&bindings, &page_up, Modifiers::SHIFT, Modes::default()),
+            Some(Action::ScrollPageUp)
+        );
+        assert_eq!(bindings.lookup(&page_up, Modifiers::SHIFT, alt_screen), None);
+
+        let mode = ModeFilter::parse(&["alt_screen".to_string()]).unwrap();
+        let combo = KeyCombo::parse("shift+pageup").unwrap();
+        bindings.bind(combo, mode, Binding::Bytes(b"\x1b[5~".to_vec()));
+        assert_eq!(
+            bindings.lookup(&page_up, Modifiers::SHIFT, alt_screen),
+            Some(&Binding::Bytes(b"\x1b[5~".to_vec()))
+        );
+        assert!(ModeFilter::parse(&["~vi".to_string()]).is_err());
+    }
+}
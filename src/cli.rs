@@ -0,0 +1,363 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use toml_edit::Value;
+
+use crate::{
+    config::file::{ConfigError, Settings},
+    control::protocol::Request,
+    term::{
+        backend::{BackendKind, Parity},
+        term::Shell,
+    },
+};
+
+/// Options from the command line. Everything but `--config` overrides the config file.
+#[derive(Debug, Parser)]
+#[command(name = "term", version, about = "A terminal emulator")]
+pub struct Options {
+    /// Read this config file instead of the one in the config directory
+    #[arg(long, value_name = "FILE")]
+    pub config: Option<PathBuf>,
+
+    /// Override one config setting, like `font.size=16` or `colors.theme=dracula`
+    #[arg(short = 'o', long = "option", value_name = "KEY=VALUE")]
+    pub options: Vec<String>,
+
+    /// Start the shell in this directory
+    #[arg(long, value_name = "DIR")]
+    pub working_directory: Option<PathBuf>,
+
+    /// Window title
+    #[arg(short, long)]
+    pub title: Option<String>,
+
+    /// Window class on X11, application id on Wayland
+    #[arg(long)]
+    pub class: Option<String>,
+
+    /// Keep the window open after the shell exits
+    #[arg(long)]
+    pub hold: bool,
+
+    /// Run shells in the session server, so they keep running after their window closes
+    #[arg(long)]
+    pub mux: bool,
+
+    /// Show a session running in the session server, see `--list-sessions`
+    #[arg(long, value_name = "ID")]
+    pub attach: Option<u64>,
+
+    /// List the sessions running in the session server and exit
+    #[arg(long, conflicts_with = "server")]
+    pub list_sessions: bool,
+
+    /// Run the program on plain pipes instead of a PTY
+    #[arg(long, conflicts_with = "serial")]
+    pub pipe: bool,
+
+    /// Open this serial device instead of running a program, like `/dev/ttyUSB0`
+    #[arg(long, value_name = "DEVICE")]
+    pub serial: Option<PathBuf>,
+
+    /// Baud rate of the serial device
+    #[arg(long, value_name = "RATE")]
+    pub baud: Option<u32>,
+
+    /// Parity of the serial device: none, even or odd
+    #[arg(long, value_name = "PARITY", value_parser = parse_parity)]
+    pub parity: Option<Parity>,
+
+    /// Run the session server instead of a window, windows start it when they need it
+    #[arg(long)]
+    pub server: bool,
+
+    /// Run this command with its arguments instead of the shell, must come last
+    #[arg(
+        short = 'e',
+        long = "command",
+        value_name = "COMMAND",
+        num_args = 1..,
+        trailing_var_arg = true,
+        allow_hyphen_values = true
+    )]
+    pub command: Vec<String>,
+
+    #[command(subcommand)]
+    pub subcommand: Option<Command>,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum Command {
+    /// Control a running terminal, one with `allow_remote_control = true` in its config
+    Msg(MsgOptions),
+}
+
+/// `term msg`, sent to the terminal it runs in, or the only one running elsewhere
+#[derive(Debug, Args)]
+pub struct MsgOptions {
+    /// The control socket of the terminal, $TERM_CONTROL_SOCKET by default
+    #[arg(long, value_name = "SOCKET")]
+    pub to: Option<PathBuf>,
+
+    /// The session to act on, the focused one by default. See `term msg list` for the ids.
+    #[arg(long, global = true, value_name = "ID")]
+    pub session: Option<usize>,
+
+    /// Print sessions and text as JSON
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub request: MsgRequest,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum MsgRequest {
+    /// List the sessions of every window
+    List,
+    /// Type text into the session
+    SendText { text: String },
+    /// Press keys in the session, written like `ctrl+c` or `enter`
+    SendKeys {
+        #[arg(required = true)]
+        keys: Vec<String>,
+    },
+    /// Set the session's title, until its program sets another one
+    SetTitle { title: String },
+    /// Open a tab in the session's window, running COMMAND instead of the shell if given
+    NewTab {
+        #[command(flatten)]
+        command: MsgCommand,
+    },
+    /// Split the session's pane, running COMMAND in the new pane if given
+    Split {
+        /// Put the new pane below instead of to the right
+        #[arg(long)]
+        down: bool,
+        #[command(flatten)]
+        command: MsgCommand,
+    },
+    /// Print the text on the session's screen
+    GetText {
+        /// Include the rows scrolled off the screen
+        #[arg(long)]
+        scrollback: bool,
+    },
+}
+
+/// A program started by `term msg`, in place of the shell
+#[derive(Debug, Args)]
+pub struct MsgCommand {
+    /// Start in this directory
+    #[arg(long, value_name = "DIR")]
+    working_directory: Option<PathBuf>,
+
+    #[arg(value_name = "COMMAND", trailing_var_arg = true, allow_hyphen_values = true)]
+    command: Vec<String>,
+}
+
+impl MsgOptions {
+    pub fn request(&self) -> Request {
+        let session = self.session;
+        match &self.request {
+            MsgRequest::List => Request::List,
+            MsgRequest::SendText { text } => Request::SendText {
+                session,
+                text: text.clone(),
+            },
+            MsgRequest::SendKeys { keys } => Request::SendKeys {
+                session,
+                keys: keys.clone(),
+            },
+            MsgRequest::SetTitle { title } => Request::SetTitle {
+                session,
+                title: title.clone(),
+            },
+            MsgRequest::NewTab { command } => Request::NewTab {
+                session,
+                command: command.command.clone(),
+                working_directory: command.working_directory.clone(),
+            },
+            MsgRequest::Split { down, command } => Request::Split {
+                session,
+                down: *down,
+                command: command.command.clone(),
+                working_directory: command.working_directory.clone(),
+            },
+            MsgRequest::GetText { scrollback } => Request::GetText {
+                session,
+                scrollback: *scrollback,
+            },
+        }
+    }
+}
+
+impl Options {
+    // The settings the options override, `-o` ones first so the dedicated options win
+    pub fn settings(&self) -> Result<Settings, ConfigError> {
+        let mut settings = Settings::default();
+        for option in &self.options {
+            settings = settings.merge(override_setting(option)?);
+        }
+        let mut command = self.command.iter().cloned();
+        if let Some(program) = command.next() {
+            settings.shell = Some(Shell::new(program, command.collect()));
+        }
+        if self.working_directory.is_some() {
+            settings.working_directory = self.working_directory.clone();
+        }
+        if self.title.is_some() {
+            settings.title = self.title.clone();
+        }
+        if self.class.is_some() {
+            settings.class = self.class.clone();
+        }
+        if self.hold {
+            settings.hold = Some(true);
+        }
+        if self.mux || self.attach.is_some() {
+            settings.mux = Some(true);
+        }
+        if self.pipe {
+            settings.backend = Some(BackendKind::Pipe);
+        }
+        if self.serial.is_some() {
+            settings.backend = BackendKind::from_name("serial");
+            settings.serial_device = self.serial.clone();
+        }
+        if self.baud.is_some() {
+            settings.baud = self.baud;
+        }
+        if self.parity.is_some() {
+            settings.parity = self.parity;
+        }
+        Ok(settings)
+    }
+}
+
+fn parse_parity(name: &str) -> Result<Parity, String> {
+    Parity::from_name(name).ok_or_else(|| {
+        let known = Parity::names().collect::<Vec<_>>().join(", ");
+        format!("should be one of {}", known)
+    })
+}
+
+// Reads `key=value` as a line of the config file. Values that are not TOML are taken as
+// strings, so `colors.theme=dracula` needs no quotes.
+fn override_setting(option: &str) -> Result<Settings, ConfigError> {
+    let source = || PathBuf::from(format!("-o {}", option));
+    let Some((key, value)) = option.split_once('=') else {
+        return Err(ConfigError::Invalid {
+            path: source(),
+            line: None,
+            key: option.to_string(),
+            message: "should be written as KEY=VALUE".to_string(),
+        });
+    };
+    let (key, value) = (key.trim(), value.trim());
+    let setting = match Settings::parse(&format!("{} = {}", key, value), source()) {
+        Err(ConfigError::Syntax(..)) => Settings::parse(&format!("{} = {}", key, Value::from(value)), source()),
+        setting => setting,
+    };
+    // There is only the one line, pointing at it says nothing
+    setting.map_err(|error| match error {
+        ConfigError::Invalid { path, key, message, .. } => ConfigError::Invalid {
+            path,
+            line: None,
+            key,
+            message,
+        },
+        error => error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &[&str]) -> Options {
+        Options::try_parse_from(["term"].iter().chain(args)).unwrap()
+    }
+
+    #[test]
+    fn command_takes_the_rest_of_the_arguments() {
+        let settings = options(&["--hold", "-e", "htop", "-d", "10"]).settings().unwrap();
+        let shell = settings.shell.unwrap();
+        assert_eq!(shell.program, "htop");
+        assert_eq!(shell.args, vec!["-d".to_string(), "10".to_string()]);
+        assert_eq!(settings.hold, Some(true));
+    }
+
+    #[test]
+    fn msg_takes_a_request_with_its_session() {
+        let options = options(&["msg", "--session", "3", "split", "--down", "htop", "-d", "10"]);
+        let Some(Command::Msg(msg)) = options.subcommand else {
+            panic!("no msg subcommand");
+        };
+        assert_eq!(
+            msg.request(),
+            Request::Split {
+                session: Some(3),
+                down: true,
+                command: vec!["htop".to_string(), "-d".to_string(), "10".to_string()],
+                working_directory: None,
+            }
+        );
+    }
+
+    #[test]
+    fn attaching_runs_in_the_session_server() {
+        let options = options(&["--attach", "3"]);
+        assert_eq!(options.attach, Some(3));
+        assert_eq!(options.settings().unwrap().mux, Some(true));
+    }
+
+    #[test]
+    fn serial_device_replaces_the_shell() {
+        let settings = options(&["--serial", "/dev/ttyUSB1", "--baud", "9600", "--parity", "odd"])
+            .settings()
+            .unwrap();
+        assert!(matches!(settings.backend, Some(BackendKind::Serial(_))));
+        assert_eq!(settings.serial_device, Some(PathBuf::from("/dev/ttyUSB1")));
+        assert_eq!(settings.baud, Some(9600));
+        assert_eq!(settings.parity, Some(Parity::Odd));
+
+        assert!(Options::try_parse_from(["term", "--parity", "mark"]).is_err());
+        assert!(Options::try_parse_from(["term", "--pipe", "--serial", "/dev/ttyS0"]).is_err());
+    }
+
+    #[test]
+    fn options_override_single_settings() {
+        let settings = options(&[
+            "-o",
+            "font.size=16",
+            "-o",
+            "colors.theme=dracula",
+            "-o",
+            "window.title=a",
+        ])
+        .settings()
+        .unwrap();
+        assert_eq!(settings.font_size, Some(16.0));
+        assert!(settings.theme.is_some());
+        assert_eq!(settings.title.as_deref(), Some("a"));
+
+        let settings = options(&["-o", "window.title=a", "--title", "b"]).settings().unwrap();
+        assert_eq!(settings.title.as_deref(), Some("b"));
+    }
+
+    #[test]
+    fn bad_overrides_are_reported() {
+        let error = options(&["-o", "font.size=big"]).settings().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "-o font.size=big: `font.size` should be a positive number, found string"
+        );
+        let error = options(&["-o", "scrollback"]).settings().unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "-o scrollback: `scrollback` should be written as KEY=VALUE"
+        );
+    }
+}
@@ -0,0 +1,741 @@
+use std::{
+    fmt, fs, io,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+};
+
+use iced::{Color, Padding};
+use toml_edit::{ImDocument, Item, TableLike};
+
+use crate::{
+    bindings::{Action, Binding, KeyCombo, ModeFilter},
+    palette::{import, Palette},
+    term::{
+        backend::{BackendKind, FlowControl, Parity},
+        colors::parse_color_spec,
+        encoding::Encoding,
+        term::Shell,
+    },
+};
+
+/// Settings read from the config file. Every setting is optional, anything left out keeps
+/// its default.
+///
+/// ```toml
+/// scrollback = 10000
+/// # Let `term msg` in the shells, or anything else running as you, control the terminal
+/// allow_remote_control = false
+///
+/// [font]
+/// family = "Iosevka"
+/// fallbacks = ["Symbols Nerd Font Mono"]
+/// size = 14
+/// ligatures = true
+///
+/// [window]
+/// width = 1024
+/// height = 726
+/// padding = { top = 25, left = 5, bottom = 5, right = 5 }
+/// title = "Terminal"
+/// # Window class on X11, application id on Wayland
+/// class = "term"
+///
+/// [colors]
+/// # A built-in theme, a file in `themes/` next to this one, or a path to an Alacritty,
+/// # iTerm2, Windows Terminal or base16 scheme
+/// theme = "dracula"
+/// background = "#1e1e2d"
+/// foreground = "#ffffff"
+/// cursor = "#b8c0e0"
+/// selection = "#5b6078"
+///
+/// [shell]
+/// program = "/bin/zsh"
+/// args = ["--login"]
+/// working_directory = "/home/me/src"
+/// # What the shell writes text in, utf-8 or a legacy encoding like iso-8859-1 or cp437
+/// encoding = "utf-8"
+/// # Read bytes 0x80 to 0x9f as 8-bit controls, like 0x9b for `ESC [`
+/// c1_controls = false
+/// # Keep a pane open after its shell exits, instead of closing it
+/// hold = false
+/// # Run shells in the session server, they keep running after their window closes
+/// mux = false
+/// # What sessions talk to: "pty", "pipe" for the program on plain pipes, or "serial" for the
+/// # device in [serial] instead of a program
+/// backend = "pty"
+///
+/// [serial]
+/// device = "/dev/ttyUSB0"
+/// baud = 115200
+/// # none, even or odd
+/// parity = "none"
+/// data_bits = 8
+/// stop_bits = 1
+/// # none, hardware (RTS/CTS) or software (XON/XOFF)
+/// flow_control = "none"
+///
+/// [keybindings]
+/// "ctrl+=" = "zoom_in"
+/// # Bytes for the shell, and bindings that only apply in some modes
+/// "ctrl+shift+l" = { chars = "clear\r" }
+/// "shift+pageup" = [{ action = "scroll_page_up", mode = "~alt_screen" }, { chars = "\u001b[5;2~" }]
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub font_family: Option<String>,
+    pub font_fallbacks: Option<Vec<String>>,
+    pub font_size: Option<f32>,
+    pub ligatures: Option<bool>,
+    pub window_width: Option<f32>,
+    pub window_height: Option<f32>,
+    pub padding: Option<Padding>,
+    pub title: Option<String>,
+    pub class: Option<String>,
+    pub theme: Option<Palette>,
+    pub background: Option<Color>,
+    pub foreground: Option<Color>,
+    pub cursor: Option<Color>,
+    pub selection: Option<Color>,
+    pub shell: Option<Shell>,
+    pub working_directory: Option<PathBuf>,
+    pub encoding: Option<Encoding>,
+    pub c1_controls: Option<bool>,
+    pub hold: Option<bool>,
+    pub mux: Option<bool>,
+    pub backend: Option<BackendKind>,
+    pub serial_device: Option<PathBuf>,
+    pub baud: Option<u32>,
+    pub parity: Option<Parity>,
+    pub data_bits: Option<u8>,
+    pub stop_bits: Option<u8>,
+    pub flow_control: Option<FlowControl>,
+    pub scrollback: Option<usize>,
+    pub allow_remote_control: Option<bool>,
+    pub keybindings: Vec<(KeyCombo, ModeFilter, Binding)>,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read(PathBuf, io::Error),
+    Syntax(PathBuf, toml_edit::TomlError),
+    // A setting that parsed as TOML but makes no sense to us, `line` is 1-based
+    Invalid {
+        path: PathBuf,
+        line: Option<usize>,
+        key: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Read(path, error) => write!(formatter, "Could not read {}: {}", path.display(), error),
+            ConfigError::Syntax(path, error) => write!(formatter, "Could not parse {}: {}", path.display(), error),
+            ConfigError::Invalid {
+                path,
+                line: Some(line),
+                key,
+                message,
+            } => write!(formatter, "{}:{}: `{}` {}", path.display(), line, key, message),
+            ConfigError::Invalid {
+                path,
+                line: None,
+                key,
+                message,
+            } => write!(formatter, "{}: `{}` {}", path.display(), key, message),
+        }
+    }
+}
+
+impl Settings {
+    pub fn read(path: PathBuf) -> Result<Self, ConfigError> {
+        let source = fs::read_to_string(&path).map_err(|error| ConfigError::Read(path.clone(), error))?;
+        Self::parse(&source, path)
+    }
+
+    // `path` is only used to point error messages at the file
+    pub fn parse(source: &str, path: PathBuf) -> Result<Self, ConfigError> {
+        let document = ImDocument::parse(source).map_err(|error| ConfigError::Syntax(path.clone(), error))?;
+        let reader = Reader {
+            source,
+            path,
+            prefix: String::new(),
+            table: document.as_table(),
+        };
+        reader.known_keys(&[
+            "font",
+            "window",
+            "colors",
+            "shell",
+            "serial",
+            "scrollback",
+            "allow_remote_control",
+            "keybindings",
+        ])?;
+
+        let mut settings = Settings {
+            scrollback: reader.integer("scrollback")?,
+            allow_remote_control: reader.boolean("allow_remote_control")?,
+            ..Settings::default()
+        };
+
+        if let Some(font) = reader.section("font")? {
+            font.known_keys(&["family", "fallbacks", "size", "ligatures"])?;
+            settings.font_family = font.string("family")?;
+            settings.font_fallbacks = font.strings("fallbacks")?;
+            settings.font_size = font.number("size")?;
+            settings.ligatures = font.boolean("ligatures")?;
+        }
+
+        if let Some(window) = reader.section("window")? {
+            window.known_keys(&["width", "height", "padding", "title", "class"])?;
+            settings.window_width = window.number("width")?;
+            settings.window_height = window.number("height")?;
+            settings.padding = window.padding("padding")?;
+            settings.title = window.string("title")?;
+            settings.class = window.string("class")?;
+        }
+
+        if let Some(colors) = reader.section("colors")? {
+            colors.known_keys(&["theme", "background", "foreground", "cursor", "selection"])?;
+            settings.theme = colors.theme("theme")?;
+            settings.background = colors.color("background")?;
+            settings.foreground = colors.color("foreground")?;
+            settings.cursor = colors.color("cursor")?;
+            settings.selection = colors.color("selection")?;
+        }
+
+        if let Some(shell) = reader.section("shell")? {
+            shell.known_keys(&[
+                "program",
+                "args",
+                "working_directory",
+                "encoding",
+                "c1_controls",
+                "hold",
+                "mux",
+                "backend",
+            ])?;
+            if let Some(program) = shell.string("program")? {
+                settings.shell = Some(Shell::new(program, shell.strings("args")?.unwrap_or_default()));
+            }
+            settings.working_directory = shell.string("working_directory")?.map(PathBuf::from);
+            settings.encoding = shell.encoding("encoding")?;
+            settings.c1_controls = shell.boolean("c1_controls")?;
+            settings.hold = shell.boolean("hold")?;
+            settings.mux = shell.boolean("mux")?;
+            settings.backend = shell.choice("backend", BackendKind::from_name, BackendKind::names())?;
+        }
+
+        if let Some(serial) = reader.section("serial")? {
+            serial.known_keys(&["device", "baud", "parity", "data_bits", "stop_bits", "flow_control"])?;
+            settings.serial_device = serial.string("device")?.map(PathBuf::from);
+            settings.baud = serial
+                .integer_within("baud", 1..=u32::MAX as usize)?
+                .map(|baud| baud as u32);
+            settings.parity = serial.choice("parity", Parity::from_name, Parity::names())?;
+            settings.data_bits = serial.integer_within("data_bits", 5..=8)?.map(|bits| bits as u8);
+            settings.stop_bits = serial.integer_within("stop_bits", 1..=2)?.map(|bits| bits as u8);
+            settings.flow_control = serial.choice("flow_control", FlowControl::from_name, FlowControl::names())?;
+        }
+
+        if let Some(keybindings) = reader.section("keybindings")? {
+            for (combo, item) in keybindings.table.iter() {
+                let bindings = keybindings.bindings(combo)?;
+                let combo = KeyCombo::parse(combo).map_err(|message| {
+                    keybindings.invalid(item, combo, format!("is not a key combination, {}", message))
+                })?;
+                for (mode, binding) in bindings {
+                    settings.keybindings.push((combo.clone(), mode, binding));
+                }
+            }
+        }
+
+        Ok(settings)
+    }
+
+    // Settings from `other` override the ones in `self`
+    pub fn merge(mut self, other: Settings) -> Self {
+        fn pick<T>(setting: &mut Option<T>, other: Option<T>) {
+            if other.is_some() {
+                *setting = other;
+            }
+        }
+        pick(&mut self.font_family, other.font_family);
+        pick(&mut self.font_fallbacks, other.font_fallbacks);
+        pick(&mut self.font_size, other.font_size);
+        pick(&mut self.ligatures, other.ligatures);
+        pick(&mut self.window_width, other.window_width);
+        pick(&mut self.window_height, other.window_height);
+        pick(&mut self.padding, other.padding);
+        pick(&mut self.title, other.title);
+        pick(&mut self.class, other.class);
+        pick(&mut self.theme, other.theme);
+        pick(&mut self.background, other.background);
+        pick(&mut self.foreground, other.foreground);
+        pick(&mut self.cursor, other.cursor);
+        pick(&mut self.selection, other.selection);
+        pick(&mut self.shell, other.shell);
+        pick(&mut self.working_directory, other.working_directory);
+        pick(&mut self.encoding, other.encoding);
+        pick(&mut self.c1_controls, other.c1_controls);
+        pick(&mut self.hold, other.hold);
+        pick(&mut self.mux, other.mux);
+        pick(&mut self.backend, other.backend);
+        pick(&mut self.serial_device, other.serial_device);
+        pick(&mut self.baud, other.baud);
+        pick(&mut self.parity, other.parity);
+        pick(&mut self.data_bits, other.data_bits);
+        pick(&mut self.stop_bits, other.stop_bits);
+        pick(&mut self.flow_control, other.flow_control);
+        pick(&mut self.scrollback, other.scrollback);
+        pick(&mut self.allow_remote_control, other.allow_remote_control);
+        self.keybindings.extend(other.keybindings);
+        self
+    }
+}
+
+// Reads typed settings out of one table, naming settings by their full dotted key in errors
+struct Reader<'a> {
+    source: &'a str,
+    path: PathBuf,
+    prefix: String,
+    table: &'a dyn TableLike,
+}
+
+impl<'a> Reader<'a> {
+    fn key(&self, key: &str) -> String {
+        if self.prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", self.prefix, key)
+        }
+    }
+
+    fn invalid(&self, item: &Item, key: &str, message: String) -> ConfigError {
+        let line = item
+            .span()
+            .map(|span| self.source[..span.start].matches('\n').count() + 1);
+        ConfigError::Invalid {
+            path: self.path.clone(),
+            line,
+            key: self.key(key),
+            message,
+        }
+    }
+
+    fn expected(&self, item: &Item, key: &str, expected: &str) -> ConfigError {
+        self.invalid(item, key, format!("should be {}, found {}", expected, item.type_name()))
+    }
+
+    fn known_keys(&self, known: &[&str]) -> Result<(), ConfigError> {
+        match self.table.iter().find(|(key, _)| !known.contains(key)) {
+            Some((key, item)) => Err(self.invalid(item, key, "is not a known setting".to_string())),
+            None => Ok(()),
+        }
+    }
+
+    fn section(&self, key: &str) -> Result<Option<Reader<'a>>, ConfigError> {
+        let Some(item) = self.table.get(key) else {
+            return Ok(None);
+        };
+        let table = item
+            .as_table_like()
+            .ok_or_else(|| self.expected(item, key, "a table"))?;
+        Ok(Some(Reader {
+            source: self.source,
+            path: self.path.clone(),
+            prefix: self.key(key),
+            table,
+        }))
+    }
+
+    fn string(&self, key: &str) -> Result<Option<String>, ConfigError> {
+        self.table
+            .get(key)
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| self.expected(item, key, "a string"))
+            })
+            .transpose()
+    }
+
+    fn strings(&self, key: &str) -> Result<Option<Vec<String>>, ConfigError> {
+        self.table
+            .get(key)
+            .map(|item| {
+                item.as_array()
+                    .and_then(|array| array.iter().map(|value| value.as_str().map(str::to_string)).collect())
+                    .ok_or_else(|| self.expected(item, key, "a list of strings"))
+            })
+            .transpose()
+    }
+
+    fn number(&self, key: &str) -> Result<Option<f32>, ConfigError> {
+        self.table
+            .get(key)
+            .map(|item| {
+                item.as_float()
+                    .or_else(|| item.as_integer().map(|integer| integer as f64))
+                    .filter(|number| *number > 0.0)
+                    .map(|number| number as f32)
+                    .ok_or_else(|| self.expected(item, key, "a positive number"))
+            })
+            .transpose()
+    }
+
+    fn integer(&self, key: &str) -> Result<Option<usize>, ConfigError> {
+        self.table
+            .get(key)
+            .map(|item| {
+                item.as_integer()
+                    .and_then(|integer| usize::try_from(integer).ok())
+                    .ok_or_else(|| self.expected(item, key, "a whole number of zero or more"))
+            })
+            .transpose()
+    }
+
+    fn integer_within(&self, key: &str, range: RangeInclusive<usize>) -> Result<Option<usize>, ConfigError> {
+        let Some(integer) = self.integer(key)? else {
+            return Ok(None);
+        };
+        if range.contains(&integer) {
+            return Ok(Some(integer));
+        }
+        let item = self.table.get(key).expect("Integer was just read");
+        let message = format!("should be from {} to {}, found {}", range.start(), range.end(), integer);
+        Err(self.invalid(item, key, message))
+    }
+
+    fn boolean(&self, key: &str) -> Result<Option<bool>, ConfigError> {
+        self.table
+            .get(key)
+            .map(|item| item.as_bool().ok_or_else(|| self.expected(item, key, "true or false")))
+            .transpose()
+    }
+
+    fn color(&self, key: &str) -> Result<Option<Color>, ConfigError> {
+        let Some(spec) = self.string(key)? else {
+            return Ok(None);
+        };
+        parse_color_spec(&spec).map(Some).ok_or_else(|| {
+            let item = self.table.get(key).expect("Color was just read");
+            self.invalid(
+                item,
+                key,
+                format!("should be a color like \"#rrggbb\", found \"{}\"", spec),
+            )
+        })
+    }
+
+    fn encoding(&self, key: &str) -> Result<Option<Encoding>, ConfigError> {
+        self.choice(key, Encoding::from_name, Encoding::names())
+    }
+
+    // One of a few names, listed in the error when it is none of them
+    fn choice<T>(
+        &self,
+        key: &str,
+        from_name: impl Fn(&str) -> Option<T>,
+        names: impl Iterator<Item = &'static str>,
+    ) -> Result<Option<T>, ConfigError> {
+        let Some(name) = self.string(key)? else {
+            return Ok(None);
+        };
+        from_name(&name).map(Some).ok_or_else(|| {
+            let item = self.table.get(key).expect("Name was just read");
+            let known = names.collect::<Vec<_>>().join(", ");
+            self.invalid(item, key, format!("should be one of {}, found \"{}\"", known, name))
+        })
+    }
+
+    fn action(&self, key: &str) -> Result<Option<Action>, ConfigError> {
+        let Some(name) = self.string(key)? else {
+            return Ok(None);
+        };
+        let item = self.table.get(key).expect("Action was just read");
+        Action::from_name(&name)
+            .map(Some)
+            .ok_or_else(|| self.invalid(item, key, format!("has unknown action `{}`", name)))
+    }
+
+    // An action name, a table with an action or chars and the modes it applies in, or a list of
+    // such tables for different modes
+    fn bindings(&self, key: &str) -> Result<Vec<(ModeFilter, Binding)>, ConfigError> {
+        let item = self.table.get(key).expect("Binding was just found");
+        if item.is_str() {
+            let action = self.action(key)?.expect("Action was just found");
+            return Ok(vec![(ModeFilter::default(), Binding::Action(action))]);
+        }
+        if item.is_table_like() {
+            let table = self.section(key)?.expect("Binding table was just found");
+            return Ok(vec![table.binding()?]);
+        }
+        let tables = item
+            .as_array()
+            .and_then(|array| {
+                array
+                    .iter()
+                    .map(|value| value.as_inline_table())
+                    .collect::<Option<Vec<_>>>()
+            })
+            .ok_or_else(|| self.expected(item, key, "an action, a binding table or a list of binding tables"))?;
+        tables
+            .into_iter()
+            .map(|table| {
+                Reader {
+                    source: self.source,
+                    path: self.path.clone(),
+                    prefix: self.key(key),
+                    table,
+                }
+                .binding()
+            })
+            .collect()
+    }
+
+    fn binding(&self) -> Result<(ModeFilter, Binding), ConfigError> {
+        self.known_keys(&["action", "chars", "mode"])?;
+        let modes = match self.table.get("mode") {
+            Some(item) if item.is_str() => self.string("mode")?.into_iter().collect(),
+            _ => self.strings("mode")?.unwrap_or_default(),
+        };
+        let mode = ModeFilter::parse(&modes).map_err(|message| {
+            let item = self.table.get("mode").expect("Modes were just read");
+            self.invalid(item, "mode", message)
+        })?;
+        let binding = match (self.action("action")?, self.string("chars")?) {
+            (Some(action), None) => Binding::Action(action),
+            (None, Some(chars)) => Binding::Bytes(chars.into_bytes()),
+            _ => {
+                return Err(ConfigError::Invalid {
+                    path: self.path.clone(),
+                    line: None,
+                    key: self.prefix.clone(),
+                    message: "needs either an `action` or `chars`".to_string(),
+                })
+            }
+        };
+        Ok((mode, binding))
+    }
+
+    // Themes are looked up next to the config file
+    fn theme(&self, key: &str) -> Result<Option<Palette>, ConfigError> {
+        let Some(name) = self.string(key)? else {
+            return Ok(None);
+        };
+        let dir = self.path.parent().unwrap_or(Path::new(""));
+        import::find(&name, dir).map(Some).map_err(|error| {
+            let item = self.table.get(key).expect("Theme was just read");
+            self.invalid(item, key, format!("could not be loaded, {}", error))
+        })
+    }
+
+    // Either one number for every side or a table of sides, missing sides are 0
+    fn padding(&self, key: &str) -> Result<Option<Padding>, ConfigError> {
+        let Some(item) = self.table.get(key) else {
+            return Ok(None);
+        };
+        if item.is_table_like() {
+            let sides = self.section(key)?.expect("Padding table was just found");
+            sides.known_keys(&["top", "left", "bottom", "right"])?;
+            let side = |side| Ok::<_, ConfigError>(sides.number(side)?.unwrap_or(0.0));
+            return Ok(Some(Padding {
+                top: side("top")?,
+                left: side("left")?,
+                bottom: side("bottom")?,
+                right: side("right")?,
+            }));
+        }
+        Ok(self.number(key)?.map(Padding::new))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(source: &str) -> Result<Settings, ConfigError> {
+        Settings::parse(source, PathBuf::from("config.toml"))
+    }
+
+    #[test]
+    fn full_config_is_read() {
+        let settings = parse(
+            r##"
+            scrollback = 500
+            allow_remote_control = true
+
+            [font]
+            family = "Fira Code"
+            size = 13.5
+            ligatures = false
+
+            [window]
+            padding = 4
+
+            [colors]
+            background = "#102030"
+
+            [shell]
+            program = "/bin/zsh"
+            args = ["--login"]
+            encoding = "cp437"
+
+            [keybindings]
+            "ctrl+shift+=" = "zoom_in"
+            "##,
+        )
+        .unwrap();
+
+        assert_eq!(settings.scrollback, Some(500));
+        assert_eq!(settings.allow_remote_control, Some(true));
+        assert_eq!(settings.font_family.as_deref(), Some("Fira Code"));
+        assert_eq!(settings.font_size, Some(13.5));
+        assert_eq!(settings.ligatures, Some(false));
+        assert_eq!(settings.padding, Some(Padding::new(4.0)));
+        assert_eq!(settings.background, Some(Color::from_rgb8(0x10, 0x20, 0x30)));
+        assert_eq!(settings.shell.unwrap().args, vec!["--login".to_string()]);
+        assert_eq!(settings.encoding, Some(Encoding::Cp437));
+        assert_eq!(settings.keybindings.len(), 1);
+    }
+
+    #[test]
+    fn padding_sides_default_to_zero() {
+        let settings = parse("window.padding = { top = 25, left = 5 }").unwrap();
+        assert_eq!(
+            settings.padding,
+            Some(Padding {
+                top: 25.0,
+                left: 5.0,
+                bottom: 0.0,
+                right: 0.0
+            })
+        );
+    }
+
+    #[test]
+    fn errors_name_the_setting_and_line() {
+        let error = parse("[font]\nfamily = \"Iosevka\"\nsize = \"big\"\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "config.toml:3: `font.size` should be a positive number, found string"
+        );
+
+        let error = parse("[font]\nsise = 12\n").unwrap_err();
+        assert_eq!(error.to_string(), "config.toml:2: `font.sise` is not a known setting");
+
+        let error = parse("[keybindings]\n\"ctrl+a\" = \"explode\"\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "config.toml:2: `keybindings.ctrl+a` has unknown action `explode`"
+        );
+    }
+
+    #[test]
+    fn keybindings_take_actions_bytes_and_modes() {
+        let settings = parse(
+            r#"
+            [keybindings]
+            "ctrl+shift+c" = "copy"
+            "ctrl+l" = { chars = "clear\r" }
+            "shift+pageup" = [{ action = "scroll_page_up", mode = "~alt_screen" }, { chars = "\u001b[5;2~", mode = ["alt_screen"] }]
+            "#,
+        )
+        .unwrap();
+        let bindings: Vec<_> = settings
+            .keybindings
+            .iter()
+            .map(|(_, _, binding)| binding.clone())
+            .collect();
+        assert_eq!(
+            bindings,
+            vec![
+                Binding::Action(Action::Copy),
+                Binding::Bytes(b"clear\r".to_vec()),
+                Binding::Action(Action::ScrollPageUp),
+                Binding::Bytes(b"\x1b[5;2~".to_vec()),
+            ]
+        );
+        assert_ne!(settings.keybindings[2].1, settings.keybindings[3].1);
+
+        let error = parse("[keybindings]\n\"ctrl+a\" = { mode = \"vi\", chars = \"a\" }\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "config.toml:2: `keybindings.ctrl+a.mode` `vi` is not a mode"
+        );
+        let error = parse("[keybindings]\n\"ctrl+a\" = { mode = \"alt_screen\" }\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "config.toml: `keybindings.ctrl+a` needs either an `action` or `chars`"
+        );
+    }
+
+    #[test]
+    fn themes_are_loaded_by_name() {
+        let settings = parse("colors.theme = \"solarized-light\"").unwrap();
+        assert_eq!(settings.theme.unwrap().background, Color::from_rgb8(0xfd, 0xf6, 0xe3));
+
+        let error = parse("[colors]\ntheme = \"nope\"\n").unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "config.toml:2: `colors.theme` could not be loaded, `nope` is neither a built-in theme nor a file in the themes directory"
+        );
+    }
+
+    #[test]
+    fn serial_line_settings_are_checked() {
+        let settings = parse(
+            r#"
+            shell.backend = "serial"
+
+            [serial]
+            device = "/dev/ttyACM0"
+            baud = 9600
+            parity = "even"
+            data_bits = 7
+            flow_control = "hardware"
+            "#,
+        )
+        .unwrap();
+        assert!(matches!(settings.backend, Some(BackendKind::Serial(_))));
+        assert_eq!(settings.serial_device, Some(PathBuf::from("/dev/ttyACM0")));
+        assert_eq!(settings.baud, Some(9600));
+        assert_eq!(settings.parity, Some(Parity::Even));
+        assert_eq!(settings.data_bits, Some(7));
+        assert_eq!(settings.flow_control, Some(FlowControl::Hardware));
+
+        let error = parse(
+            "[serial]
+parity = \"mark\"
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "config.toml:2: `serial.parity` should be one of none, even, odd, found \"mark\""
+        );
+        let error = parse(
+            "[serial]
+stop_bits = 3
+",
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "config.toml:2: `serial.stop_bits` should be from 1 to 2, found 3"
+        );
+    }
+
+    #[test]
+    fn later_settings_override_earlier_ones() {
+        let file = parse("font.size = 12\nfont.family = \"Iosevka\"").unwrap();
+        let overrides = parse("font.size = 16").unwrap();
+        let merged = file.merge(overrides);
+        assert_eq!(merged.font_size, Some(16.0));
+        assert_eq!(merged.font_family.as_deref(), Some("Iosevka"));
+    }
+}
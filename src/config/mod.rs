@@ -0,0 +1,235 @@
+pub mod file;
+pub mod watch;
+
+use std::path::PathBuf;
+
+use iced::{Padding, Size};
+
+use crate::{
+    bindings::Bindings,
+    control,
+    font::{self, Font},
+    palette::Palette,
+    renderer::faces::Faces,
+    term::{backend::BackendKind, encoding::Encoding, term::Shell},
+    window::WindowConfig,
+};
+use file::{ConfigError, Settings};
+
+const DEFAULT_FONT: &str = "Iosevka";
+const DEFAULT_FALLBACKS: [&str; 2] = ["Symbols Nerd Font Mono", "Noto Color Emoji"];
+const DEFAULT_FONT_SIZE: f32 = 14.0;
+const DEFAULT_SCROLLBACK: usize = 10_000;
+const DEFAULT_TITLE: &str = "Terminal";
+
+// Font sizes zooming stays within
+const MIN_FONT_SIZE: f32 = 6.0;
+const MAX_FONT_SIZE: f32 = 72.0;
+
+#[derive(Debug, Clone)]
+pub struct Config {
+    pub font: Font,
+    // Size the font is reset to when zooming back
+    pub default_font_size: f32,
+    pub font_size: f32,
+    pub font_families: Vec<&'static str>,
+    // Shape runs of text with the font's ligatures, like `->` and `!=`
+    pub ligatures: bool,
+    pub window_config: WindowConfig,
+    pub title: String,
+    // Logical size of a cell, snapped to whole device pixels
+    pub cell_size: Size,
+    pub scale_factor: f32,
+    pub faces: Faces,
+    pub palette: Palette,
+    // Started instead of $SHELL
+    pub shell: Option<Shell>,
+    pub working_directory: Option<PathBuf>,
+    pub encoding: Encoding,
+    pub c1_controls: bool,
+    // Keep the window open after the shell exits, to see how it ended and start another one
+    pub hold: bool,
+    // Shells run in the session server instead of under the window, see `mux`
+    pub mux: bool,
+    // What sessions talk to, a serial device with its line settings in place of a program
+    pub backend: BackendKind,
+    // Rows kept after they scroll off the screen
+    pub scrollback: usize,
+    // Listen for `term msg` on the control socket, off unless asked for
+    pub allow_remote_control: bool,
+    pub bindings: Bindings,
+    // The config file, watched for changes when set
+    pub path: Option<PathBuf>,
+    // Settings from the command line, they win over the file's every time it is read
+    pub overrides: Settings,
+}
+
+impl Config {
+    fn new(font: &Font) -> Self {
+        Self {
+            font: font.clone(),
+            default_font_size: font.size,
+            font_size: font.size,
+            font_families: font.families(),
+            ligatures: true,
+            window_config: WindowConfig {
+                size: Size {
+                    width: 1024.0,
+                    height: 726.0,
+                },
+                padding: Padding {
+                    top: 25.0,
+                    left: 5.0,
+                    bottom: 5.0,
+                    right: 5.0,
+                },
+                class: None,
+            },
+            title: DEFAULT_TITLE.to_string(),
+            cell_size: snap_to_pixels(font.measure_glyph("M"), 1.0),
+            scale_factor: 1.0,
+            faces: Faces::detect(font.name),
+            palette: Palette::default(),
+            shell: None,
+            working_directory: None,
+            encoding: Encoding::default(),
+            c1_controls: false,
+            hold: false,
+            mux: false,
+            backend: BackendKind::default(),
+            scrollback: DEFAULT_SCROLLBACK,
+            allow_remote_control: false,
+            bindings: Bindings::default(),
+            path: None,
+            overrides: Settings::default(),
+        }
+    }
+
+    // Reads the config file at `path`, or the one in the user's config directory. A missing
+    // default file means defaults, a broken file is reported and defaults are used until it
+    // is fixed.
+    pub fn load(path: Option<PathBuf>, overrides: Settings) -> Self {
+        let explicit = path.is_some();
+        let path = path.or_else(|| dirs::config_dir().map(|dir| dir.join("term").join("config.toml")));
+        let settings = match &path {
+            Some(path) if explicit || path.exists() => Settings::read(path.clone()).unwrap_or_else(|error| {
+                eprintln!("{}", error);
+                Settings::default()
+            }),
+            _ => Settings::default(),
+        };
+        Self {
+            path,
+            overrides: overrides.clone(),
+            ..Self::from_settings(&settings.merge(overrides))
+        }
+    }
+
+    // Reads the config file again, for the running windows to pick up edits
+    pub fn reload(&self) -> Result<Self, ConfigError> {
+        let settings = match &self.path {
+            Some(path) if path.exists() => Settings::read(path.clone())?,
+            _ => Settings::default(),
+        };
+        let mut config = Self {
+            path: self.path.clone(),
+            overrides: self.overrides.clone(),
+            ..Self::from_settings(&settings.merge(self.overrides.clone()))
+        };
+        config.set_scale_factor(self.scale_factor);
+        Ok(config)
+    }
+
+    pub fn from_settings(settings: &Settings) -> Self {
+        let family = settings.font_family.as_deref().map_or(DEFAULT_FONT, font::intern);
+        let fallbacks = match &settings.font_fallbacks {
+            Some(fallbacks) => fallbacks.iter().map(|name| font::intern(name)).collect(),
+            None => DEFAULT_FALLBACKS.to_vec(),
+        };
+        let font = Font::new(family, settings.font_size.unwrap_or(DEFAULT_FONT_SIZE)).with_fallbacks(fallbacks);
+
+        let mut config = Self::new(&font);
+        config.ligatures = settings.ligatures.unwrap_or(config.ligatures);
+        let window_size = &mut config.window_config.size;
+        window_size.width = settings.window_width.unwrap_or(window_size.width);
+        window_size.height = settings.window_height.unwrap_or(window_size.height);
+        config.window_config.padding = settings.padding.unwrap_or(config.window_config.padding);
+        config.window_config.class = settings.class.clone();
+        config.title = settings.title.clone().unwrap_or(config.title);
+        // Colors set on their own override the theme's
+        let palette = &mut config.palette;
+        if let Some(theme) = &settings.theme {
+            *palette = theme.clone();
+        }
+        palette.background = settings.background.unwrap_or(palette.background);
+        palette.foreground = settings.foreground.unwrap_or(palette.foreground);
+        palette.cursor = settings.cursor.unwrap_or(palette.cursor);
+        palette.selection = settings.selection.unwrap_or(palette.selection);
+        config.shell = settings.shell.clone();
+        config.working_directory = settings.working_directory.clone();
+        config.encoding = settings.encoding.unwrap_or(config.encoding);
+        config.c1_controls = settings.c1_controls.unwrap_or(config.c1_controls);
+        config.hold = settings.hold.unwrap_or(config.hold);
+        config.mux = settings.mux.unwrap_or(config.mux);
+        config.allow_remote_control = settings.allow_remote_control.unwrap_or(config.allow_remote_control);
+        config.backend = match settings.backend.clone().unwrap_or_default() {
+            BackendKind::Serial(mut serial) => {
+                serial.device = settings.serial_device.clone().unwrap_or(serial.device);
+                serial.baud = settings.baud.unwrap_or(serial.baud);
+                serial.parity = settings.parity.unwrap_or(serial.parity);
+                serial.data_bits = settings.data_bits.unwrap_or(serial.data_bits);
+                serial.stop_bits = settings.stop_bits.unwrap_or(serial.stop_bits);
+                serial.flow_control = settings.flow_control.unwrap_or(serial.flow_control);
+                BackendKind::Serial(serial)
+            }
+            backend => backend,
+        };
+        config.scrollback = settings.scrollback.unwrap_or(config.scrollback);
+        for (combo, mode, binding) in &settings.keybindings {
+            config.bindings.bind(combo.clone(), *mode, binding.clone());
+        }
+        config
+    }
+
+    // The program a new terminal starts
+    pub fn shell(&self) -> Shell {
+        let mut shell = self.shell.clone().unwrap_or_else(Shell::from_env);
+        shell.working_directory = self.working_directory.clone();
+        shell.encoding = self.encoding;
+        shell.c1_controls = self.c1_controls;
+        shell.backend = self.backend.clone();
+        // Without a private place for the socket there is none
+        shell.control_socket = self
+            .allow_remote_control
+            .then(control::socket_path)
+            .and_then(Result::ok);
+        shell
+    }
+
+    // Zooms the font, measuring the cells again for the new size
+    pub fn set_font_size(&mut self, size: f32) {
+        self.font.size = size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        self.font_size = self.font.size;
+        self.cell_size = snap_to_pixels(self.font.measure_glyph("M"), self.scale_factor);
+    }
+
+    pub fn set_scale_factor(&mut self, scale_factor: f32) {
+        self.scale_factor = scale_factor;
+        self.cell_size = snap_to_pixels(self.font.measure_glyph("M"), scale_factor);
+    }
+
+    // Size of a cell in device pixels
+    pub fn device_cell_size(&self) -> Size<usize> {
+        Size::new(
+            (self.cell_size.width * self.scale_factor).round() as usize,
+            (self.cell_size.height * self.scale_factor).round() as usize,
+        )
+    }
+}
+
+// Rounds a logical size to whole device pixels, so every cell of the grid is equally wide
+// and glyphs are not resampled between pixels
+fn snap_to_pixels(size: Size, scale_factor: f32) -> Size {
+    let snap = |length: f32| (length * scale_factor).round().max(1.0) / scale_factor;
+    Size::new(snap(size.width), snap(size.height))
+}
@@ -0,0 +1,28 @@
+use std::{fs, path::PathBuf, time::Duration, time::SystemTime};
+
+use iced::futures::{SinkExt, Stream};
+
+// How often the config file is checked for changes
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Yields every time the file at `path` is written, created or removed. Polling the
+// modification time is plenty for a file edited by hand.
+pub fn changes(path: PathBuf) -> impl Stream<Item = ()> {
+    iced::stream::channel(1, |mut output| async move {
+        let mut last_modified = modified(&path);
+        loop {
+            async_std::task::sleep(POLL_INTERVAL).await;
+            let modified = modified(&path);
+            if modified != last_modified {
+                last_modified = modified;
+                if output.send(()).await.is_err() {
+                    break;
+                }
+            }
+        }
+    })
+}
+
+fn modified(path: &PathBuf) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|metadata| metadata.modified()).ok()
+}
@@ -0,0 +1,108 @@
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use super::{
+    protocol::{read_response, write_request, Request, Response},
+    SOCKET_PREFIX, SOCKET_VARIABLE,
+};
+use crate::{cli::MsgOptions, sockets};
+
+// Sends what `term msg` was asked and prints the answer
+pub fn run(options: &MsgOptions) -> Result<(), String> {
+    let socket = find_socket(options.to.clone())?;
+    let response = send(&socket, &options.request())
+        .map_err(|error| format!("could not reach the terminal on {}: {}", socket.display(), error))?;
+    match response {
+        Response::Done => {}
+        Response::Opened(session) => println!("{}", session),
+        Response::Sessions(sessions) if options.json => println!("{}", json(&sessions)),
+        Response::Sessions(sessions) => {
+            for session in sessions {
+                let focused = if session.focused { "*" } else { " " };
+                println!(
+                    "{}\t{}\t{}\t{}\t{}",
+                    session.id, session.window, session.tab, focused, session.title
+                );
+            }
+        }
+        Response::Text(text) if options.json => println!("{}", json(&text)),
+        Response::Text(text) => println!("{}", text.lines.join("\n")),
+        Response::Failed(message) => return Err(message),
+    }
+    Ok(())
+}
+
+fn send(socket: &Path, request: &Request) -> std::io::Result<Response> {
+    let mut stream = sockets::connect(socket)?;
+    write_request(&mut stream, request)?;
+    read_response(&mut stream)?.ok_or_else(|| std::io::Error::other("the terminal hung up"))
+}
+
+// Outside of the terminal's shells the only terminal running is the one meant
+fn find_socket(to: Option<PathBuf>) -> Result<PathBuf, String> {
+    if let Some(socket) = to.or_else(|| env::var_os(SOCKET_VARIABLE).map(PathBuf::from)) {
+        return Ok(socket);
+    }
+    let dir = sockets::dir().map_err(|error| error.to_string())?;
+    let entries = fs::read_dir(dir).map_err(|error| error.to_string())?;
+    let mut running: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| {
+            let name = path.file_name().unwrap_or_default().to_string_lossy();
+            name.starts_with(SOCKET_PREFIX) && name.ends_with(".sock")
+        })
+        .filter(|path| sockets::connect(path).is_ok())
+        .collect();
+    match running.len() {
+        1 => Ok(running.remove(0)),
+        0 => Err("no terminal is running with allow_remote_control on".to_string()),
+        _ => Err(format!(
+            "several terminals are running, pick one with --to or ${}",
+            SOCKET_VARIABLE
+        )),
+    }
+}
+
+// Lists and text come out the way they are stored, only their fields are named
+fn json(value: &impl Serialize) -> String {
+    serde_json::to_string(value).expect("Sessions and text serialize to JSON")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::control::protocol::{ScreenText, SessionInfo};
+
+    #[test]
+    fn text_is_printed_as_json() {
+        let text = ScreenText {
+            lines: vec!["say \"hi\"\\".to_string(), "\x07".to_string()],
+            cursor: (1, 0),
+            title: "tab\there".to_string(),
+        };
+        assert_eq!(
+            json(&text),
+            r#"{"lines":["say \"hi\"\\","\u0007"],"cursor":{"row":1,"col":0},"title":"tab\there"}"#
+        );
+    }
+
+    #[test]
+    fn sessions_are_printed_as_json() {
+        let sessions = [SessionInfo {
+            id: 4,
+            window: 0,
+            tab: 1,
+            title: "vim".to_string(),
+            focused: true,
+        }];
+        assert_eq!(
+            json(&sessions),
+            r#"[{"id":4,"window":0,"tab":1,"title":"vim","focused":true}]"#
+        );
+    }
+}
@@ -0,0 +1,17 @@
+pub mod client;
+pub mod protocol;
+pub mod server;
+
+use std::{io, path::PathBuf};
+
+use crate::sockets;
+
+// Set for the shells a terminal starts, `term msg` run in them talks to that terminal
+pub const SOCKET_VARIABLE: &str = "TERM_CONTROL_SOCKET";
+
+const SOCKET_PREFIX: &str = "term-control-";
+
+// Where this process listens for `term msg`, one socket per running terminal
+pub fn socket_path() -> io::Result<PathBuf> {
+    Ok(sockets::dir()?.join(format!("{}{}.sock", SOCKET_PREFIX, std::process::id())))
+}
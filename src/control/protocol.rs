@@ -0,0 +1,357 @@
+use std::{
+    io::{self, Read, Write},
+    path::PathBuf,
+};
+
+use serde::{Serialize, Serializer};
+
+use crate::structs::frame::{invalid, Frame, Payload};
+
+/// What `term msg` asks of a running terminal. `session` picks the session by its id, the
+/// focused one when not given; new tabs and splits go to the window it is in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    List,
+    SendText {
+        session: Option<usize>,
+        text: String,
+    },
+    // Keys written like in the keybindings, `ctrl+c` or `enter`
+    SendKeys {
+        session: Option<usize>,
+        keys: Vec<String>,
+    },
+    SetTitle {
+        session: Option<usize>,
+        title: String,
+    },
+    // The shell runs `command` when not empty
+    NewTab {
+        session: Option<usize>,
+        command: Vec<String>,
+        working_directory: Option<PathBuf>,
+    },
+    Split {
+        session: Option<usize>,
+        down: bool,
+        command: Vec<String>,
+        working_directory: Option<PathBuf>,
+    },
+    GetText {
+        session: Option<usize>,
+        scrollback: bool,
+    },
+}
+
+/// What the terminal answers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Response {
+    Done,
+    // The session a new tab or split started
+    Opened(usize),
+    Sessions(Vec<SessionInfo>),
+    Text(ScreenText),
+    Failed(String),
+}
+
+/// A session as `term msg list` shows it, windows and tabs are counted from 0
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SessionInfo {
+    pub id: usize,
+    pub window: usize,
+    pub tab: usize,
+    pub title: String,
+    // The focused pane of the active tab in the focused window
+    pub focused: bool,
+}
+
+/// Lines of a session's screen without trailing blanks, the scrollback first if asked for
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ScreenText {
+    pub lines: Vec<String>,
+    // Row and column on the screen, the scrollback rows come before it
+    #[serde(serialize_with = "row_and_col")]
+    pub cursor: (usize, usize),
+    pub title: String,
+}
+
+// Written as `{"row": 1, "col": 0}` for scripts reading `term msg get-text --json`
+fn row_and_col<S: Serializer>(cursor: &(usize, usize), serializer: S) -> Result<S::Ok, S::Error> {
+    #[derive(Serialize)]
+    struct Position {
+        row: usize,
+        col: usize,
+    }
+    let (row, col) = *cursor;
+    Position { row, col }.serialize(serializer)
+}
+
+pub fn write_request(writer: &mut impl Write, request: &Request) -> io::Result<()> {
+    let mut frame = Frame::default();
+    match request {
+        Request::List => frame.u8(1),
+        Request::SendText { session, text } => {
+            frame.u8(2);
+            write_session(&mut frame, *session);
+            frame.string(text);
+        }
+        Request::SendKeys { session, keys } => {
+            frame.u8(3);
+            write_session(&mut frame, *session);
+            frame.strings(keys);
+        }
+        Request::SetTitle { session, title } => {
+            frame.u8(4);
+            write_session(&mut frame, *session);
+            frame.string(title);
+        }
+        Request::NewTab {
+            session,
+            command,
+            working_directory,
+        } => {
+            frame.u8(5);
+            write_session(&mut frame, *session);
+            write_command(&mut frame, command, working_directory);
+        }
+        Request::Split {
+            session,
+            down,
+            command,
+            working_directory,
+        } => {
+            frame.u8(6);
+            write_session(&mut frame, *session);
+            frame.u8(*down as u8);
+            write_command(&mut frame, command, working_directory);
+        }
+        Request::GetText { session, scrollback } => {
+            frame.u8(7);
+            write_session(&mut frame, *session);
+            frame.u8(*scrollback as u8);
+        }
+    }
+    frame.write_to(writer)
+}
+
+// `None` once the other side hung up between frames
+pub fn read_request(reader: &mut impl Read) -> io::Result<Option<Request>> {
+    let Some(mut payload) = Payload::read_from(reader)? else {
+        return Ok(None);
+    };
+    let request = match payload.u8()? {
+        1 => Request::List,
+        2 => Request::SendText {
+            session: read_session(&mut payload)?,
+            text: payload.string()?,
+        },
+        3 => Request::SendKeys {
+            session: read_session(&mut payload)?,
+            keys: payload.strings()?,
+        },
+        4 => Request::SetTitle {
+            session: read_session(&mut payload)?,
+            title: payload.string()?,
+        },
+        5 => {
+            let session = read_session(&mut payload)?;
+            let (command, working_directory) = read_command(&mut payload)?;
+            Request::NewTab {
+                session,
+                command,
+                working_directory,
+            }
+        }
+        6 => {
+            let session = read_session(&mut payload)?;
+            let down = payload.u8()? != 0;
+            let (command, working_directory) = read_command(&mut payload)?;
+            Request::Split {
+                session,
+                down,
+                command,
+                working_directory,
+            }
+        }
+        7 => Request::GetText {
+            session: read_session(&mut payload)?,
+            scrollback: payload.u8()? != 0,
+        },
+        tag => return Err(invalid(format!("unknown request {}", tag))),
+    };
+    Ok(Some(request))
+}
+
+pub fn write_response(writer: &mut impl Write, response: &Response) -> io::Result<()> {
+    let mut frame = Frame::default();
+    match response {
+        Response::Done => frame.u8(1),
+        Response::Opened(session) => {
+            frame.u8(2);
+            frame.u64(*session as u64);
+        }
+        Response::Sessions(sessions) => {
+            frame.u8(3);
+            frame.u32(sessions.len() as u32);
+            for session in sessions {
+                frame.u64(session.id as u64);
+                frame.u32(session.window as u32);
+                frame.u32(session.tab as u32);
+                frame.string(&session.title);
+                frame.u8(session.focused as u8);
+            }
+        }
+        Response::Text(text) => {
+            frame.u8(4);
+            frame.strings(&text.lines);
+            frame.u32(text.cursor.0 as u32);
+            frame.u32(text.cursor.1 as u32);
+            frame.string(&text.title);
+        }
+        Response::Failed(message) => {
+            frame.u8(5);
+            frame.string(message);
+        }
+    }
+    frame.write_to(writer)
+}
+
+pub fn read_response(reader: &mut impl Read) -> io::Result<Option<Response>> {
+    let Some(mut payload) = Payload::read_from(reader)? else {
+        return Ok(None);
+    };
+    let response = match payload.u8()? {
+        1 => Response::Done,
+        2 => Response::Opened(payload.u64()? as usize),
+        3 => {
+            let sessions = (0..payload.u32()?)
+                .map(|_| {
+                    Ok(SessionInfo {
+                        id: payload.u64()? as usize,
+                        window: payload.u32()? as usize,
+                        tab: payload.u32()? as usize,
+                        title: payload.string()?,
+                        focused: payload.u8()? != 0,
+                    })
+                })
+                .collect::<io::Result<_>>()?;
+            Response::Sessions(sessions)
+        }
+        4 => Response::Text(ScreenText {
+            lines: payload.strings()?,
+            cursor: (payload.u32()? as usize, payload.u32()? as usize),
+            title: payload.string()?,
+        }),
+        5 => Response::Failed(payload.string()?),
+        tag => return Err(invalid(format!("unknown response {}", tag))),
+    };
+    Ok(Some(response))
+}
+
+fn write_session(frame: &mut Frame, session: Option<usize>) {
+    match session {
+        Some(session) => {
+            frame.u8(1);
+            frame.u64(session as u64);
+        }
+        None => frame.u8(0),
+    }
+}
+
+fn read_session(payload: &mut Payload) -> io::Result<Option<usize>> {
+    match payload.u8()? {
+        0 => Ok(None),
+        _ => Ok(Some(payload.u64()? as usize)),
+    }
+}
+
+fn write_command(frame: &mut Frame, command: &[String], working_directory: &Option<PathBuf>) {
+    frame.strings(command);
+    let directory = working_directory.as_ref().map(|path| path.to_string_lossy());
+    frame.string(directory.as_deref().unwrap_or(""));
+}
+
+fn read_command(payload: &mut Payload) -> io::Result<(Vec<String>, Option<PathBuf>)> {
+    let command = payload.strings()?;
+    let working_directory = Some(payload.string()?)
+        .filter(|directory| !directory.is_empty())
+        .map(PathBuf::from);
+    Ok((command, working_directory))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn requests_read_back_as_written() {
+        let requests = [
+            Request::List,
+            Request::SendText {
+                session: Some(2),
+                text: "make\r".to_string(),
+            },
+            Request::SendKeys {
+                session: None,
+                keys: vec!["ctrl+c".to_string(), "up".to_string()],
+            },
+            Request::SetTitle {
+                session: None,
+                title: "build".to_string(),
+            },
+            Request::NewTab {
+                session: None,
+                command: vec!["htop".to_string()],
+                working_directory: Some(PathBuf::from("/tmp")),
+            },
+            Request::Split {
+                session: Some(0),
+                down: true,
+                command: Vec::new(),
+                working_directory: None,
+            },
+            Request::GetText {
+                session: Some(1),
+                scrollback: true,
+            },
+        ];
+        let mut bytes = Vec::new();
+        for request in &requests {
+            write_request(&mut bytes, request).unwrap();
+        }
+        let mut reader = &bytes[..];
+        for request in requests {
+            assert_eq!(read_request(&mut reader).unwrap(), Some(request));
+        }
+        assert_eq!(read_request(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn responses_read_back_as_written() {
+        let responses = [
+            Response::Done,
+            Response::Opened(4),
+            Response::Sessions(vec![SessionInfo {
+                id: 4,
+                window: 0,
+                tab: 1,
+                title: "vim".to_string(),
+                focused: true,
+            }]),
+            Response::Text(ScreenText {
+                lines: vec!["$ ls".to_string(), "".to_string()],
+                cursor: (1, 0),
+                title: "bash".to_string(),
+            }),
+            Response::Failed("there is no session 9".to_string()),
+        ];
+        let mut bytes = Vec::new();
+        for response in &responses {
+            write_response(&mut bytes, response).unwrap();
+        }
+        let mut reader = &bytes[..];
+        for response in responses {
+            assert_eq!(read_response(&mut reader).unwrap(), Some(response));
+        }
+    }
+}
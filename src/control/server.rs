@@ -0,0 +1,82 @@
+use std::{
+    fs,
+    os::unix::net::UnixStream,
+    sync::{mpsc, Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use iced::futures::{channel::mpsc::Sender, executor::block_on, SinkExt, Stream};
+
+use super::protocol::{read_request, write_response, Request, Response};
+use crate::sockets;
+
+// Requests waiting for the application
+const COMMAND_QUEUE: usize = 16;
+
+// How long `term msg` waits for the application to answer
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// A request from `term msg`, answered once the application handled it
+#[derive(Debug, Clone)]
+pub struct Command {
+    pub request: Request,
+    reply: Arc<Mutex<Option<mpsc::Sender<Response>>>>,
+}
+
+impl Command {
+    // Only the first response is sent, the command is cloned along with the message carrying it
+    pub fn reply(&self, response: Response) {
+        if let Some(reply) = self.reply.lock().unwrap().take() {
+            let _ = reply.send(response);
+        }
+    }
+}
+
+// Listens on our socket for as long as the application runs, every connection can send any
+// number of requests
+pub fn listen() -> impl Stream<Item = Command> {
+    iced::stream::channel(COMMAND_QUEUE, move |commands| async move {
+        let path = match super::socket_path() {
+            Ok(path) => path,
+            Err(error) => {
+                eprintln!("Could not listen for `term msg`: {}", error);
+                return;
+            }
+        };
+        // Left behind by a terminal that had our process id before
+        let _ = fs::remove_file(&path);
+        let listener = match sockets::bind(&path) {
+            Ok(listener) => listener,
+            Err(error) => {
+                eprintln!("Could not listen for `term msg` on {}: {}", path.display(), error);
+                return;
+            }
+        };
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let commands = commands.clone();
+                thread::spawn(move || serve(stream, commands));
+            }
+        });
+    })
+}
+
+fn serve(mut stream: UnixStream, mut commands: Sender<Command>) {
+    while let Ok(Some(request)) = read_request(&mut stream) {
+        let (reply, response) = mpsc::channel();
+        let command = Command {
+            request,
+            reply: Arc::new(Mutex::new(Some(reply))),
+        };
+        if block_on(commands.send(command)).is_err() {
+            return;
+        }
+        let response = response
+            .recv_timeout(REPLY_TIMEOUT)
+            .unwrap_or_else(|_| Response::Failed("the terminal did not answer".to_string()));
+        if write_response(&mut stream, &response).is_err() {
+            return;
+        }
+    }
+}
@@ -1,27 +1,58 @@
-use iced::{widget::text::LineHeight, Size};
+use std::sync::Mutex;
 
+use iced::{
+    advanced::graphics::text::{cosmic_text, font_system},
+    widget::text::LineHeight,
+    Size,
+};
+use once_cell::sync::Lazy;
+
+// Every font name handed out by `intern`
+static NAMES: Lazy<Mutex<Vec<&'static str>>> = Lazy::new(|| Mutex::new(Vec::new()));
+
+#[derive(Debug, Clone)]
 pub struct Font {
     pub name: &'static str,
     pub size: f32,
+    // Families used for glyphs missing from the primary font, in order of preference
+    pub fallbacks: Vec<&'static str>,
 }
 
 impl Font {
     pub fn new(name: &'static str, size: f32) -> Self {
-        Self { name, size }
+        Self {
+            name,
+            size,
+            fallbacks: Vec::new(),
+        }
+    }
+
+    pub fn with_fallbacks(mut self, fallbacks: Vec<&'static str>) -> Self {
+        self.fallbacks = fallbacks;
+        self
+    }
+
+    // The primary font followed by its fallbacks
+    pub fn families(&self) -> Vec<&'static str> {
+        std::iter::once(self.name)
+            .chain(self.fallbacks.iter().copied())
+            .collect()
     }
 
     pub fn measure_glyph(&self, char: &str) -> Size {
-        use cosmic_text::{Attrs, Buffer, Family, FontSystem, Metrics, Shaping};
+        use cosmic_text::{Attrs, Buffer, Family, Metrics, Shaping};
         let line_height_scale = LineHeight::default();
         let line_height = line_height_scale.to_absolute(self.size.into()).0;
 
-        let mut font_system = FontSystem::new();
+        // Loading every installed font takes a while, so measure with the font system iced
+        // draws with
+        let mut font_system = font_system().write().expect("Could not lock font system");
         let mut buffer = Buffer::new_empty(Metrics {
             font_size: self.size,
             line_height,
         });
         let font_attributes = Attrs::new().family(Family::Name(self.name));
-        buffer.set_text(&mut font_system, char, font_attributes, Shaping::Advanced);
+        buffer.set_text(font_system.raw(), char, font_attributes, Shaping::Advanced);
 
         let width = buffer.layout_runs().fold(0.0, |width, run| run.line_w.max(width));
 
@@ -31,3 +62,15 @@ impl Font {
         }
     }
 }
+
+// iced refers to fonts by `&'static str` names, so names read at runtime are leaked. Each name
+// is only leaked once, reloading the config reuses it.
+pub fn intern(name: &str) -> &'static str {
+    let mut names = NAMES.lock().expect("Could not lock font names");
+    if let Some(interned) = names.iter().find(|interned| **interned == name) {
+        return interned;
+    }
+    let interned: &'static str = Box::leak(name.to_string().into_boxed_str());
+    names.push(interned);
+    interned
+}
@@ -0,0 +1,172 @@
+use iced::keyboard::{key::Named, Key, Modifiers};
+
+use crate::bindings::Modes;
+
+// Encodes a key the way xterm sends it to the shell. `text` is what the key typed, if
+// anything. Keys the shell has no sequence for give `None`.
+pub fn encode(key: &Key, modifiers: Modifiers, text: Option<&str>, modes: Modes) -> Option<Vec<u8>> {
+    let bytes = match key.as_ref() {
+        Key::Named(named) => named_key(named, modifiers, modes)?,
+        Key::Character(character) => character_key(character, modifiers, text)?,
+        Key::Unidentified => text?.as_bytes().to_vec(),
+    };
+    Some(bytes)
+}
+
+fn named_key(key: Named, modifiers: Modifiers, modes: Modes) -> Option<Vec<u8>> {
+    let parameter = modifier_parameter(modifiers);
+    let bytes = match key {
+        Named::Enter => alt_prefixed(modifiers, b"\r"),
+        Named::Tab if modifiers.shift() => b"\x1b[Z".to_vec(),
+        Named::Tab => alt_prefixed(modifiers, b"\t"),
+        Named::Backspace if modifiers.control() => alt_prefixed(modifiers, b"\x08"),
+        Named::Backspace => alt_prefixed(modifiers, b"\x7f"),
+        Named::Escape => alt_prefixed(modifiers, b"\x1b"),
+        Named::Space if modifiers.control() => alt_prefixed(modifiers, b"\0"),
+        Named::Space => alt_prefixed(modifiers, b" "),
+
+        Named::ArrowUp => cursor_key(b'A', parameter, modes),
+        Named::ArrowDown => cursor_key(b'B', parameter, modes),
+        Named::ArrowRight => cursor_key(b'C', parameter, modes),
+        Named::ArrowLeft => cursor_key(b'D', parameter, modes),
+        Named::Home => cursor_key(b'H', parameter, modes),
+        Named::End => cursor_key(b'F', parameter, modes),
+
+        Named::Insert => tilde_key(2, parameter),
+        Named::Delete => tilde_key(3, parameter),
+        Named::PageUp => tilde_key(5, parameter),
+        Named::PageDown => tilde_key(6, parameter),
+
+        // F1 to F4 are SS3 sequences, the rest numbered like the editing keys
+        Named::F1 => function_key(b'P', parameter),
+        Named::F2 => function_key(b'Q', parameter),
+        Named::F3 => function_key(b'R', parameter),
+        Named::F4 => function_key(b'S', parameter),
+        Named::F5 => tilde_key(15, parameter),
+        Named::F6 => tilde_key(17, parameter),
+        Named::F7 => tilde_key(18, parameter),
+        Named::F8 => tilde_key(19, parameter),
+        Named::F9 => tilde_key(20, parameter),
+        Named::F10 => tilde_key(21, parameter),
+        Named::F11 => tilde_key(23, parameter),
+        Named::F12 => tilde_key(24, parameter),
+        _ => return None,
+    };
+    Some(bytes)
+}
+
+fn character_key(character: &str, modifiers: Modifiers, text: Option<&str>) -> Option<Vec<u8>> {
+    if modifiers.control() {
+        if let Some(control) = control_code(character) {
+            return Some(alt_prefixed(modifiers, &[control]));
+        }
+    }
+    let text = text
+        .filter(|text| !text.chars().all(char::is_control))
+        .unwrap_or(character);
+    Some(alt_prefixed(modifiers, text.as_bytes()))
+}
+
+// Ctrl with a letter or one of `@[\]^_` sends the matching C0 control
+fn control_code(character: &str) -> Option<u8> {
+    let mut chars = character.chars();
+    let c = chars.next()?.to_ascii_uppercase();
+    if chars.next().is_some() {
+        return None;
+    }
+    match c {
+        '@'..='_' => Some(c as u8 & 0x1f),
+        '2' | ' ' => Some(0),
+        '/' => Some(0x1f),
+        '?' => Some(0x7f),
+        _ => None,
+    }
+}
+
+// Alt sends ESC before the key, the way xterm does with `metaSendsEscape`
+fn alt_prefixed(modifiers: Modifiers, bytes: &[u8]) -> Vec<u8> {
+    if modifiers.alt() {
+        [b"\x1b", bytes].concat()
+    } else {
+        bytes.to_vec()
+    }
+}
+
+// xterm's modifier parameter: 1 plus 1 for shift, 2 for alt, 4 for ctrl and 8 for super
+fn modifier_parameter(modifiers: Modifiers) -> Option<u8> {
+    let parameter = 1
+        + modifiers.shift() as u8
+        + 2 * modifiers.alt() as u8
+        + 4 * modifiers.control() as u8
+        + 8 * modifiers.logo() as u8;
+    (parameter > 1).then_some(parameter)
+}
+
+fn cursor_key(final_byte: u8, parameter: Option<u8>, modes: Modes) -> Vec<u8> {
+    match parameter {
+        Some(parameter) => format!("\x1b[1;{}{}", parameter, final_byte as char).into_bytes(),
+        None if modes.app_cursor => vec![0x1b, b'O', final_byte],
+        None => vec![0x1b, b'[', final_byte],
+    }
+}
+
+fn function_key(final_byte: u8, parameter: Option<u8>) -> Vec<u8> {
+    match parameter {
+        Some(parameter) => format!("\x1b[1;{}{}", parameter, final_byte as char).into_bytes(),
+        None => vec![0x1b, b'O', final_byte],
+    }
+}
+
+fn tilde_key(number: u8, parameter: Option<u8>) -> Vec<u8> {
+    match parameter {
+        Some(parameter) => format!("\x1b[{};{}~", number, parameter).into_bytes(),
+        None => format!("\x1b[{}~", number).into_bytes(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(key: Named, modifiers: Modifiers, modes: Modes) -> Vec<u8> {
+        encode(&Key::Named(key), modifiers, None, modes).unwrap()
+    }
+
+    #[test]
+    fn cursor_keys_follow_the_cursor_mode() {
+        let app_cursor = Modes {
+            app_cursor: true,
+            ..Modes::default()
+        };
+        assert_eq!(named(Named::ArrowUp, Modifiers::empty(), Modes::default()), b"\x1b[A");
+        assert_eq!(named(Named::ArrowUp, Modifiers::empty(), app_cursor), b"\x1bOA");
+        assert_eq!(named(Named::ArrowLeft, Modifiers::CTRL, app_cursor), b"\x1b[1;5D");
+        assert_eq!(named(Named::End, Modifiers::SHIFT, Modes::default()), b"\x1b[1;2F");
+    }
+
+    #[test]
+    fn editing_and_function_keys() {
+        let modes = Modes::default();
+        assert_eq!(named(Named::PageUp, Modifiers::empty(), modes), b"\x1b[5~");
+        assert_eq!(named(Named::Delete, Modifiers::ALT, modes), b"\x1b[3;3~");
+        assert_eq!(named(Named::F1, Modifiers::empty(), modes), b"\x1bOP");
+        assert_eq!(named(Named::F12, Modifiers::empty(), modes), b"\x1b[24~");
+        assert_eq!(named(Named::Tab, Modifiers::SHIFT, modes), b"\x1b[Z");
+        assert_eq!(named(Named::Backspace, Modifiers::empty(), modes), b"\x7f");
+        assert_eq!(encode(&Key::Named(Named::Shift), Modifiers::SHIFT, None, modes), None);
+    }
+
+    #[test]
+    fn control_and_alt_characters() {
+        let encode_char =
+            |c: &str, modifiers, text| encode(&Key::Character(c.into()), modifiers, text, Modes::default());
+        assert_eq!(encode_char("c", Modifiers::CTRL, Some("\u{3}")), Some(vec![3]));
+        assert_eq!(encode_char("[", Modifiers::CTRL, None), Some(vec![0x1b]));
+        assert_eq!(encode_char("x", Modifiers::ALT, Some("x")), Some(b"\x1bx".to_vec()));
+        assert_eq!(encode_char("a", Modifiers::SHIFT, Some("A")), Some(b"A".to_vec()));
+        assert_eq!(
+            encode_char("é", Modifiers::empty(), Some("é")),
+            Some("é".as_bytes().to_vec())
+        );
+    }
+}
@@ -1,30 +1,86 @@
 mod ansi_parser;
 mod application;
+mod bindings;
+mod cli;
 mod config;
+mod control;
 mod font;
+mod keys;
+mod mux;
+mod palette;
+mod panes;
+mod renderer;
+mod session;
+mod sockets;
 mod structs;
+mod tabs;
 mod term;
 mod terminal;
+mod terminal_window;
 mod window;
 
 use crate::config::Config;
 use application::Application;
+use clap::Parser;
 use font::Font;
-use iced::{window::Id, Color, Size};
+use iced::{
+    keyboard::{Key, Modifiers},
+    mouse,
+    widget::pane_grid,
+    window::Id,
+    Color, Size,
+};
 use window::WindowFocus;
 
 fn main() -> iced::Result {
-    let font = Font::new("Iosevka", 14.0);
-    let settings = settings(&font);
-    let config = Config::new(&font);
-    iced::daemon("Terminal", Application::update, Application::view)
-        .style(|_state, _theme| iced::daemon::Appearance {
-            background_color: Color::from_rgb(0.11764706, 0.11764706, 0.17647059),
+    let options = cli::Options::parse();
+    if options.server {
+        if let Err(error) = mux::socket_path().and_then(|path| mux::server::run(&path)) {
+            eprintln!("Could not run the session server: {}", error);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if let Some(cli::Command::Msg(msg)) = &options.subcommand {
+        if let Err(error) = control::client::run(msg) {
+            eprintln!("term msg: {}", error);
+            std::process::exit(1);
+        }
+        return Ok(());
+    }
+    if options.list_sessions {
+        list_sessions();
+        return Ok(());
+    }
+    let overrides = options.settings().unwrap_or_else(|error| {
+        eprintln!("{}", error);
+        std::process::exit(2);
+    });
+    let attach = options.attach;
+    let config = Config::load(options.config, overrides);
+    let settings = settings(&config.font);
+    iced::daemon(Application::title, Application::update, Application::view)
+        .style(|state, _theme| iced::daemon::Appearance {
+            background_color: state.background(),
             text_color: Color::from_rgb(0.0, 0.0, 0.0),
         })
         .settings(settings)
         .subscription(Application::subscription)
-        .run_with(move || Application::new(config))
+        .run_with(move || Application::new(config, attach))
+}
+
+// One line per session of the session server: its id, whether a window shows it, and what runs
+// in it
+fn list_sessions() {
+    let sessions = mux::client::list().unwrap_or_else(|error| {
+        eprintln!("Could not list the sessions: {}", error);
+        std::process::exit(1);
+    });
+    for session in sessions {
+        let attached = if session.attached { "attached" } else { "detached" };
+        let title = session.title.unwrap_or(session.program);
+        println!("{}\t{}\t{}", session.id, attached, title);
+    }
 }
 
 fn settings(font: &Font) -> iced::Settings {
@@ -40,9 +96,19 @@ fn settings(font: &Font) -> iced::Settings {
 #[derive(Debug, Clone)]
 pub enum Message {
     TerminalInput,
-    KeyboardBytes(Vec<u8>),
-    TerminalOutput(term::term::Event),
+    KeyPressed(Id, Key, Modifiers, Option<String>),
+    Paste(Id, Option<String>),
+    Mouse(Id, mouse::Event),
+    TerminalOutput(usize, term::term::Event),
+    SelectTab(Id, usize),
+    CloseTab(Id, usize),
+    PaneResized(Id, pane_grid::ResizeEvent),
+    CursorBlink,
+    ConfigChanged,
     WindowCreated(Id),
-    WindowResized(Size),
-    WindowFocus(WindowFocus),
+    WindowResized(Id, Size),
+    ScaleFactorChanged(f32),
+    WindowFocus(Id, WindowFocus),
+    WindowClosed(Id),
+    Control(control::server::Command),
 }
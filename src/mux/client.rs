@@ -0,0 +1,184 @@
+use std::{
+    io::{self, BufReader, ErrorKind},
+    net::Shutdown,
+    os::unix::{net::UnixStream, process::CommandExt},
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+use iced::futures::{
+    channel::mpsc::{self, Sender},
+    executor::block_on,
+    SinkExt, Stream, StreamExt,
+};
+
+use super::{
+    protocol::{read_reply, write_request, Reply, Request, SessionInfo},
+    socket_path,
+};
+use crate::{
+    sockets,
+    structs::terminalsize::TerminalSize,
+    term::{
+        term::{Event, Shell},
+        terminal_output::TerminalOutput,
+    },
+};
+
+// Batches of output waiting for the terminal, like for a shell of our own
+const OUTPUT_QUEUE: usize = 4;
+
+// How long a server started for us gets to start listening
+const SERVER_START_TIMEOUT: Duration = Duration::from_secs(2);
+const SERVER_START_POLL: Duration = Duration::from_millis(50);
+
+// Starts `shell` in a new session on the session server, the server is started if none runs.
+// Gives the same events as a shell of our own.
+pub fn open(shell: Shell, size: TerminalSize, scrollback: usize) -> impl Stream<Item = Event> {
+    let program = shell.program.clone();
+    let request = Request::Open {
+        shell,
+        size,
+        scrollback,
+    };
+    session(request, connect_or_start, program)
+}
+
+// Shows a session that is already running, starting with what is on its screen
+pub fn attach(session_id: u64, size: TerminalSize, scrollback: usize) -> impl Stream<Item = Event> {
+    let request = Request::Attach {
+        session: session_id,
+        size,
+        scrollback,
+    };
+    session(request, connect, format!("session {}", session_id))
+}
+
+// The sessions running on the server, none if no server runs
+pub fn list() -> io::Result<Vec<SessionInfo>> {
+    let mut stream = match connect() {
+        Ok(stream) => stream,
+        Err(error) if error.kind() == ErrorKind::PermissionDenied => return Err(error),
+        Err(_) => return Ok(Vec::new()),
+    };
+    write_request(&mut stream, &Request::List)?;
+    match read_reply(&mut stream)? {
+        Some(Reply::Sessions(sessions)) => Ok(sessions),
+        _ => Err(io::Error::other("the session server gave no list")),
+    }
+}
+
+fn session(
+    request: Request,
+    connect: impl FnOnce() -> io::Result<UnixStream> + Send + 'static,
+    name: String,
+) -> impl Stream<Item = Event> {
+    iced::stream::channel(OUTPUT_QUEUE, move |mut output| async move {
+        let connected = connect().and_then(|mut stream| write_request(&mut stream, &request).map(|_| stream));
+        let mut connection = match connected {
+            Ok(stream) => Connection(stream),
+            Err(error) => {
+                let message = format!("could not reach the session server for {}: {}", name, error);
+                let _ = output.send(Event::Failed(message)).await;
+                return;
+            }
+        };
+        let reader = match connection.0.try_clone() {
+            Ok(reader) => reader,
+            Err(error) => {
+                let _ = output.send(Event::Failed(error.to_string())).await;
+                return;
+            }
+        };
+        // The first reply says whether we got the session
+        let mut reader = BufReader::new(reader);
+        match read_reply(&mut reader) {
+            Ok(Some(Reply::Attached(_))) => {}
+            Ok(Some(Reply::Failed(message))) => {
+                let _ = output.send(Event::Failed(message)).await;
+                return;
+            }
+            _ => {
+                let message = "the session server did not answer".to_string();
+                let _ = output.send(Event::Failed(message)).await;
+                return;
+            }
+        }
+
+        let (sender, mut receiver) = mpsc::channel(100);
+        if output.send(Event::Ready(sender)).await.is_err() {
+            return;
+        }
+        let replies = output.clone();
+        thread::spawn(move || read_replies(reader, replies));
+
+        while let Some(message) = receiver.next().await {
+            if write_request(&mut connection.0, &Request::Input(message)).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+// Hangs up once the window lets the session go, the server then keeps it running without us.
+// Shutting down also ends the thread still reading from it.
+struct Connection(UnixStream);
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        let _ = self.0.shutdown(Shutdown::Both);
+    }
+}
+
+// Passes the session's output on until the shell ends or the connection does
+fn read_replies(mut reader: BufReader<UnixStream>, mut output: Sender<Event>) {
+    loop {
+        let event = match read_reply(&mut reader) {
+            Ok(Some(Reply::Output(text))) => Event::Output(TerminalOutput::split(&text)),
+            Ok(Some(Reply::Exited(status))) => Event::Exited(status),
+            Ok(Some(Reply::Failed(message))) => Event::Failed(message),
+            Ok(Some(_)) => continue,
+            Ok(None) | Err(_) => Event::Failed("the session server went away".to_string()),
+        };
+        let last = !matches!(event, Event::Output(_));
+        if block_on(output.send(event)).is_err() || last {
+            return;
+        }
+    }
+}
+
+// Connects to the session server, if it runs as the user
+fn connect() -> io::Result<UnixStream> {
+    sockets::connect(&socket_path()?)
+}
+
+// Starts this program as the session server when none is running yet. It runs in a process
+// group of its own so it outlives us.
+fn connect_or_start() -> io::Result<UnixStream> {
+    let path = socket_path()?;
+    match sockets::connect(&path) {
+        Err(error) if error.kind() == ErrorKind::PermissionDenied => return Err(error),
+        Err(_) => {}
+        stream => return stream,
+    }
+    let mut server = Command::new(std::env::current_exe()?)
+        .arg("--server")
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .process_group(0)
+        .spawn()?;
+    // Waited for so it is not left a zombie if it ends while we run
+    thread::spawn(move || server.wait());
+
+    let started = Instant::now();
+    loop {
+        thread::sleep(SERVER_START_POLL);
+        match sockets::connect(&path) {
+            Ok(stream) => return Ok(stream),
+            Err(error) if started.elapsed() > SERVER_START_TIMEOUT => return Err(error),
+            Err(_) => {}
+        }
+    }
+}
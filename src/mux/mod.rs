@@ -0,0 +1,13 @@
+pub mod client;
+pub mod protocol;
+mod replay;
+pub mod server;
+
+use std::{io, path::PathBuf};
+
+use crate::sockets;
+
+// Where the session server listens, only the user can get at it there
+pub fn socket_path() -> io::Result<PathBuf> {
+    Ok(sockets::dir()?.join("mux.sock"))
+}
@@ -0,0 +1,348 @@
+use std::{
+    io::{self, Read, Write},
+    os::unix::process::ExitStatusExt,
+    path::PathBuf,
+    process::ExitStatus,
+};
+
+use crate::{
+    structs::{
+        frame::{invalid, Frame, Payload},
+        terminalsize::TerminalSize,
+    },
+    term::{
+        backend::{BackendKind, FlowControl, Parity, SerialSettings},
+        term::{Shell, TermMessage},
+    },
+};
+
+/// What a window asks of the session server. `Open` and `Attach` tie the connection to a
+/// session, `Input` goes to that session's shell from then on.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Request {
+    // Starts a shell in a new session
+    Open {
+        shell: Shell,
+        size: TerminalSize,
+        scrollback: usize,
+    },
+    // Takes a running session over from whichever window had it, its screen is replayed first
+    Attach {
+        session: u64,
+        size: TerminalSize,
+        scrollback: usize,
+    },
+    List,
+    Input(TermMessage),
+}
+
+/// What the session server answers
+#[derive(Debug, Clone, PartialEq)]
+pub enum Reply {
+    Attached(u64),
+    Output(String),
+    Exited(ExitStatus),
+    Failed(String),
+    Sessions(Vec<SessionInfo>),
+}
+
+/// A running session, as listed by `term --list-sessions`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionInfo {
+    pub id: u64,
+    pub program: String,
+    pub title: Option<String>,
+    // A window shows the session right now
+    pub attached: bool,
+}
+
+pub fn write_request(writer: &mut impl Write, request: &Request) -> io::Result<()> {
+    let mut frame = Frame::default();
+    match request {
+        Request::Open {
+            shell,
+            size,
+            scrollback,
+        } => {
+            frame.u8(1);
+            frame.string(&shell.program);
+            frame.strings(&shell.args);
+            let directory = shell.working_directory.as_ref().map(|path| path.to_string_lossy());
+            frame.string(directory.as_deref().unwrap_or(""));
+            frame.string(&shell.encoding.to_string());
+            frame.u8(shell.c1_controls as u8);
+            write_backend(&mut frame, &shell.backend);
+            let socket = shell.control_socket.as_ref().map(|path| path.to_string_lossy());
+            frame.string(socket.as_deref().unwrap_or(""));
+            frame.size(size);
+            frame.u64(*scrollback as u64);
+        }
+        Request::Attach {
+            session,
+            size,
+            scrollback,
+        } => {
+            frame.u8(2);
+            frame.u64(*session);
+            frame.size(size);
+            frame.u64(*scrollback as u64);
+        }
+        Request::List => frame.u8(3),
+        Request::Input(TermMessage::Bytes(bytes)) => {
+            frame.u8(4);
+            frame.bytes(bytes);
+        }
+        Request::Input(TermMessage::WindowResized(size)) => {
+            frame.u8(5);
+            frame.size(size);
+        }
+        Request::Input(TermMessage::SetEncoding(encoding)) => {
+            frame.u8(6);
+            frame.string(&encoding.to_string());
+        }
+        Request::Input(TermMessage::SetC1Controls(c1_controls)) => {
+            frame.u8(7);
+            frame.u8(*c1_controls as u8);
+        }
+    }
+    frame.write_to(writer)
+}
+
+// `None` once the other side hung up between frames
+pub fn read_request(reader: &mut impl Read) -> io::Result<Option<Request>> {
+    let Some(mut payload) = Payload::read_from(reader)? else {
+        return Ok(None);
+    };
+    let request = match payload.u8()? {
+        1 => {
+            let program = payload.string()?;
+            let args = payload.strings()?;
+            let working_directory = Some(payload.string()?)
+                .filter(|directory| !directory.is_empty())
+                .map(PathBuf::from);
+            let encoding = payload.encoding()?;
+            let c1_controls = payload.u8()? != 0;
+            let backend = read_backend(&mut payload)?;
+            let control_socket = Some(payload.string()?)
+                .filter(|socket| !socket.is_empty())
+                .map(PathBuf::from);
+            Request::Open {
+                shell: Shell {
+                    program,
+                    args,
+                    working_directory,
+                    encoding,
+                    c1_controls,
+                    backend,
+                    control_socket,
+                },
+                size: payload.size()?,
+                scrollback: payload.u64()? as usize,
+            }
+        }
+        2 => Request::Attach {
+            session: payload.u64()?,
+            size: payload.size()?,
+            scrollback: payload.u64()? as usize,
+        },
+        3 => Request::List,
+        4 => Request::Input(TermMessage::Bytes(payload.bytes()?)),
+        5 => Request::Input(TermMessage::WindowResized(payload.size()?)),
+        6 => Request::Input(TermMessage::SetEncoding(payload.encoding()?)),
+        7 => Request::Input(TermMessage::SetC1Controls(payload.u8()? != 0)),
+        tag => return Err(invalid(format!("unknown request {}", tag))),
+    };
+    Ok(Some(request))
+}
+
+pub fn write_reply(writer: &mut impl Write, reply: &Reply) -> io::Result<()> {
+    let mut frame = Frame::default();
+    match reply {
+        Reply::Attached(session) => {
+            frame.u8(1);
+            frame.u64(*session);
+        }
+        Reply::Output(text) => {
+            frame.u8(2);
+            frame.string(text);
+        }
+        Reply::Exited(status) => {
+            frame.u8(3);
+            frame.u32(status.into_raw() as u32);
+        }
+        Reply::Failed(message) => {
+            frame.u8(4);
+            frame.string(message);
+        }
+        Reply::Sessions(sessions) => {
+            frame.u8(5);
+            frame.u32(sessions.len() as u32);
+            for session in sessions {
+                frame.u64(session.id);
+                frame.string(&session.program);
+                frame.string(session.title.as_deref().unwrap_or(""));
+                frame.u8(session.attached as u8);
+            }
+        }
+    }
+    frame.write_to(writer)
+}
+
+pub fn read_reply(reader: &mut impl Read) -> io::Result<Option<Reply>> {
+    let Some(mut payload) = Payload::read_from(reader)? else {
+        return Ok(None);
+    };
+    let reply = match payload.u8()? {
+        1 => Reply::Attached(payload.u64()?),
+        2 => Reply::Output(payload.string()?),
+        3 => Reply::Exited(ExitStatus::from_raw(payload.u32()? as i32)),
+        4 => Reply::Failed(payload.string()?),
+        5 => {
+            let sessions = (0..payload.u32()?)
+                .map(|_| {
+                    Ok(SessionInfo {
+                        id: payload.u64()?,
+                        program: payload.string()?,
+                        title: Some(payload.string()?).filter(|title| !title.is_empty()),
+                        attached: payload.u8()? != 0,
+                    })
+                })
+                .collect::<io::Result<_>>()?;
+            Reply::Sessions(sessions)
+        }
+        tag => return Err(invalid(format!("unknown reply {}", tag))),
+    };
+    Ok(Some(reply))
+}
+
+fn write_backend(frame: &mut Frame, backend: &BackendKind) {
+    match backend {
+        BackendKind::Pty => frame.u8(0),
+        BackendKind::Pipe => frame.u8(1),
+        BackendKind::Serial(serial) => {
+            frame.u8(2);
+            frame.string(&serial.device.to_string_lossy());
+            frame.u32(serial.baud);
+            frame.u8(serial.parity as u8);
+            frame.u8(serial.data_bits);
+            frame.u8(serial.stop_bits);
+            frame.u8(serial.flow_control as u8);
+        }
+    }
+}
+
+fn read_backend(payload: &mut Payload) -> io::Result<BackendKind> {
+    let backend = match payload.u8()? {
+        0 => BackendKind::Pty,
+        1 => BackendKind::Pipe,
+        2 => BackendKind::Serial(SerialSettings {
+            device: PathBuf::from(payload.string()?),
+            baud: payload.u32()?,
+            parity: match payload.u8()? {
+                0 => Parity::None,
+                1 => Parity::Even,
+                2 => Parity::Odd,
+                parity => return Err(invalid(format!("unknown parity {}", parity))),
+            },
+            data_bits: payload.u8()?,
+            stop_bits: payload.u8()?,
+            flow_control: match payload.u8()? {
+                0 => FlowControl::None,
+                1 => FlowControl::Hardware,
+                2 => FlowControl::Software,
+                flow => return Err(invalid(format!("unknown flow control {}", flow))),
+            },
+        }),
+        tag => return Err(invalid(format!("unknown backend {}", tag))),
+    };
+    Ok(backend)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::encoding::Encoding;
+
+    fn round_trip_request(request: Request) -> Request {
+        let mut bytes = Vec::new();
+        write_request(&mut bytes, &request).unwrap();
+        read_request(&mut &bytes[..]).unwrap().unwrap()
+    }
+
+    #[test]
+    fn requests_read_back_as_written() {
+        let mut shell = Shell::new("htop".to_string(), vec!["-d".to_string(), "10".to_string()]);
+        shell.working_directory = Some(PathBuf::from("/tmp"));
+        shell.encoding = Encoding::Cp437;
+        shell.control_socket = Some(PathBuf::from("/run/user/1000/term-control-42.sock"));
+        let size = TerminalSize::new(80, 24).with_cell_size(9, 18);
+        let mut console = Shell::new("sh".to_string(), Vec::new());
+        console.backend = BackendKind::Serial(SerialSettings {
+            device: PathBuf::from("/dev/ttyACM0"),
+            baud: 9600,
+            parity: Parity::Odd,
+            data_bits: 7,
+            stop_bits: 2,
+            flow_control: FlowControl::Software,
+        });
+        let requests = [
+            Request::Open {
+                shell,
+                size,
+                scrollback: 1000,
+            },
+            Request::Open {
+                shell: console,
+                size,
+                scrollback: 0,
+            },
+            Request::Attach {
+                session: 7,
+                size,
+                scrollback: 0,
+            },
+            Request::List,
+            Request::Input(TermMessage::Bytes(b"ls\r".to_vec())),
+            Request::Input(TermMessage::WindowResized(size)),
+            Request::Input(TermMessage::SetEncoding(Encoding::Latin1)),
+            Request::Input(TermMessage::SetC1Controls(true)),
+        ];
+        for request in requests {
+            assert_eq!(round_trip_request(request.clone()), request);
+        }
+    }
+
+    #[test]
+    fn replies_read_back_as_written() {
+        let replies = [
+            Reply::Attached(3),
+            Reply::Output("héllo\r\n".to_string()),
+            Reply::Exited(ExitStatus::from_raw(256)),
+            Reply::Failed("no such session".to_string()),
+            Reply::Sessions(vec![SessionInfo {
+                id: 1,
+                program: "/bin/sh".to_string(),
+                title: Some("make".to_string()),
+                attached: false,
+            }]),
+        ];
+        let mut bytes = Vec::new();
+        for reply in &replies {
+            write_reply(&mut bytes, reply).unwrap();
+        }
+        let mut reader = &bytes[..];
+        for reply in replies {
+            assert_eq!(read_reply(&mut reader).unwrap(), Some(reply));
+        }
+        assert_eq!(read_reply(&mut reader).unwrap(), None);
+    }
+
+    #[test]
+    fn broken_frames_are_errors() {
+        let mut bytes = Vec::new();
+        write_reply(&mut bytes, &Reply::Output("cut off".to_string())).unwrap();
+        bytes.truncate(bytes.len() - 2);
+        assert!(read_reply(&mut &bytes[..]).is_err());
+        assert!(read_request(&mut &[1, 0, 0, 0, 99][..]).is_err());
+    }
+}
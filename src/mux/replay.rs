@@ -0,0 +1,213 @@
+use std::fmt::Write;
+
+use crate::{
+    structs::{
+        buffer::Buffer,
+        cell::{Cell, CellStyle, FontWeight, Underline},
+    },
+    term::colors::TerminalColor,
+    terminal::Terminal,
+};
+
+// What a terminal shows, with its scrollback, written out as output that draws it again on a
+// new terminal. Sent to windows attaching to a session that ran without them.
+pub fn replay(terminal: &Terminal) -> String {
+    let mut output = String::new();
+    let primary = terminal.primary_buffer();
+    write_buffer(&mut output, primary, true);
+    if terminal.modes().alt_screen {
+        output.push_str("\x1b[?1049h\x1b[H\x1b[2J");
+        write_buffer(&mut output, terminal.buffer(), false);
+    }
+    if let Some(title) = terminal.title() {
+        let _ = write!(output, "\x1b]2;{}\x07", title);
+    }
+    if !terminal.cursor_visible() {
+        output.push_str("\x1b[?25l");
+    }
+    for (mode, on) in terminal.private_modes() {
+        let _ = write!(output, "\x1b[?{}{}", mode, if on { 'h' } else { 'l' });
+    }
+    let _ = write!(output, "\x1b[{} q", terminal.cursor_style().to_ansi());
+    if let Some(color) = terminal.cursor_color() {
+        let [r, g, b, _] = color.into_rgba8();
+        let _ = write!(output, "\x1b]12;#{:02x}{:02x}{:02x}\x07", r, g, b);
+    }
+    // Last, the window's replies are 8-bit from here on
+    if terminal.eight_bit_controls() {
+        output.push_str("\x1b G");
+    }
+    output
+}
+
+// The history scrolls off the screen on its own as the rows after it are written
+fn write_buffer(output: &mut String, buffer: &Buffer<Cell>, with_history: bool) {
+    let history = buffer.history().filter(|_| with_history);
+    let screen = (0..buffer.rows).map(|row| buffer.row(row));
+    for (index, row) in history.chain(screen).enumerate() {
+        if index > 0 {
+            output.push_str("\r\n");
+        }
+        write_row(output, row);
+    }
+    let (top, bottom) = buffer.top_bottom();
+    if (top, bottom) != (0, buffer.rows - 1) {
+        let _ = write!(output, "\x1b[{};{}r", top + 1, bottom + 1);
+    }
+    let cursor = buffer.cursor;
+    let _ = write!(output, "\x1b[0m\x1b[{};{}H", cursor.row + 1, cursor.col + 1);
+}
+
+// Trailing blank cells are left out, the new terminal starts blank there anyway
+fn write_row(output: &mut String, row: &[Cell]) {
+    let end = row
+        .iter()
+        .rposition(|cell| cell.content != ' ' || cell.style != CellStyle::default())
+        .map_or(0, |last| last + 1);
+    let mut style = CellStyle::default();
+    output.push_str("\x1b[0m");
+    for cell in &row[..end] {
+        if cell.style != style {
+            style = cell.style;
+            write_style(output, &style);
+        }
+        // The wide character before already moved the new terminal's cursor past this cell
+        if !cell.is_spacer() {
+            output.push(cell.content);
+        }
+    }
+}
+
+fn write_style(output: &mut String, style: &CellStyle) {
+    output.push_str("\x1b[0");
+    match style.weight {
+        FontWeight::Bold => output.push_str(";1"),
+        FontWeight::Dim => output.push_str(";2"),
+        FontWeight::Normal => {}
+    }
+    let flags = [
+        (style.italic, ";3"),
+        (style.reversed, ";7"),
+        (style.strikethrough, ";9"),
+        (style.overline, ";53"),
+    ];
+    for (set, code) in flags {
+        if set {
+            output.push_str(code);
+        }
+    }
+    write_color(output, style.foreground, 30);
+    write_color(output, style.background, 40);
+    if let Some(color) = style.underline_color {
+        write_color(output, color, 50);
+    }
+    output.push('m');
+    // Styles other than single and double only come as `4:n` on their own
+    match style.underline {
+        Underline::None => {}
+        Underline::Single => output.push_str("\x1b[4m"),
+        Underline::Double => output.push_str("\x1b[21m"),
+        Underline::Curly => output.push_str("\x1b[4:3m"),
+        Underline::Dotted => output.push_str("\x1b[4:4m"),
+        Underline::Dashed => output.push_str("\x1b[4:5m"),
+    }
+}
+
+// `base` is 30 for the foreground, 40 for the background and 50 for underlines, which only
+// take indexed and direct colors
+fn write_color(output: &mut String, color: TerminalColor, base: u8) {
+    let named = |index: u8| match base {
+        50 => format!(";58;5;{}", index),
+        _ => format!(";{}", base + index),
+    };
+    let code = match color {
+        TerminalColor::Default => return,
+        TerminalColor::Black => named(0),
+        TerminalColor::Red => named(1),
+        TerminalColor::Green => named(2),
+        TerminalColor::Yellow => named(3),
+        TerminalColor::Blue => named(4),
+        TerminalColor::Magenta => named(5),
+        TerminalColor::Cyan => named(6),
+        TerminalColor::White => named(7),
+        TerminalColor::EightBit(index) => format!(";{};5;{}", base + 8, index),
+        TerminalColor::TwentyFourBit(r, g, b) => format!(";{};2;{};{};{}", base + 8, r, g, b),
+    };
+    output.push_str(&code);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        structs::{cursor::CursorStyle, terminalsize::TerminalSize},
+        term::term::Event,
+        term::terminal_output::TerminalOutput,
+    };
+
+    fn terminal(output: &str, scrollback: usize) -> Terminal {
+        let mut terminal = Terminal::new(TerminalSize::new(20, 3));
+        terminal.set_scrollback(scrollback);
+        let _ = terminal.parse(Event::Output(TerminalOutput::split(output)));
+        terminal
+    }
+
+    fn text(buffer: &Buffer<Cell>) -> Vec<String> {
+        let rows = buffer.history().chain((0..buffer.rows).map(|row| buffer.row(row)));
+        rows.map(|row| {
+            row.iter()
+                .map(|cell| cell.content)
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        })
+        .collect()
+    }
+
+    #[test]
+    fn replay_draws_the_screen_and_scrollback_again() {
+        let original = terminal("one\r\n\x1b[1;31mtwo\x1b[0m\r\nthree\r\nfour\x1b[2D", 10);
+        let copy = terminal(&replay(&original), 10);
+        assert_eq!(text(copy.buffer()), ["one", "two", "three", "four"]);
+        assert_eq!(copy.buffer().cursor, original.buffer().cursor);
+        let cell = copy.buffer().row(0)[0];
+        assert_eq!(cell.style.weight, FontWeight::Bold);
+        assert_eq!(cell.style.foreground, TerminalColor::Red);
+    }
+
+    #[test]
+    fn replay_keeps_the_alternate_screen_and_title() {
+        let original = terminal("shell\x1b]2;vim\x07\x1b[?1049h\x1b[2;1Heditor", 10);
+        let copy = terminal(&replay(&original), 10);
+        assert!(copy.modes().alt_screen);
+        assert_eq!(text(copy.buffer()), ["", "editor", ""]);
+        assert_eq!(text(copy.primary_buffer())[0], "shell");
+        assert_eq!(copy.title(), Some("vim"));
+    }
+
+    #[test]
+    fn replay_keeps_the_modes() {
+        let modes = "\x1b[?1h\x1b[?2004h\x1b[?1002h\x1b[?1006h\x1b[?1004h\x1b[?7l\x1b[2;3r";
+        let cursor = "\x1b[5 q\x1b]12;#ff8000\x07\x1b G";
+        let original = terminal(&format!("{}{}", modes, cursor), 10);
+        let copy = terminal(&replay(&original), 10);
+        assert_eq!(copy.private_modes(), original.private_modes());
+        assert_eq!(
+            copy.private_modes(),
+            [
+                (1, true),
+                (7, false),
+                (1004, true),
+                (2004, true),
+                (1002, true),
+                (1006, true)
+            ]
+        );
+        assert!(copy.modes().app_cursor);
+        assert_eq!(copy.buffer().top_bottom(), (1, 2));
+        assert_eq!(copy.cursor_style(), CursorStyle::BlinkingBar);
+        assert_eq!(copy.cursor_color(), original.cursor_color());
+        assert!(copy.cursor_color().is_some());
+        assert!(copy.eight_bit_controls());
+    }
+}
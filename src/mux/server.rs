@@ -0,0 +1,327 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, BufReader, ErrorKind},
+    net::Shutdown,
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    sync::{mpsc, Arc, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use iced::futures::{channel::mpsc::Sender, executor::block_on, SinkExt, StreamExt};
+
+use super::{
+    protocol::{read_request, write_reply, Reply, Request, SessionInfo},
+    replay::replay,
+};
+use crate::{
+    sockets,
+    structs::terminalsize::TerminalSize,
+    term::{
+        term::{Event, Shell, Term, TermMessage},
+        terminal_output::TerminalOutput,
+    },
+    terminal::Terminal,
+};
+
+// A window that takes longer than this to read its output is let go, the shell keeps running
+const WRITE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Shells started for windows, kept running with their screens while no window shows them
+struct Server {
+    path: PathBuf,
+    sessions: Mutex<Sessions>,
+}
+
+#[derive(Default)]
+struct Sessions {
+    by_id: HashMap<u64, ServerSession>,
+    next_id: u64,
+    // Tells a window's connection from the ones that had its session before
+    next_client: u64,
+}
+
+struct ServerSession {
+    program: String,
+    // Follows the shell's output so a window attaching later can be shown the screen
+    terminal: Terminal,
+    input: Option<Sender<TermMessage>>,
+    // Input that came before the shell was started
+    pending: Vec<TermMessage>,
+    client: Option<Client>,
+}
+
+/// A window's connection. Replies are written on a thread of their own, so a window slow to
+/// read holds up neither the shell nor the other sessions. Dropping the client closes the
+/// connection once what was queued is written.
+struct Client {
+    id: u64,
+    replies: mpsc::Sender<Reply>,
+    writer: JoinHandle<()>,
+}
+
+impl Client {
+    fn new(id: u64, mut stream: UnixStream) -> Self {
+        let (replies, queue) = mpsc::channel::<Reply>();
+        let writer = thread::spawn(move || {
+            for reply in queue {
+                if write_reply(&mut stream, &reply).is_err() {
+                    break;
+                }
+            }
+            let _ = stream.shutdown(Shutdown::Both);
+        });
+        Self { id, replies, writer }
+    }
+
+    // Waits for the queued replies to be written, or for the window to be let go
+    fn close(self) {
+        drop(self.replies);
+        let _ = self.writer.join();
+    }
+}
+
+impl ServerSession {
+    // Losing the window leaves the session running, detached
+    fn send(&mut self, reply: Reply) {
+        let Some(client) = &self.client else {
+            return;
+        };
+        if client.replies.send(reply).is_err() {
+            self.client = None;
+        }
+    }
+}
+
+// Listens on `path` until the last session ends. Fails if another server already does.
+pub fn run(path: &Path) -> io::Result<()> {
+    if UnixStream::connect(path).is_ok() {
+        return Err(io::Error::new(
+            ErrorKind::AddrInUse,
+            format!("a session server is already running on {}", path.display()),
+        ));
+    }
+    // Left behind by a server that did not get to clean up
+    let _ = fs::remove_file(path);
+    let listener = sockets::bind(path)?;
+
+    let server = Arc::new(Server {
+        path: path.to_path_buf(),
+        sessions: Mutex::default(),
+    });
+    for stream in listener.incoming() {
+        match stream {
+            Ok(stream) => {
+                let server = server.clone();
+                thread::spawn(move || server.serve(stream));
+            }
+            Err(error) => eprintln!("Session server could not accept a window: {}", error),
+        }
+    }
+    Ok(())
+}
+
+impl Server {
+    fn serve(self: Arc<Self>, mut stream: UnixStream) {
+        let _ = stream.set_write_timeout(Some(WRITE_TIMEOUT));
+        let Ok(reader) = stream.try_clone() else {
+            return;
+        };
+        let mut reader = BufReader::new(reader);
+        let attached = match read_request(&mut reader) {
+            Ok(Some(Request::Open {
+                shell,
+                size,
+                scrollback,
+            })) => self.open(shell, size, scrollback, stream),
+            Ok(Some(Request::Attach {
+                session,
+                size,
+                scrollback,
+            })) => self.attach(session, size, scrollback, stream),
+            Ok(Some(Request::List)) => {
+                let _ = write_reply(&mut stream, &Reply::Sessions(self.list()));
+                None
+            }
+            Ok(Some(Request::Input(_))) | Ok(None) | Err(_) => None,
+        };
+        let Some((session, client)) = attached else {
+            return;
+        };
+
+        // Everything after the first request is input for the session's shell
+        while let Ok(Some(Request::Input(message))) = read_request(&mut reader) {
+            let mut input = {
+                let mut sessions = self.sessions.lock().unwrap();
+                let Some(server_session) = sessions.by_id.get_mut(&session) else {
+                    break;
+                };
+                if let TermMessage::WindowResized(size) = message {
+                    let _ = server_session.terminal.resize(size);
+                }
+                match &server_session.input {
+                    Some(input) => input.clone(),
+                    None => {
+                        server_session.pending.push(message);
+                        continue;
+                    }
+                }
+            };
+            let _ = block_on(input.send(message));
+        }
+        self.detach(session, client);
+    }
+
+    fn open(
+        self: &Arc<Self>,
+        shell: Shell,
+        size: TerminalSize,
+        scrollback: usize,
+        stream: UnixStream,
+    ) -> Option<(u64, u64)> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let (id, client) = (sessions.next_id, sessions.next_client);
+        let window = Client::new(client, stream);
+        window.replies.send(Reply::Attached(id)).ok()?;
+        sessions.next_id += 1;
+        sessions.next_client += 1;
+
+        let mut terminal = Terminal::new(size);
+        terminal.set_scrollback(scrollback);
+        terminal.set_c1_controls(shell.c1_controls);
+        sessions.by_id.insert(
+            id,
+            ServerSession {
+                program: shell.name(),
+                terminal,
+                input: None,
+                pending: Vec::new(),
+                client: Some(window),
+            },
+        );
+        let server = self.clone();
+        thread::spawn(move || server.run_session(id, shell, size));
+        Some((id, client))
+    }
+
+    // Takes the session from the window that had it and shows the new one what it missed
+    fn attach(
+        &self,
+        session: u64,
+        size: TerminalSize,
+        scrollback: usize,
+        mut stream: UnixStream,
+    ) -> Option<(u64, u64)> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let client = sessions.next_client;
+        let Some(server_session) = sessions.by_id.get_mut(&session) else {
+            let message = format!("there is no session {}", session);
+            let _ = write_reply(&mut stream, &Reply::Failed(message));
+            return None;
+        };
+        // The other window's connection closes once it is told
+        server_session.send(Reply::Failed("the session was attached in another window".to_string()));
+        server_session.client = None;
+
+        server_session.terminal.set_scrollback(scrollback);
+        let _ = server_session.terminal.resize(size);
+        if let Some(input) = &mut server_session.input {
+            let _ = input.try_send(TermMessage::WindowResized(size));
+        }
+        let window = Client::new(client, stream);
+        window.replies.send(Reply::Attached(session)).ok()?;
+        window
+            .replies
+            .send(Reply::Output(replay(&server_session.terminal)))
+            .ok()?;
+        server_session.client = Some(window);
+        sessions.next_client += 1;
+        Some((session, client))
+    }
+
+    // Only if the window still has the session, it may have been attached elsewhere since
+    fn detach(&self, session: u64, client: u64) {
+        let mut sessions = self.sessions.lock().unwrap();
+        let Some(server_session) = sessions.by_id.get_mut(&session) else {
+            return;
+        };
+        if server_session
+            .client
+            .as_ref()
+            .is_some_and(|attached| attached.id == client)
+        {
+            server_session.client = None;
+        }
+    }
+
+    fn list(&self) -> Vec<SessionInfo> {
+        let sessions = self.sessions.lock().unwrap();
+        let mut list: Vec<_> = sessions
+            .by_id
+            .iter()
+            .map(|(id, session)| SessionInfo {
+                id: *id,
+                program: session.program.clone(),
+                title: session.terminal.title().map(str::to_string),
+                attached: session.client.is_some(),
+            })
+            .collect();
+        list.sort_by_key(|session| session.id);
+        list
+    }
+
+    // Runs the shell, keeping the session's screen up to date and passing its output on to
+    // the window showing it
+    fn run_session(&self, id: u64, shell: Shell, size: TerminalSize) {
+        let mut events = Box::pin(Term::spawn(size.winsize(), shell));
+        block_on(async {
+            while let Some(event) = events.next().await {
+                let mut sessions = self.sessions.lock().unwrap();
+                let Some(session) = sessions.by_id.get_mut(&id) else {
+                    return;
+                };
+                match event {
+                    Event::Ready(mut input) => {
+                        for message in session.pending.drain(..) {
+                            let _ = input.try_send(message);
+                        }
+                        session.input = Some(input);
+                    }
+                    Event::Output(output) => {
+                        let text = TerminalOutput::join(&output);
+                        let _ = session.terminal.parse(Event::Output(output));
+                        session.send(Reply::Output(text));
+                    }
+                    Event::Exited(status) => {
+                        session.send(Reply::Exited(status));
+                        break;
+                    }
+                    Event::Failed(message) => {
+                        session.send(Reply::Failed(message));
+                        break;
+                    }
+                }
+            }
+        });
+        self.end_session(id);
+    }
+
+    // The server goes with its last session, there would be nothing left to attach to
+    fn end_session(&self, id: u64) {
+        let (client, last) = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let client = sessions.by_id.remove(&id).and_then(|session| session.client);
+            (client, sessions.by_id.is_empty())
+        };
+        // The window is told how the shell ended before the server exits
+        if let Some(client) = client {
+            client.close();
+        }
+        if last {
+            let _ = fs::remove_file(&self.path);
+            std::process::exit(0);
+        }
+    }
+}
@@ -0,0 +1,407 @@
+use std::{
+    collections::BTreeMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use iced::Color;
+use serde::Deserialize;
+use toml_edit::{ImDocument, Item, TableLike};
+
+use super::{parse_hex, themes, Palette, ThemeError};
+
+// Names of the 8 ANSI colors, in order, the way Alacritty writes them
+const ANSI_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+
+// The same for Windows Terminal, which calls magenta purple
+const WINDOWS_NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "purple", "cyan", "white"];
+
+// Which base16 color every ANSI color takes, the mapping base16's shell templates use
+const BASE16_ANSI: [usize; 16] = [
+    0x00, 0x08, 0x0b, 0x0a, 0x0d, 0x0e, 0x0c, 0x05, 0x03, 0x08, 0x0b, 0x0a, 0x0d, 0x0e, 0x0c, 0x07,
+];
+
+// Extensions tried, in order, for themes named without one
+const EXTENSIONS: [&str; 5] = ["toml", "yml", "yaml", "itermcolors", "json"];
+
+// Finds the theme called `name`. Names with an extension or a slash are paths relative to
+// `dir`, other names are looked up in `dir/themes` first, so a team's scheme can replace a
+// built-in one, and then among the built-in themes.
+pub fn find(name: &str, dir: &Path) -> Result<Palette, ThemeError> {
+    if name.contains('/') || Path::new(name).extension().is_some() {
+        return import(&dir.join(name));
+    }
+    let themes = dir.join("themes");
+    if let Some(path) = EXTENSIONS
+        .iter()
+        .map(|extension| themes.join(format!("{}.{}", name, extension)))
+        .find(|path| path.exists())
+    {
+        return import(&path);
+    }
+    themes::builtin(name).ok_or_else(|| ThemeError::NotFound(name.to_string()))
+}
+
+// Reads a theme file, telling its format by the extension. Colors the file leaves out keep
+// the default theme's.
+pub fn import(path: &Path) -> Result<Palette, ThemeError> {
+    let source = fs::read_to_string(path).map_err(|error| ThemeError::Read(path.to_path_buf(), error))?;
+    let extension = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .unwrap_or_default();
+    let palette = match extension.to_lowercase().as_str() {
+        "toml" => alacritty_toml(&source),
+        "yml" | "yaml" => yaml(&source),
+        "itermcolors" => iterm(&source),
+        "json" => windows_terminal(&source),
+        _ => return Err(ThemeError::UnknownFormat(path.to_path_buf())),
+    };
+    palette.map_err(|error| invalid(path, error))
+}
+
+fn invalid(path: &Path, message: String) -> ThemeError {
+    ThemeError::Invalid(PathBuf::from(path), message)
+}
+
+fn color(key: &str, value: &str) -> Result<Color, String> {
+    parse_hex(value).ok_or_else(|| format!("`{}` should be a color like \"#rrggbb\", found \"{}\"", key, value))
+}
+
+// Alacritty's TOML themes, the YAML ones are the same keys
+fn alacritty_toml(source: &str) -> Result<Palette, String> {
+    let document = ImDocument::parse(source).map_err(|error| error.to_string())?;
+    let mut values = Vec::new();
+    flatten(document.as_table(), "", &mut values);
+    let mut palette = alacritty(&values)?;
+
+    let indexed = document
+        .get("colors")
+        .and_then(|colors| colors.get("indexed_colors"))
+        .and_then(Item::as_array_of_tables);
+    for table in indexed.into_iter().flatten() {
+        let index = table.get("index").and_then(Item::as_integer);
+        let value = table.get("color").and_then(Item::as_str);
+        if let (Some(index), Some(value)) = (index.and_then(|index| u8::try_from(index).ok()), value) {
+            palette.set_indexed(index, color("colors.indexed_colors", value)?);
+        }
+    }
+    Ok(palette)
+}
+
+// Collects every string in `table` under its dotted key
+fn flatten(table: &dyn TableLike, prefix: &str, values: &mut Vec<(String, String)>) {
+    for (key, item) in table.iter() {
+        let key = if prefix.is_empty() {
+            key.to_string()
+        } else {
+            format!("{}.{}", prefix, key)
+        };
+        if let Some(table) = item.as_table_like() {
+            flatten(table, &key, values);
+        } else if let Some(value) = item.as_str() {
+            values.push((key, value.to_string()));
+        }
+    }
+}
+
+/// The colors of an Alacritty YAML theme. Strings are read as written, so hex colors that look
+/// like numbers stay hex colors.
+#[derive(Debug, Deserialize)]
+struct AlacrittyYaml {
+    colors: AlacrittyColors,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AlacrittyColors {
+    primary: BTreeMap<String, String>,
+    cursor: BTreeMap<String, String>,
+    selection: BTreeMap<String, String>,
+    normal: BTreeMap<String, String>,
+    bright: BTreeMap<String, String>,
+    indexed_colors: Vec<IndexedColor>,
+}
+
+#[derive(Debug, Deserialize)]
+struct IndexedColor {
+    index: u8,
+    color: String,
+}
+
+/// A base16 scheme in the newer format, with its colors under `palette`
+#[derive(Debug, Deserialize)]
+struct Base16Palette {
+    palette: BTreeMap<String, String>,
+}
+
+// Alacritty themes keep their colors under `colors`, base16 schemes at the top or under `palette`
+fn yaml(source: &str) -> Result<Palette, String> {
+    let document: serde_yaml::Value = serde_yaml::from_str(source).map_err(|error| error.to_string())?;
+    if document.get("colors").is_some() {
+        return alacritty_yaml(source);
+    }
+    let colors = if document.get("palette").is_some() {
+        serde_yaml::from_str::<Base16Palette>(source).map(|scheme| scheme.palette)
+    } else {
+        serde_yaml::from_str::<BTreeMap<String, String>>(source)
+    };
+    base16(&colors.map_err(|error| error.to_string())?)
+}
+
+fn alacritty_yaml(source: &str) -> Result<Palette, String> {
+    let theme: AlacrittyYaml = serde_yaml::from_str(source).map_err(|error| error.to_string())?;
+    let colors = theme.colors;
+    let sections = [
+        ("primary", colors.primary),
+        ("cursor", colors.cursor),
+        ("selection", colors.selection),
+        ("normal", colors.normal),
+        ("bright", colors.bright),
+    ];
+    let values: Vec<(String, String)> = sections
+        .into_iter()
+        .flat_map(|(section, values)| {
+            values
+                .into_iter()
+                .map(move |(key, value)| (format!("colors.{}.{}", section, key), value))
+        })
+        .collect();
+    let mut palette = alacritty(&values)?;
+    for indexed in colors.indexed_colors {
+        palette.set_indexed(indexed.index, color("colors.indexed_colors", &indexed.color)?);
+    }
+    Ok(palette)
+}
+
+fn alacritty(values: &[(String, String)]) -> Result<Palette, String> {
+    let mut palette = Palette::default();
+    for (key, value) in values {
+        // Cursor and selection may take the colors of the cell under them, we don't do that
+        if value.starts_with("Cell") {
+            continue;
+        }
+        match key.as_str() {
+            "colors.primary.foreground" => palette.foreground = color(key, value)?,
+            "colors.primary.background" => palette.background = color(key, value)?,
+            "colors.cursor.cursor" => palette.cursor = color(key, value)?,
+            "colors.selection.background" => palette.selection = color(key, value)?,
+            _ => {
+                let ansi = key
+                    .strip_prefix("colors.normal.")
+                    .map(|name| (name, 0))
+                    .or_else(|| key.strip_prefix("colors.bright.").map(|name| (name, 8)));
+                let Some((name, offset)) = ansi else {
+                    continue;
+                };
+                if let Some(index) = ANSI_NAMES.iter().position(|ansi| *ansi == name) {
+                    palette.set_indexed((index + offset) as u8, color(key, value)?);
+                }
+            }
+        }
+    }
+    Ok(palette)
+}
+
+// base16 schemes only have 16 colors, both the ANSI colors and the UI ones are picked from them
+fn base16(values: &BTreeMap<String, String>) -> Result<Palette, String> {
+    let mut base = [Color::BLACK; 16];
+    for (index, base) in base.iter_mut().enumerate() {
+        let key = format!("base{:02X}", index);
+        let (key, value) = values
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(&key))
+            .ok_or_else(|| format!("`{}` is missing", key))?;
+        *base = color(key, value)?;
+    }
+    let mut palette = Palette::new(BASE16_ANSI.map(|index| base[index]), base[0x05], base[0x00]);
+    palette.cursor = base[0x05];
+    palette.selection = base[0x02];
+    Ok(palette)
+}
+
+// iTerm2's property lists, a dict of named colors that are dicts of components from 0 to 1
+fn iterm(source: &str) -> Result<Palette, String> {
+    let document = roxmltree::Document::parse(source).map_err(|error| error.to_string())?;
+    let colors = document
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("dict"))
+        .ok_or_else(|| "there is no dict of colors".to_string())?;
+
+    let mut palette = Palette::default();
+    for (name, dict) in key_values(colors) {
+        let component = |component: &str| {
+            key_values(dict)
+                .find(|(key, _)| *key == format!("{} Component", component))
+                .and_then(|(_, value)| value.text()?.trim().parse::<f32>().ok())
+        };
+        let (Some(r), Some(g), Some(b)) = (component("Red"), component("Green"), component("Blue")) else {
+            return Err(format!("`{}` needs a red, green and blue component", name));
+        };
+        let color = Color::from_rgb(r, g, b);
+        match name {
+            "Foreground Color" => palette.foreground = color,
+            "Background Color" => palette.background = color,
+            "Cursor Color" => palette.cursor = color,
+            "Selection Color" => palette.selection = color,
+            _ => {
+                let index = name
+                    .strip_prefix("Ansi ")
+                    .and_then(|name| name.strip_suffix(" Color"))
+                    .and_then(|index| index.parse::<u8>().ok())
+                    .filter(|index| *index < 16);
+                if let Some(index) = index {
+                    palette.set_indexed(index, color);
+                }
+            }
+        }
+    }
+    Ok(palette)
+}
+
+// The `<key>` elements of a plist dict with the element following each of them
+fn key_values<'a, 'input>(
+    dict: roxmltree::Node<'a, 'input>,
+) -> impl Iterator<Item = (&'a str, roxmltree::Node<'a, 'input>)> {
+    let mut elements = dict.children().filter(|node| node.is_element());
+    std::iter::from_fn(move || loop {
+        let key = elements.next()?;
+        if key.has_tag_name("key") {
+            return Some((key.text().unwrap_or_default(), elements.next()?));
+        }
+    })
+}
+
+// Either a single scheme, or a settings file of which the first scheme is taken. Plain JSON
+// only: the comments Windows Terminal writes into its settings file are errors, export the
+// scheme on its own instead.
+fn windows_terminal(source: &str) -> Result<Palette, String> {
+    let settings: serde_json::Value = serde_json::from_str(source).map_err(|error| error.to_string())?;
+    let scheme = match settings.get("schemes") {
+        Some(serde_json::Value::Array(schemes)) => schemes.first().ok_or_else(|| "`schemes` is empty".to_string())?,
+        _ => &settings,
+    };
+    let mut palette = Palette::default();
+    let mut set = |key: &str, assign: &mut dyn FnMut(&mut Palette, Color)| -> Result<(), String> {
+        if let Some(value) = scheme.get(key) {
+            let value = value.as_str().ok_or_else(|| format!("`{}` should be a string", key))?;
+            assign(&mut palette, color(key, value)?);
+        }
+        Ok(())
+    };
+    set("foreground", &mut |palette, color| palette.foreground = color)?;
+    set("background", &mut |palette, color| palette.background = color)?;
+    set("cursorColor", &mut |palette, color| palette.cursor = color)?;
+    set("selectionBackground", &mut |palette, color| palette.selection = color)?;
+    for (index, name) in WINDOWS_NAMES.iter().enumerate() {
+        set(name, &mut |palette, color| palette.set_indexed(index as u8, color))?;
+        let bright = format!("bright{}{}", name[..1].to_uppercase(), &name[1..]);
+        set(&bright, &mut |palette, color| {
+            palette.set_indexed(index as u8 + 8, color)
+        })?;
+    }
+    Ok(palette)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Color = Color {
+        r: 1.0,
+        g: 0.0,
+        b: 0.0,
+        a: 1.0,
+    };
+
+    #[test]
+    fn alacritty_toml_and_yaml() {
+        let toml = alacritty_toml(
+            "[colors.primary]\nbackground = '#000000'\n[colors.normal]\nred = '0xff0000'\n[colors.cursor]\ncursor = 'CellForeground'\n[[colors.indexed_colors]]\nindex = 16\ncolor = '#ff0000'\n",
+        )
+        .unwrap();
+        assert_eq!(toml.background, Color::BLACK);
+        assert_eq!(toml.indexed(1), RED);
+        assert_eq!(toml.indexed(16), RED);
+
+        let from_yaml = yaml(
+            "colors:\n  bright:\n    red: '#ff0000'\n  search:\n    matches: { foreground: '#000000' }\n  indexed_colors:\n    - { index: 17, color: '0xff0000' }\n",
+        )
+        .unwrap();
+        assert_eq!(from_yaml.indexed(9), RED);
+        assert_eq!(from_yaml.indexed(17), RED);
+        assert!(yaml("colors:\n  normal: [red]\n").is_err());
+    }
+
+    #[test]
+    fn base16_maps_onto_ansi_colors() {
+        // Unquoted, the way many schemes write them, even where they look like numbers
+        let source: String = (0..16)
+            .map(|index| {
+                let value = match index {
+                    8 => "ff0000",
+                    7 => "282e36",
+                    _ => "000000",
+                };
+                format!("base{:02X}: {}\n", index, value)
+            })
+            .collect();
+        let palette = yaml(&format!("scheme: Test\n{}", source)).unwrap();
+        assert_eq!(palette.indexed(1), RED);
+        assert_eq!(palette.indexed(9), RED);
+        assert_eq!(palette.indexed(15), Color::from_rgb8(0x28, 0x2e, 0x36));
+        assert_eq!(palette.background, Color::BLACK);
+
+        let nested = source.replace("base", "  base");
+        let palette = yaml(&format!("system: base16\npalette:\n{}", nested)).unwrap();
+        assert_eq!(palette.indexed(1), RED);
+        assert_eq!(yaml("base00: 000000").unwrap_err(), "`base01` is missing");
+    }
+
+    #[test]
+    fn iterm_property_lists() {
+        let palette = iterm(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <plist version="1.0"><dict>
+                <key>Ansi 1 Color</key>
+                <dict>
+                    <key>Color Space</key><string>sRGB</string>
+                    <key>Blue Component</key><real>0</real>
+                    <key>Green Component</key><real>0</real>
+                    <key>Red Component</key><real>1</real>
+                </dict>
+                <key>Background Color</key>
+                <dict>
+                    <key>Blue Component</key><real>0</real>
+                    <key>Green Component</key><real>0</real>
+                    <key>Red Component</key><real>0</real>
+                </dict>
+            </dict></plist>"#,
+        )
+        .unwrap();
+        assert_eq!(palette.indexed(1), RED);
+        assert_eq!(palette.background, Color::BLACK);
+    }
+
+    #[test]
+    fn windows_terminal_schemes() {
+        let palette = windows_terminal(
+            r##"{ "schemes": [{ "name": "Red", "purple": "#ff0000", "brightRed": "#FF0000", "background": "#000000" }] }"##,
+        )
+        .unwrap();
+        assert_eq!(palette.indexed(5), RED);
+        assert_eq!(palette.indexed(9), RED);
+        assert_eq!(palette.background, Color::BLACK);
+        assert!(windows_terminal(r#"{ "red": 12 }"#).is_err());
+        assert!(windows_terminal("{\n// comment\n}").is_err());
+    }
+
+    #[test]
+    fn names_fall_back_to_built_in_themes() {
+        let dir = Path::new("/nonexistent");
+        assert!(find("dracula", dir).is_ok());
+        assert!(matches!(find("nope", dir), Err(ThemeError::NotFound(_))));
+        assert!(matches!(find("scheme.txt", dir), Err(ThemeError::Read(..))));
+    }
+}
@@ -0,0 +1,177 @@
+pub mod import;
+pub mod themes;
+
+use std::{fmt, io, path::PathBuf};
+
+use iced::Color;
+
+use crate::term::colors::TerminalColor;
+
+/// Every color the terminal draws with: the 256 indexed colors programs pick from, of which
+/// the first 16 come from the theme, and the colors of everything without a color of its own
+#[derive(Debug, Clone, PartialEq)]
+pub struct Palette {
+    indexed: [Color; 256],
+    pub foreground: Color,
+    pub background: Color,
+    pub cursor: Color,
+    pub selection: Color,
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Read(PathBuf, io::Error),
+    // The file parsed as far as its format goes, but `message` says what is wrong with it
+    Invalid(PathBuf, String),
+    UnknownFormat(PathBuf),
+    NotFound(String),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Read(path, error) => write!(formatter, "could not read {}: {}", path.display(), error),
+            ThemeError::Invalid(path, message) => write!(formatter, "{}: {}", path.display(), message),
+            ThemeError::UnknownFormat(path) => write!(
+                formatter,
+                "{} is not a theme format we can import, use .toml, .yml, .yaml, .itermcolors or .json",
+                path.display()
+            ),
+            ThemeError::NotFound(name) => write!(
+                formatter,
+                "`{}` is neither a built-in theme nor a file in the themes directory",
+                name
+            ),
+        }
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        themes::builtin(themes::DEFAULT).expect("Default theme is built in")
+    }
+}
+
+impl Palette {
+    // The colors past the first 16 are the same for every theme: a 6×6×6 color cube
+    // followed by 24 grays
+    pub fn new(ansi: [Color; 16], foreground: Color, background: Color) -> Self {
+        let mut indexed = [Color::BLACK; 256];
+        indexed[..16].copy_from_slice(&ansi);
+        for (index, color) in indexed.iter_mut().enumerate().skip(16) {
+            *color = cube_color(index as u8);
+        }
+        Self {
+            indexed,
+            foreground,
+            background,
+            cursor: foreground,
+            selection: ansi[8],
+        }
+    }
+
+    pub fn indexed(&self, index: u8) -> Color {
+        self.indexed[index as usize]
+    }
+
+    pub fn set_indexed(&mut self, index: u8, color: Color) {
+        self.indexed[index as usize] = color;
+    }
+
+    pub fn foreground_color(&self, color: TerminalColor) -> Color {
+        match color {
+            TerminalColor::Default => self.foreground,
+            color => self.resolve(color),
+        }
+    }
+
+    // Default backgrounds are transparent, the window's background shows through
+    pub fn background_color(&self, color: TerminalColor) -> Color {
+        match color {
+            TerminalColor::Default => Color::TRANSPARENT,
+            color => self.resolve(color),
+        }
+    }
+
+    fn resolve(&self, color: TerminalColor) -> Color {
+        match color {
+            TerminalColor::Black => self.indexed(0),
+            TerminalColor::Red => self.indexed(1),
+            TerminalColor::Green => self.indexed(2),
+            TerminalColor::Yellow => self.indexed(3),
+            TerminalColor::Blue => self.indexed(4),
+            TerminalColor::Magenta => self.indexed(5),
+            TerminalColor::Cyan => self.indexed(6),
+            TerminalColor::White => self.indexed(7),
+            TerminalColor::EightBit(index) => self.indexed(index),
+            TerminalColor::TwentyFourBit(r, g, b) => Color::from_rgb8(r, g, b),
+            TerminalColor::Default => self.foreground,
+        }
+    }
+}
+
+fn cube_color(index: u8) -> Color {
+    if index >= 232 {
+        let gray = (index - 232) * 10 + 8;
+        return Color::from_rgb8(gray, gray, gray);
+    }
+    let scale = |n: u8| if n == 0 { 0 } else { n * 40 + 55 };
+    let index = index - 16;
+    Color::from_rgb8(scale(index / 36), scale(index / 6 % 6), scale(index % 6))
+}
+
+// Reads `#rrggbb`, `0xrrggbb` or bare `rrggbb`, the ways theme files write colors
+pub fn parse_hex(hex: &str) -> Option<Color> {
+    let hex = hex.trim();
+    let hex = hex
+        .strip_prefix('#')
+        .or_else(|| hex.strip_prefix("0x"))
+        .or_else(|| hex.strip_prefix("0X"))
+        .unwrap_or(hex);
+    if hex.len() != 6 {
+        return None;
+    }
+    let component = |index: usize| u8::from_str_radix(hex.get(index..index + 2)?, 16).ok();
+    Some(Color::from_rgb8(component(0)?, component(2)?, component(4)?))
+}
+
+// Goes `amount` of the way from one color to another, for shades between the theme's colors
+pub fn mix(from: Color, to: Color, amount: f32) -> Color {
+    Color::from_rgb(
+        from.r + (to.r - from.r) * amount,
+        from.g + (to.g - from.g) * amount,
+        from.b + (to.b - from.b) * amount,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexed_colors_past_the_theme_are_computed() {
+        let palette = Palette::default();
+        assert_eq!(palette.indexed(16), Color::from_rgb8(0, 0, 0));
+        assert_eq!(palette.indexed(196), Color::from_rgb8(255, 0, 0));
+        assert_eq!(palette.indexed(232), Color::from_rgb8(8, 8, 8));
+        assert_eq!(palette.indexed(255), Color::from_rgb8(238, 238, 238));
+    }
+
+    #[test]
+    fn named_colors_come_from_the_theme() {
+        let mut palette = Palette::default();
+        palette.set_indexed(1, Color::from_rgb8(1, 2, 3));
+        assert_eq!(palette.foreground_color(TerminalColor::Red), Color::from_rgb8(1, 2, 3));
+        assert_eq!(palette.foreground_color(TerminalColor::Default), palette.foreground);
+        assert_eq!(palette.background_color(TerminalColor::Default), Color::TRANSPARENT);
+    }
+
+    #[test]
+    fn hex_colors() {
+        let orange = Some(Color::from_rgb8(255, 136, 0));
+        assert_eq!(parse_hex("#ff8800"), orange);
+        assert_eq!(parse_hex("0xFF8800"), orange);
+        assert_eq!(parse_hex("ff8800"), orange);
+        assert_eq!(parse_hex("#f80"), None);
+    }
+}
@@ -0,0 +1,98 @@
+use iced::Color;
+
+use super::Palette;
+
+pub const DEFAULT: &str = "default";
+
+// A theme as 0xrrggbb values, the 16 ANSI colors go black, red, green, yellow, blue, magenta,
+// cyan, white and then the same again in bright
+struct Theme {
+    name: &'static str,
+    foreground: u32,
+    background: u32,
+    cursor: u32,
+    selection: u32,
+    ansi: [u32; 16],
+}
+
+const THEMES: &[Theme] = &[
+    Theme {
+        name: DEFAULT,
+        foreground: 0xffffff,
+        background: 0x1e1e2d,
+        cursor: 0xb8c0e0,
+        selection: 0x5b6078,
+        ansi: [
+            0x494d64, 0xed8796, 0xa6da95, 0xeed49f, 0x8aadf4, 0xf5bde6, 0x8bd5ca, 0xb8c0e0, //
+            0x5b6078, 0xed8796, 0xa6da95, 0xeed49f, 0x8aadf4, 0xf5bde6, 0x8bd5ca, 0xa5adcb,
+        ],
+    },
+    Theme {
+        name: "catppuccin-macchiato",
+        foreground: 0xcad3f5,
+        background: 0x24273a,
+        cursor: 0xf4dbd6,
+        selection: 0x5b6078,
+        ansi: [
+            0x494d64, 0xed8796, 0xa6da95, 0xeed49f, 0x8aadf4, 0xf5bde6, 0x8bd5ca, 0xb8c0e0, //
+            0x5b6078, 0xed8796, 0xa6da95, 0xeed49f, 0x8aadf4, 0xf5bde6, 0x8bd5ca, 0xa5adcb,
+        ],
+    },
+    Theme {
+        name: "dracula",
+        foreground: 0xf8f8f2,
+        background: 0x282a36,
+        cursor: 0xf8f8f2,
+        selection: 0x44475a,
+        ansi: [
+            0x21222c, 0xff5555, 0x50fa7b, 0xf1fa8c, 0xbd93f9, 0xff79c6, 0x8be9fd, 0xf8f8f2, //
+            0x6272a4, 0xff6e6e, 0x69ff94, 0xffffa5, 0xd6acff, 0xff92df, 0xa4ffff, 0xffffff,
+        ],
+    },
+    Theme {
+        name: "gruvbox-dark",
+        foreground: 0xebdbb2,
+        background: 0x282828,
+        cursor: 0xebdbb2,
+        selection: 0x504945,
+        ansi: [
+            0x282828, 0xcc241d, 0x98971a, 0xd79921, 0x458588, 0xb16286, 0x689d6a, 0xa89984, //
+            0x928374, 0xfb4934, 0xb8bb26, 0xfabd2f, 0x83a598, 0xd3869b, 0x8ec07c, 0xebdbb2,
+        ],
+    },
+    Theme {
+        name: "solarized-dark",
+        foreground: 0x839496,
+        background: 0x002b36,
+        cursor: 0x93a1a1,
+        selection: 0x073642,
+        ansi: [
+            0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5, //
+            0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+        ],
+    },
+    Theme {
+        name: "solarized-light",
+        foreground: 0x657b83,
+        background: 0xfdf6e3,
+        cursor: 0x586e75,
+        selection: 0xeee8d5,
+        ansi: [
+            0x073642, 0xdc322f, 0x859900, 0xb58900, 0x268bd2, 0xd33682, 0x2aa198, 0xeee8d5, //
+            0x002b36, 0xcb4b16, 0x586e75, 0x657b83, 0x839496, 0x6c71c4, 0x93a1a1, 0xfdf6e3,
+        ],
+    },
+];
+
+pub fn builtin(name: &str) -> Option<Palette> {
+    let theme = THEMES.iter().find(|theme| theme.name.eq_ignore_ascii_case(name))?;
+    let mut palette = Palette::new(theme.ansi.map(rgb), rgb(theme.foreground), rgb(theme.background));
+    palette.cursor = rgb(theme.cursor);
+    palette.selection = rgb(theme.selection);
+    Some(palette)
+}
+
+fn rgb(color: u32) -> Color {
+    let [_, r, g, b] = color.to_be_bytes();
+    Color::from_rgb8(r, g, b)
+}
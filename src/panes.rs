@@ -0,0 +1,253 @@
+use iced::{
+    widget::pane_grid::{self, Axis, Direction, Node, Pane},
+    Point, Rectangle, Size,
+};
+
+// Room between panes, dragging it resizes them
+pub const PANE_SPACING: f32 = 4.0;
+
+// Resizing stops before a pane gets smaller than this part of the split
+const MIN_RATIO: f32 = 0.1;
+
+/// The panes of a tab, splits of its area with something in each of them, one of them focused.
+/// Like `Tabs` there is always at least one.
+pub struct Panes<T> {
+    state: pane_grid::State<T>,
+    focused: Pane,
+}
+
+impl<T> Panes<T> {
+    pub fn new(first: T) -> Self {
+        let (state, focused) = pane_grid::State::new(first);
+        Self { state, focused }
+    }
+
+    pub fn state(&self) -> &pane_grid::State<T> {
+        &self.state
+    }
+
+    pub fn focused(&self) -> &T {
+        self.state.get(self.focused).expect("The focused pane is gone")
+    }
+
+    pub fn focused_mut(&mut self) -> &mut T {
+        self.state.get_mut(self.focused).expect("The focused pane is gone")
+    }
+
+    pub fn focused_pane(&self) -> Pane {
+        self.focused
+    }
+
+    pub fn len(&self) -> usize {
+        self.state.len()
+    }
+
+    pub fn get_mut(&mut self, pane: Pane) -> Option<&mut T> {
+        self.state.get_mut(pane)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.state.iter().map(|(_, content)| content)
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.state.iter_mut().map(|(_, content)| content)
+    }
+
+    pub fn find(&self, predicate: impl Fn(&T) -> bool) -> Option<Pane> {
+        self.state
+            .iter()
+            .find(|(_, content)| predicate(content))
+            .map(|(pane, _)| *pane)
+    }
+
+    // Halves the focused pane, the new half gets focused. A zoomed pane is shown among the others
+    // again.
+    pub fn split(&mut self, axis: Axis, content: T) {
+        if let Some((pane, _)) = self.state.split(axis, self.focused, content) {
+            self.focused = pane;
+        }
+    }
+
+    // The last pane stays, there would be nothing to show without it. The focus goes to a
+    // neighbour if the focused pane closes.
+    pub fn close(&mut self, pane: Pane) -> Option<T> {
+        if self.state.len() == 1 {
+            return None;
+        }
+        let (content, sibling) = self.state.close(pane)?;
+        if pane == self.focused {
+            self.focused = sibling;
+        }
+        Some(content)
+    }
+
+    // Returns whether the focus moved
+    pub fn focus(&mut self, pane: Pane) -> bool {
+        if pane == self.focused || self.state.get(pane).is_none() {
+            return false;
+        }
+        self.focused = pane;
+        // Only the zoomed pane is visible, focusing another one shows them all
+        if self.state.maximized().is_some() {
+            self.state.restore();
+        }
+        true
+    }
+
+    pub fn neighbour(&self, direction: Direction) -> Option<Pane> {
+        self.state.adjacent(self.focused, direction)
+    }
+
+    // A zoomed pane takes the whole area until zoomed out again
+    pub fn toggle_zoom(&mut self) {
+        match self.state.maximized() {
+            Some(_) => self.state.restore(),
+            None if self.state.len() > 1 => self.state.maximize(self.focused),
+            None => {}
+        }
+    }
+
+    // Where the panes are in an area of `size`, only the zoomed one if a pane is zoomed
+    pub fn regions(&self, size: Size) -> Vec<(Pane, Rectangle)> {
+        match self.state.maximized() {
+            Some(pane) => vec![(pane, Rectangle::with_size(size))],
+            None => self
+                .state
+                .layout()
+                .pane_regions(PANE_SPACING, size)
+                .into_iter()
+                .collect(),
+        }
+    }
+
+    pub fn focused_region(&self, size: Size) -> Rectangle {
+        let mut regions = self.regions(size).into_iter();
+        let focused = regions.find(|(pane, _)| *pane == self.focused);
+        focused.map_or(Rectangle::with_size(size), |(_, region)| region)
+    }
+
+    pub fn pane_at(&self, size: Size, point: Point) -> Option<(Pane, Rectangle)> {
+        self.regions(size)
+            .into_iter()
+            .find(|(_, region)| region.contains(point))
+    }
+
+    pub fn set_ratio(&mut self, split: pane_grid::Split, ratio: f32) {
+        self.state.resize(split, ratio.clamp(MIN_RATIO, 1.0 - MIN_RATIO));
+    }
+
+    // Moves the nearest border between the focused pane and its neighbours `amount` pixels in
+    // `direction`, in an area of `size`
+    pub fn resize(&mut self, direction: Direction, amount: f32, size: Size) {
+        let axis = match direction {
+            Direction::Left | Direction::Right => Axis::Vertical,
+            Direction::Up | Direction::Down => Axis::Horizontal,
+        };
+        let area = Rectangle::with_size(size);
+        let Some((split, region, ratio)) = enclosing_split(self.state.layout(), self.focused, axis, area) else {
+            return;
+        };
+        let (length, amount) = match direction {
+            Direction::Left => (region.width, -amount),
+            Direction::Right => (region.width, amount),
+            Direction::Up => (region.height, -amount),
+            Direction::Down => (region.height, amount),
+        };
+        self.set_ratio(split, ratio + amount / length);
+    }
+}
+
+// The innermost split along `axis` with `pane` on one of its sides, with its area and ratio
+fn enclosing_split(
+    node: &Node,
+    pane: Pane,
+    axis: Axis,
+    region: Rectangle,
+) -> Option<(pane_grid::Split, Rectangle, f32)> {
+    let Node::Split {
+        id,
+        axis: split_axis,
+        ratio,
+        a,
+        b,
+    } = node
+    else {
+        return None;
+    };
+    let (region_a, region_b) = split_axis.split(&region, *ratio, PANE_SPACING);
+    let inner = match contains(a, pane) {
+        true => enclosing_split(a, pane, axis, region_a),
+        false if contains(b, pane) => enclosing_split(b, pane, axis, region_b),
+        false => return None,
+    };
+    inner.or_else(|| (*split_axis == axis).then_some((*id, region, *ratio)))
+}
+
+fn contains(node: &Node, pane: Pane) -> bool {
+    match node {
+        Node::Split { a, b, .. } => contains(a, pane) || contains(b, pane),
+        Node::Pane(leaf) => *leaf == pane,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SIZE: Size = Size::new(804.0, 604.0);
+
+    fn region(panes: &Panes<&str>, pane: Pane) -> Rectangle {
+        let regions = panes.regions(SIZE);
+        regions.into_iter().find(|(found, _)| *found == pane).unwrap().1
+    }
+
+    #[test]
+    fn splitting_focuses_the_new_pane() {
+        let mut panes = Panes::new("a");
+        let a = panes.focused_pane();
+        panes.split(Axis::Vertical, "b");
+        assert_eq!(*panes.focused(), "b");
+        assert_eq!(region(&panes, a).width, 400.0);
+
+        panes.split(Axis::Horizontal, "c");
+        assert_eq!(panes.neighbour(Direction::Up), panes.find(|content| *content == "b"));
+        assert_eq!(panes.neighbour(Direction::Left), Some(a));
+
+        assert_eq!(panes.close(panes.focused_pane()), Some("c"));
+        assert_eq!(*panes.focused(), "b");
+        assert_eq!(panes.close(a), Some("a"));
+        assert_eq!(panes.close(panes.focused_pane()), None);
+    }
+
+    #[test]
+    fn resizing_moves_the_nearest_border() {
+        let mut panes = Panes::new("a");
+        panes.split(Axis::Vertical, "b");
+        panes.split(Axis::Horizontal, "c");
+        let b = panes.find(|content| *content == "b").unwrap();
+
+        panes.resize(Direction::Left, 80.0, SIZE);
+        assert_eq!(region(&panes, b).width, 480.0);
+        panes.resize(Direction::Down, 60.0, SIZE);
+        assert_eq!(region(&panes, b).height, 360.0);
+        // Borders stop short of the edges
+        panes.resize(Direction::Left, 10_000.0, SIZE);
+        assert!(region(&panes, b).width < SIZE.width);
+    }
+
+    #[test]
+    fn a_zoomed_pane_takes_the_whole_area() {
+        let mut panes = Panes::new("a");
+        let a = panes.focused_pane();
+        panes.split(Axis::Vertical, "b");
+        panes.toggle_zoom();
+        assert_eq!(
+            panes.regions(SIZE),
+            vec![(panes.focused_pane(), Rectangle::with_size(SIZE))]
+        );
+
+        assert!(panes.focus(a));
+        assert_eq!(panes.regions(SIZE).len(), 2);
+    }
+}
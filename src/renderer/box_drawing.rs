@@ -0,0 +1,518 @@
+use iced::Rectangle;
+
+/// Arms of every box drawing character in U+2500–257F as up, right, down and left, where
+/// `l` is light, `h` heavy, `d` double and `0` no line. Dashes, arcs and diagonals are empty
+/// here and drawn separately.
+#[rustfmt::skip]
+const BOX_LINES: [&str; 128] = [
+    "0l0l", "0h0h", "l0l0", "h0h0", "", "", "", "", // U+2500
+    "", "", "", "", "0ll0", "0hl0", "0lh0", "0hh0", // U+2508
+    "00ll", "00lh", "00hl", "00hh", "ll00", "lh00", "hl00", "hh00", // U+2510
+    "l00l", "l00h", "h00l", "h00h", "lll0", "lhl0", "hll0", "llh0", // U+2518
+    "hlh0", "hhl0", "lhh0", "hhh0", "l0ll", "l0lh", "h0ll", "l0hl", // U+2520
+    "h0hl", "h0lh", "l0hh", "h0hh", "0lll", "0llh", "0hll", "0hlh", // U+2528
+    "0lhl", "0lhh", "0hhl", "0hhh", "ll0l", "ll0h", "lh0l", "lh0h", // U+2530
+    "hl0l", "hl0h", "hh0l", "hh0h", "llll", "lllh", "lhll", "lhlh", // U+2538
+    "hlll", "llhl", "hlhl", "hllh", "hhll", "llhh", "lhhl", "hhlh", // U+2540
+    "lhhh", "hlhh", "hhhl", "hhhh", "", "", "", "", // U+2548
+    "0d0d", "d0d0", "0dl0", "0ld0", "0dd0", "00ld", "00dl", "00dd", // U+2550
+    "ld00", "dl00", "dd00", "l00d", "d00l", "d00d", "ldl0", "dld0", // U+2558
+    "ddd0", "l0ld", "d0dl", "d0dd", "0dld", "0ldl", "0ddd", "ld0d", // U+2560
+    "dl0l", "dd0d", "ldld", "dldl", "dddd", "", "", "", // U+2568
+    "", "", "", "", "000l", "l000", "0l00", "00l0", // U+2570
+    "000h", "h000", "0h00", "00h0", "0h0l", "l0h0", "0l0h", "h0l0", // U+2578
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Line {
+    None,
+    Light,
+    Heavy,
+    Double,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Direction {
+    Up,
+    Right,
+    Down,
+    Left,
+}
+
+// Box drawing, block elements, braille and Powerline separators are drawn from rectangles
+// instead of font glyphs, so they fill their cell exactly and join their neighbours
+pub fn is_drawn(c: char) -> bool {
+    matches!(
+        c,
+        '\u{2500}'..='\u{259f}'
+            | '\u{2800}'..='\u{28ff}'
+            | '\u{e0b0}'..='\u{e0b4}'
+            | '\u{e0b6}'
+            | '\u{e0b8}'
+            | '\u{e0ba}'
+            | '\u{e0bc}'
+            | '\u{e0be}'
+    )
+}
+
+// Shades are the only drawn characters that are not fully opaque
+pub fn opacity(c: char) -> f32 {
+    match c {
+        '\u{2591}' => 0.25,
+        '\u{2592}' => 0.5,
+        '\u{2593}' => 0.75,
+        _ => 1.0,
+    }
+}
+
+// Rectangles making up `c` inside `cell`, with `thickness` as the width of a light line.
+// The cell is expected to be snapped to whole pixels.
+pub fn rectangles(c: char, cell: Rectangle, thickness: f32) -> Vec<Rectangle> {
+    let mut canvas = Canvas::new(cell, thickness);
+    match c {
+        '\u{2504}' | '\u{2505}' | '\u{2508}' | '\u{2509}' | '\u{254c}' | '\u{254d}' => {
+            canvas.horizontal_dashes(dash_count(c), heaviness(c))
+        }
+        '\u{2506}' | '\u{2507}' | '\u{250a}' | '\u{250b}' | '\u{254e}' | '\u{254f}' => {
+            canvas.vertical_dashes(dash_count(c), heaviness(c))
+        }
+        '\u{256d}' => canvas.arc(1.0, 1.0),
+        '\u{256e}' => canvas.arc(-1.0, 1.0),
+        '\u{256f}' => canvas.arc(-1.0, -1.0),
+        '\u{2570}' => canvas.arc(1.0, -1.0),
+        '\u{2571}' => canvas.diagonal(true),
+        '\u{2572}' => canvas.diagonal(false),
+        '\u{2573}' => {
+            canvas.diagonal(true);
+            canvas.diagonal(false);
+        }
+        '\u{2500}'..='\u{257f}' => canvas.lines(BOX_LINES[c as usize - 0x2500]),
+        '\u{2580}'..='\u{259f}' => canvas.block(c),
+        '\u{2800}'..='\u{28ff}' => canvas.braille(c as u32 - 0x2800),
+        '\u{e0b0}'..='\u{e0be}' => canvas.powerline(c),
+        _ => {}
+    }
+    canvas.rectangles
+}
+
+fn dash_count(c: char) -> usize {
+    match c {
+        '\u{254c}'..='\u{254f}' => 2,
+        '\u{2504}'..='\u{2507}' => 3,
+        _ => 4,
+    }
+}
+
+fn heaviness(c: char) -> Line {
+    // Light and heavy dashes alternate
+    if c as u32 % 2 == 0 {
+        Line::Light
+    } else {
+        Line::Heavy
+    }
+}
+
+fn parse_line(c: char) -> Line {
+    match c {
+        'l' => Line::Light,
+        'h' => Line::Heavy,
+        'd' => Line::Double,
+        _ => Line::None,
+    }
+}
+
+struct Canvas {
+    cell: Rectangle,
+    thickness: f32,
+    center_x: f32,
+    center_y: f32,
+    rectangles: Vec<Rectangle>,
+}
+
+impl Canvas {
+    fn new(cell: Rectangle, thickness: f32) -> Self {
+        Self {
+            cell,
+            thickness,
+            center_x: (cell.x + cell.width / 2.0).floor(),
+            center_y: (cell.y + cell.height / 2.0).floor(),
+            rectangles: Vec::new(),
+        }
+    }
+
+    fn right(&self) -> f32 {
+        self.cell.x + self.cell.width
+    }
+
+    fn bottom(&self) -> f32 {
+        self.cell.y + self.cell.height
+    }
+
+    fn width_of(&self, line: Line) -> f32 {
+        match line {
+            Line::None => 0.0,
+            Line::Light | Line::Double => self.thickness,
+            Line::Heavy => 2.0 * self.thickness,
+        }
+    }
+
+    // Distance from the center to each of the two strokes of a double line
+    fn double_gap(&self) -> f32 {
+        self.thickness
+    }
+
+    // Fills the area between the given edges, rounded to whole pixels and clipped to the cell
+    fn fill(&mut self, left: f32, top: f32, right: f32, bottom: f32) {
+        let left = left.round().max(self.cell.x);
+        let top = top.round().max(self.cell.y);
+        let right = right.round().min(self.right());
+        let bottom = bottom.round().min(self.bottom());
+        if right > left && bottom > top {
+            self.rectangles.push(Rectangle {
+                x: left,
+                y: top,
+                width: right - left,
+                height: bottom - top,
+            });
+        }
+    }
+
+    // Fills a line of `width` pixels centered `across` pixels from the cell center, running
+    // from `start` pixels past the center (negative values reach behind it) to the cell edge
+    fn arm(&mut self, direction: Direction, start: f32, across: f32, width: f32) {
+        let width = width.round().max(1.0);
+        let (center_x, center_y) = (self.center_x, self.center_y);
+        match direction {
+            Direction::Up | Direction::Down => {
+                let left = (center_x + across - width / 2.0).round();
+                let (top, bottom) = if direction == Direction::Up {
+                    (self.cell.y, center_y - start)
+                } else {
+                    (center_y + start, self.bottom())
+                };
+                self.fill(left, top, left + width, bottom);
+            }
+            Direction::Left | Direction::Right => {
+                let top = (center_y + across - width / 2.0).round();
+                let (left, right) = if direction == Direction::Left {
+                    (self.cell.x, center_x - start)
+                } else {
+                    (center_x + start, self.right())
+                };
+                self.fill(left, top, right, top + width);
+            }
+        }
+    }
+
+    fn lines(&mut self, arms: &str) {
+        let arms: Vec<Line> = arms.chars().map(parse_line).collect();
+        let line = |direction: Direction| arms[direction as usize];
+
+        for direction in [Direction::Up, Direction::Right, Direction::Down, Direction::Left] {
+            // Arms at a right angle, the first one on the negative side of the arm's axis
+            let (before, after) = match direction {
+                Direction::Up | Direction::Down => (line(Direction::Left), line(Direction::Right)),
+                Direction::Left | Direction::Right => (line(Direction::Up), line(Direction::Down)),
+            };
+            let gap = self.double_gap();
+            let half = self.thickness / 2.0;
+
+            match line(direction) {
+                Line::None => {}
+                Line::Double => {
+                    for (across, near, far) in [(-gap, before, after), (gap, after, before)] {
+                        let start = match (near, far) {
+                            (Line::Double, _) => gap - half,
+                            (Line::Light | Line::Heavy, _) => 0.0,
+                            (Line::None, Line::Light | Line::Heavy) => -self.width_of(far) / 2.0,
+                            (Line::None, _) => -gap - half,
+                        };
+                        self.arm(direction, start, across, self.thickness);
+                    }
+                }
+                single => {
+                    let start = match (before, after) {
+                        (Line::Double, Line::Double) => gap - half,
+                        (Line::Double, _) | (_, Line::Double) => -gap - half,
+                        _ => -self.width_of(before).max(self.width_of(after)) / 2.0,
+                    };
+                    self.arm(direction, start, 0.0, self.width_of(single));
+                }
+            }
+        }
+    }
+
+    fn horizontal_dashes(&mut self, count: usize, line: Line) {
+        let width = self.width_of(line);
+        let top = (self.center_y - width / 2.0).round();
+        let segment = self.cell.width / count as f32;
+        for dash in 0..count {
+            let left = self.cell.x + dash as f32 * segment;
+            self.fill(left, top, left + segment * 0.6, top + width);
+        }
+    }
+
+    fn vertical_dashes(&mut self, count: usize, line: Line) {
+        let width = self.width_of(line);
+        let left = (self.center_x - width / 2.0).round();
+        let segment = self.cell.height / count as f32;
+        for dash in 0..count {
+            let top = self.cell.y + dash as f32 * segment;
+            self.fill(left, top, left + width, top + segment * 0.6);
+        }
+    }
+
+    fn block(&mut self, c: char) {
+        let Rectangle { x, y, width, height } = self.cell;
+        let (right, bottom) = (self.right(), self.bottom());
+        let eighth_x = |n: f32| x + width * n / 8.0;
+        let eighth_y = |n: f32| y + height * n / 8.0;
+        let (center_x, center_y) = (x + width / 2.0, y + height / 2.0);
+
+        match c {
+            '\u{2580}' => self.fill(x, y, right, center_y),
+            '\u{2581}'..='\u{2588}' => {
+                let eighths = (c as u32 - 0x2580) as f32;
+                self.fill(x, eighth_y(8.0 - eighths), right, bottom);
+            }
+            '\u{2589}'..='\u{258f}' => {
+                let eighths = (0x2590 - c as u32) as f32;
+                self.fill(x, y, eighth_x(eighths), bottom);
+            }
+            '\u{2590}' => self.fill(center_x, y, right, bottom),
+            '\u{2591}'..='\u{2593}' => self.fill(x, y, right, bottom),
+            '\u{2594}' => self.fill(x, y, right, eighth_y(1.0)),
+            '\u{2595}' => self.fill(eighth_x(7.0), y, right, bottom),
+            _ => {
+                // Quadrants as upper left, upper right, lower left and lower right
+                let quadrants: [bool; 4] = match c {
+                    '\u{2596}' => [false, false, true, false],
+                    '\u{2597}' => [false, false, false, true],
+                    '\u{2598}' => [true, false, false, false],
+                    '\u{2599}' => [true, false, true, true],
+                    '\u{259a}' => [true, false, false, true],
+                    '\u{259b}' => [true, true, true, false],
+                    '\u{259c}' => [true, true, false, true],
+                    '\u{259d}' => [false, true, false, false],
+                    '\u{259e}' => [false, true, true, false],
+                    _ => [false, true, true, true],
+                };
+                let areas = [
+                    (x, y, center_x, center_y),
+                    (center_x, y, right, center_y),
+                    (x, center_y, center_x, bottom),
+                    (center_x, center_y, right, bottom),
+                ];
+                for (filled, (left, top, right, bottom)) in quadrants.into_iter().zip(areas) {
+                    if filled {
+                        self.fill(left, top, right, bottom);
+                    }
+                }
+            }
+        }
+    }
+
+    // Braille dots are numbered down the left column, then down the right column, with the
+    // fourth row added last as dots 7 and 8
+    fn braille(&mut self, dots: u32) {
+        const POSITIONS: [(f32, f32); 8] = [
+            (0.0, 0.0),
+            (0.0, 1.0),
+            (0.0, 2.0),
+            (1.0, 0.0),
+            (1.0, 1.0),
+            (1.0, 2.0),
+            (0.0, 3.0),
+            (1.0, 3.0),
+        ];
+        let size = (self.cell.width / 4.0).round().max(1.0);
+        for (bit, (col, row)) in POSITIONS.iter().enumerate() {
+            if dots & (1 << bit) != 0 {
+                let center_x = self.cell.x + self.cell.width * (2.0 * col + 1.0) / 4.0;
+                let center_y = self.cell.y + self.cell.height * (2.0 * row + 1.0) / 8.0;
+                let left = (center_x - size / 2.0).round();
+                let top = (center_y - size / 2.0).round();
+                self.fill(left, top, left + size, top + size);
+            }
+        }
+    }
+
+    // Fills every pixel row with the span returned for the row's vertical center, given as
+    // fractions of the cell width from its left edge
+    fn fill_rows(&mut self, span: impl Fn(f32) -> (f32, f32)) {
+        let (x, width) = (self.cell.x, self.cell.width);
+        let mut top = self.cell.y;
+        while top < self.bottom() {
+            // Fraction of the cell height, measured at the center of the pixel row
+            let fraction = (top + 0.5 - self.cell.y) / self.cell.height;
+            let (from, to) = span(fraction);
+            if to > from {
+                // Snapping is done per row here, so fill without rounding away thin slivers
+                self.rectangles.push(Rectangle {
+                    x: x + width * from,
+                    y: top,
+                    width: width * (to - from),
+                    height: 1.0_f32.min(self.bottom() - top),
+                });
+            }
+            top += 1.0;
+        }
+    }
+
+    // Strokes the path through `points` with a light line, one rectangle per pixel row
+    fn stroke(&mut self, points: &[(f32, f32)]) {
+        let half = self.thickness / 2.0;
+        let rows = self.cell.height.ceil() as usize;
+        let mut spans: Vec<Option<(f32, f32)>> = vec![None; rows];
+
+        for pair in points.windows(2) {
+            let ((x0, y0), (x1, y1)) = (pair[0], pair[1]);
+            let steps = ((x1 - x0).abs().max((y1 - y0).abs()) * 4.0).ceil().max(1.0) as usize;
+            for step in 0..=steps {
+                let t = step as f32 / steps as f32;
+                let (x, y) = (x0 + (x1 - x0) * t, y0 + (y1 - y0) * t);
+                let first = ((y - half - self.cell.y).floor().max(0.0)) as usize;
+                let last = ((y + half - self.cell.y).ceil().max(0.0) as usize).min(rows);
+                for span in spans[first.min(rows)..last].iter_mut() {
+                    *span = Some(match *span {
+                        Some((left, right)) => (left.min(x - half), right.max(x + half)),
+                        None => (x - half, x + half),
+                    });
+                }
+            }
+        }
+
+        for (row, span) in spans.into_iter().enumerate() {
+            if let Some((left, right)) = span {
+                let top = self.cell.y + row as f32;
+                self.fill(left, top, right, top + 1.0);
+            }
+        }
+    }
+
+    // Quarter circle joining the center of the vertical and horizontal edges on the
+    // `horizontal` (1 right, -1 left) and `vertical` (1 down, -1 up) sides
+    fn arc(&mut self, horizontal: f32, vertical: f32) {
+        let radius = (self.cell.width / 2.0).min(self.cell.height / 2.0);
+        let (center_x, center_y) = (self.center_x, self.center_y);
+        let (origin_x, origin_y) = (center_x + horizontal * radius, center_y + vertical * radius);
+
+        let mut points: Vec<(f32, f32)> = (0..=16)
+            .map(|step| {
+                let angle = step as f32 / 16.0 * std::f32::consts::FRAC_PI_2;
+                (
+                    origin_x - horizontal * radius * angle.cos(),
+                    origin_y - vertical * radius * angle.sin(),
+                )
+            })
+            .collect();
+        // Continue straight on to the vertical edge, and the horizontal edge when the cell
+        // is wider than it is tall
+        let vertical_edge = if vertical > 0.0 { self.bottom() } else { self.cell.y };
+        let horizontal_edge = if horizontal > 0.0 { self.right() } else { self.cell.x };
+        points.insert(0, (center_x, vertical_edge));
+        points.push((horizontal_edge, center_y));
+        self.stroke(&points);
+    }
+
+    fn diagonal(&mut self, rising: bool) {
+        let (left, top, right, bottom) = (self.cell.x, self.cell.y, self.right(), self.bottom());
+        if rising {
+            self.stroke(&[(left, bottom), (right, top)]);
+        } else {
+            self.stroke(&[(left, top), (right, bottom)]);
+        }
+    }
+
+    fn powerline(&mut self, c: char) {
+        let (left, top, right, bottom) = (self.cell.x, self.cell.y, self.right(), self.bottom());
+        let center_y = top + self.cell.height / 2.0;
+        // Distance from the vertical center as a fraction of half the cell height
+        let from_center = |fraction: f32| ((fraction - 0.5) * 2.0).abs();
+        match c {
+            '\u{e0b0}' => self.fill_rows(|y| (0.0, 1.0 - from_center(y))),
+            '\u{e0b1}' => self.stroke(&[(left, top), (right, center_y), (left, bottom)]),
+            '\u{e0b2}' => self.fill_rows(|y| (from_center(y), 1.0)),
+            '\u{e0b3}' => self.stroke(&[(right, top), (left, center_y), (right, bottom)]),
+            '\u{e0b4}' => self.fill_rows(|y| (0.0, (1.0 - from_center(y).powi(2)).sqrt())),
+            '\u{e0b6}' => self.fill_rows(|y| (1.0 - (1.0 - from_center(y).powi(2)).sqrt(), 1.0)),
+            '\u{e0b8}' => self.fill_rows(|y| (0.0, y)),
+            '\u{e0ba}' => self.fill_rows(|y| (1.0 - y, 1.0)),
+            '\u{e0bc}' => self.fill_rows(|y| (0.0, 1.0 - y)),
+            '\u{e0be}' => self.fill_rows(|y| (y, 1.0)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CELL: Rectangle = Rectangle {
+        x: 10.0,
+        y: 20.0,
+        width: 8.0,
+        height: 16.0,
+    };
+
+    fn covers(rectangles: &[Rectangle], x: f32, y: f32) -> bool {
+        rectangles
+            .iter()
+            .any(|rectangle| rectangle.contains(iced::Point::new(x, y)))
+    }
+
+    #[test]
+    fn only_known_ranges_are_drawn() {
+        assert!(is_drawn('─'));
+        assert!(is_drawn('█'));
+        assert!(is_drawn('⣿'));
+        assert!(is_drawn('\u{e0b0}'));
+        assert!(!is_drawn('a'));
+        assert!(!is_drawn('\u{e0b5}'));
+    }
+
+    #[test]
+    fn horizontal_line_spans_the_whole_cell_width() {
+        let rectangles = rectangles('─', CELL, 1.0);
+        assert!(covers(&rectangles, 10.0, 28.5));
+        assert!(covers(&rectangles, 17.9, 28.5));
+        assert!(!covers(&rectangles, 14.0, 20.5));
+    }
+
+    #[test]
+    fn corner_reaches_right_and_bottom_edges_only() {
+        let rectangles = rectangles('┌', CELL, 1.0);
+        assert!(covers(&rectangles, 17.9, 28.5));
+        assert!(covers(&rectangles, 14.5, 35.9));
+        assert!(!covers(&rectangles, 10.0, 28.5));
+        assert!(!covers(&rectangles, 14.5, 20.0));
+    }
+
+    #[test]
+    fn double_lines_leave_a_gap_between_strokes() {
+        let rectangles = rectangles('═', CELL, 1.0);
+        assert!(covers(&rectangles, 12.0, 27.5));
+        assert!(!covers(&rectangles, 12.0, 28.5));
+        assert!(covers(&rectangles, 12.0, 29.5));
+    }
+
+    #[test]
+    fn full_block_fills_the_cell_exactly() {
+        assert_eq!(rectangles('█', CELL, 1.0), vec![CELL]);
+    }
+
+    #[test]
+    fn braille_draws_one_dot_per_bit() {
+        assert_eq!(rectangles('⣿', CELL, 1.0).len(), 8);
+        assert_eq!(rectangles('⠁', CELL, 1.0).len(), 1);
+    }
+
+    #[test]
+    fn powerline_triangle_points_right() {
+        let rectangles = rectangles('\u{e0b0}', CELL, 1.0);
+        assert!(covers(&rectangles, 17.0, 28.0));
+        assert!(!covers(&rectangles, 17.5, 20.5));
+        assert!(covers(&rectangles, 10.2, 20.5));
+    }
+}
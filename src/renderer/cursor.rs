@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use iced::{
+    futures::{SinkExt, Stream},
+    Color,
+};
+
+// Time the cursor stays on or off while blinking
+const BLINK_INTERVAL: Duration = Duration::from_millis(530);
+
+use crate::structs::cursor::CursorStyle;
+
+/// How the cursor is drawn, besides its position which comes from the buffer
+#[derive(Debug, Clone, Copy)]
+pub struct CursorAppearance {
+    pub visible: bool,
+    pub style: CursorStyle,
+    // Unfocused windows get a hollow block, whatever the shape
+    pub focused: bool,
+    pub color: Color,
+}
+
+/// Blink phase of the cursor. The phase restarts on input and output, so the cursor never
+/// disappears while something is happening under it.
+#[derive(Debug, Clone, Copy)]
+pub struct Blink {
+    on: bool,
+}
+
+impl Blink {
+    pub fn new() -> Self {
+        Self { on: true }
+    }
+
+    pub fn toggle(&mut self) {
+        self.on = !self.on;
+    }
+
+    pub fn reset(&mut self) {
+        self.on = true;
+    }
+
+    pub fn is_on(&self) -> bool {
+        self.on
+    }
+}
+
+// Ticks every time a blinking cursor should toggle
+pub fn blink_ticks() -> impl Stream<Item = ()> {
+    iced::stream::channel(1, |mut output| async move {
+        loop {
+            async_std::task::sleep(BLINK_INTERVAL).await;
+            if output.send(()).await.is_err() {
+                break;
+            }
+        }
+    })
+}
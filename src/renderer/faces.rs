@@ -0,0 +1,36 @@
+use iced::advanced::graphics::text::{
+    cosmic_text::fontdb::{Database, FaceInfo, Family, Query, Stretch, Style, Weight},
+    font_system,
+};
+
+/// Styled faces of the terminal font that are actually installed. Text asking for a missing
+/// face is synthesized when drawing, instead of silently using the regular face.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Faces {
+    pub bold: bool,
+    pub italic: bool,
+}
+
+impl Faces {
+    pub fn detect(family: &str) -> Self {
+        let mut font_system = font_system().write().expect("Could not lock font system");
+        let database = font_system.raw().db();
+
+        Self {
+            bold: query(database, family, Weight::BOLD, Style::Normal).is_some_and(|face| face.weight.0 >= 600),
+            italic: query(database, family, Weight::NORMAL, Style::Italic)
+                .is_some_and(|face| face.style != Style::Normal),
+        }
+    }
+}
+
+// Closest installed face, which fontdb happily returns even when it is not the asked for style
+fn query<'a>(database: &'a Database, family: &str, weight: Weight, style: Style) -> Option<&'a FaceInfo> {
+    let id = database.query(&Query {
+        families: &[Family::Name(family)],
+        weight,
+        stretch: Stretch::Normal,
+        style,
+    })?;
+    database.face(id)
+}
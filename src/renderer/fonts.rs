@@ -0,0 +1,81 @@
+use std::collections::HashMap;
+
+use iced::advanced::graphics::text::{
+    cosmic_text::fontdb::{Family, Query, Stretch, Style, Weight, ID},
+    font_system,
+};
+
+/// Font families to draw glyphs with, in order of preference. Every character is drawn with the
+/// first family that has a glyph for it, so symbols and emoji missing from the primary font come
+/// from the families after it. Characters no family covers are left to cosmic-text's own
+/// fallback.
+#[derive(Debug)]
+pub struct FontChain {
+    families: Vec<&'static str>,
+    faces: Vec<Option<ID>>,
+    coverage: HashMap<char, Option<usize>>,
+}
+
+impl FontChain {
+    pub fn new(families: Vec<&'static str>) -> Self {
+        assert!(!families.is_empty(), "Font chain needs at least a primary font");
+
+        let mut font_system = font_system().write().expect("Could not lock font system");
+        let database = font_system.raw().db();
+        let faces = families
+            .iter()
+            .map(|family| {
+                database.query(&Query {
+                    families: &[Family::Name(family)],
+                    weight: Weight::NORMAL,
+                    stretch: Stretch::Normal,
+                    style: Style::Normal,
+                })
+            })
+            .collect();
+
+        Self {
+            families,
+            faces,
+            coverage: HashMap::new(),
+        }
+    }
+
+    // A chain that knows which family covers which character without asking the installed fonts,
+    // characters not listed are covered by none
+    #[cfg(test)]
+    pub fn with_coverage(
+        families: Vec<&'static str>,
+        coverage: impl IntoIterator<Item = (char, Option<usize>)>,
+    ) -> Self {
+        Self {
+            faces: vec![None; families.len()],
+            families,
+            coverage: coverage.into_iter().collect(),
+        }
+    }
+
+    pub fn family(&self, index: usize) -> &'static str {
+        self.families[index]
+    }
+
+    // Index of the first family with a glyph for `c`, or `None` when no family has one
+    pub fn resolve(&mut self, c: char) -> Option<usize> {
+        // Any font fit for a terminal covers ASCII, so skip the lookup for the common case
+        if c.is_ascii() {
+            return Some(0);
+        }
+
+        if let Some(family) = self.coverage.get(&c) {
+            return *family;
+        }
+
+        let mut font_system = font_system().write().expect("Could not lock font system");
+        let family = self.faces.iter().position(|face| {
+            face.and_then(|id| font_system.raw().get_font(id))
+                .is_some_and(|font| font.as_swash().charmap().map(c) != 0)
+        });
+        self.coverage.insert(c, family);
+        family
+    }
+}
@@ -0,0 +1,514 @@
+use iced::{
+    advanced::{
+        layout, mouse,
+        renderer::{self, Quad},
+        text::{self, Text},
+        widget::Tree,
+        Layout, Widget,
+    },
+    alignment,
+    font::{Style, Weight},
+    widget::text::{LineHeight, Shaping, Wrapping},
+    Color, Element, Length, Point, Rectangle, Size,
+};
+
+use crate::{
+    config::Config,
+    structs::{
+        buffer::Buffer,
+        cell::{char_width, Cell, Underline},
+        cursor::{Cursor, CursorStyle},
+        selection::TextSelection,
+    },
+};
+
+use super::{
+    box_drawing,
+    cursor::CursorAppearance,
+    rows::{DecorationRun, DrawnGlyph, RowCache, TextRun},
+};
+
+// Synthesized italic shears glyphs by the same angle as cosmic-text does, 14 degrees
+const ITALIC_SHEAR: f32 = 0.249;
+
+// Height in pixels of each horizontal slice used to shear synthesized italic text
+const ITALIC_SLICE_HEIGHT: f32 = 2.0;
+
+// Font and shaping strategy to draw a piece of text with
+#[derive(Clone, Copy)]
+struct Face {
+    font: iced::Font,
+    shaping: Shaping,
+}
+
+/// Paints the whole terminal grid as one widget: a quad per background run, the selection, a
+/// text primitive per text run and quads for drawn glyphs and text decorations, with the
+/// cursor drawn on top. While searching, a bar with the query is drawn over a row.
+pub struct Grid<'a> {
+    buffer: &'a Buffer<Cell>,
+    rows: &'a RowCache,
+    config: &'a Config,
+    cursor: CursorAppearance,
+    selection: Option<TextSelection>,
+    search: Option<&'a str>,
+}
+
+impl<'a> Grid<'a> {
+    pub fn new(
+        buffer: &'a Buffer<Cell>,
+        rows: &'a RowCache,
+        config: &'a Config,
+        cursor: CursorAppearance,
+        selection: Option<TextSelection>,
+    ) -> Self {
+        Self {
+            buffer,
+            rows,
+            config,
+            cursor,
+            selection,
+            search: None,
+        }
+    }
+
+    // Shows the bar of a search for `query`
+    pub fn search(self, query: Option<&'a str>) -> Self {
+        Self { search: query, ..self }
+    }
+
+    // Row of the view the cursor is on, it moves down while the view is scrolled back
+    fn cursor_row(&self) -> usize {
+        self.buffer.cursor.row + self.buffer.display_offset()
+    }
+
+    // Edges are rounded to whole device pixels, so neighbouring cells share an edge and drawn
+    // glyphs line up without seams
+    fn cell_bounds(&self, origin: Point, row: usize, col: usize, cols: usize) -> Rectangle {
+        let cell_size = self.config.cell_size;
+        let scale_factor = self.config.scale_factor;
+        let snap = |position: f32| (position * scale_factor).round() / scale_factor;
+        let left = snap(origin.x + col as f32 * cell_size.width);
+        let top = snap(origin.y + row as f32 * cell_size.height);
+        let right = snap(origin.x + (col + cols) as f32 * cell_size.width);
+        let bottom = snap(origin.y + (row + 1) as f32 * cell_size.height);
+        Rectangle {
+            x: left,
+            y: top,
+            width: right - left,
+            height: bottom - top,
+        }
+    }
+
+    // Approximate baseline inside a cell, cosmic-text centers the glyphs vertically in the line
+    fn baseline(&self, cell_top: f32) -> f32 {
+        let font_size = self.config.font_size;
+        cell_top + (self.config.cell_size.height - font_size) / 2.0 + font_size * 0.8
+    }
+
+    fn line_thickness(&self) -> f32 {
+        (self.config.font_size / 14.0).round().max(1.0)
+    }
+
+    fn primary_font(&self) -> iced::Font {
+        iced::Font::with_name(self.rows.fonts().family(0))
+    }
+
+    fn fill_run<Renderer>(&self, renderer: &mut Renderer, run: &TextRun, bounds: Rectangle, clip: Rectangle)
+    where
+        Renderer: text::Renderer<Font = iced::Font>,
+    {
+        // Characters no family in the chain covers are shaped with the primary font, letting
+        // cosmic-text look through every installed font for them
+        let mut face = match run.family {
+            Some(family) => Face {
+                font: iced::Font::with_name(self.rows.fonts().family(family)),
+                shaping: if run.ligatures {
+                    Shaping::Advanced
+                } else {
+                    Shaping::Basic
+                },
+            },
+            None => Face {
+                font: self.primary_font(),
+                shaping: Shaping::Advanced,
+            },
+        };
+        if run.bold {
+            face.font.weight = Weight::Bold;
+        }
+        if run.italic {
+            face.font.style = Style::Italic;
+        }
+
+        let synthesize_italic = run.italic && !self.config.faces.italic;
+        let synthesize_bold = run.bold && !self.config.faces.bold;
+
+        // Missing bold faces are faked by drawing the text a second time slightly to the right
+        let offsets: &[f32] = if synthesize_bold { &[0.0, 1.0] } else { &[0.0] };
+        for offset in offsets {
+            let bounds = Rectangle {
+                x: bounds.x + offset,
+                ..bounds
+            };
+            if synthesize_italic {
+                self.fill_sheared_text(renderer, &run.content, face, bounds, run.color, clip);
+            } else {
+                self.fill_text(renderer, &run.content, face, bounds, run.color, clip);
+            }
+        }
+    }
+
+    // Fakes italic by drawing the text in thin horizontal slices, each shifted right
+    // proportionally to its height above the baseline
+    fn fill_sheared_text<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        content: &str,
+        face: Face,
+        bounds: Rectangle,
+        color: Color,
+        clip: Rectangle,
+    ) where
+        Renderer: text::Renderer<Font = iced::Font>,
+    {
+        let baseline = self.baseline(bounds.y);
+        let slices = (bounds.height / ITALIC_SLICE_HEIGHT).ceil() as usize;
+        for slice in 0..slices {
+            let top = bounds.y + slice as f32 * ITALIC_SLICE_HEIGHT;
+            let shift = (baseline - (top + ITALIC_SLICE_HEIGHT / 2.0)) * ITALIC_SHEAR;
+            let slice_clip = Rectangle {
+                x: bounds.x + shift.min(0.0),
+                y: top,
+                width: bounds.width + self.config.cell_size.width + shift.abs(),
+                height: ITALIC_SLICE_HEIGHT,
+            };
+            if let Some(slice_clip) = slice_clip.intersection(&clip) {
+                let bounds = Rectangle {
+                    x: bounds.x + shift,
+                    ..bounds
+                };
+                self.fill_text(renderer, content, face, bounds, color, slice_clip);
+            }
+        }
+    }
+
+    fn fill_text<Renderer>(
+        &self,
+        renderer: &mut Renderer,
+        content: &str,
+        face: Face,
+        bounds: Rectangle,
+        color: Color,
+        clip: Rectangle,
+    ) where
+        Renderer: text::Renderer<Font = iced::Font>,
+    {
+        let text = Text {
+            content: content.to_string(),
+            // Leave room for one more cell so rounding never clips the last glyph
+            bounds: Size::new(bounds.width + self.config.cell_size.width, bounds.height),
+            size: self.config.font_size.into(),
+            line_height: LineHeight::default(),
+            font: face.font,
+            horizontal_alignment: alignment::Horizontal::Left,
+            vertical_alignment: alignment::Vertical::Top,
+            shaping: face.shaping,
+            wrapping: Wrapping::None,
+        };
+        renderer.fill_text(text, bounds.position(), color, clip);
+    }
+
+    fn draw_decorations<Renderer>(&self, renderer: &mut Renderer, run: &DecorationRun, bounds: Rectangle)
+    where
+        Renderer: renderer::Renderer,
+    {
+        let thickness = self.line_thickness();
+        let baseline = self.baseline(bounds.y);
+        let bottom = bounds.y + bounds.height - thickness;
+
+        if run.overline {
+            fill_rectangle(renderer, horizontal_line(bounds, bounds.y, thickness), run.color);
+        }
+
+        if run.strikethrough {
+            let y = baseline - self.config.font_size * 0.3;
+            fill_rectangle(renderer, horizontal_line(bounds, y, thickness), run.color);
+        }
+
+        let y = (baseline + thickness).min(bottom);
+        let color = run.underline_color;
+        match run.underline {
+            Underline::None => {}
+            Underline::Single => fill_rectangle(renderer, horizontal_line(bounds, y, thickness), color),
+            Underline::Double => {
+                let y = y.min(bottom - 2.0 * thickness);
+                fill_rectangle(renderer, horizontal_line(bounds, y, thickness), color);
+                fill_rectangle(renderer, horizontal_line(bounds, y + 2.0 * thickness, thickness), color);
+            }
+            Underline::Dotted => fill_dashes(renderer, bounds, y, thickness, thickness, color),
+            Underline::Dashed => fill_dashes(renderer, bounds, y, thickness, 3.0 * thickness, color),
+            Underline::Curly => {
+                // One wave per cell, drawn as a column of quads per pixel
+                let amplitude = thickness.max(1.5);
+                let y = y.min(bottom - amplitude);
+                let period = self.config.cell_size.width;
+                let mut x = 0.0;
+                while x < bounds.width {
+                    let offset = amplitude * (x / period * std::f32::consts::TAU).sin();
+                    let dot = Rectangle {
+                        x: bounds.x + x,
+                        y: y + offset,
+                        width: 1.0,
+                        height: thickness,
+                    };
+                    fill_rectangle(renderer, dot, color);
+                    x += 1.0;
+                }
+            }
+        }
+    }
+
+    fn draw_glyph<Renderer>(&self, renderer: &mut Renderer, glyph: &DrawnGlyph, bounds: Rectangle)
+    where
+        Renderer: renderer::Renderer,
+    {
+        let color = Color {
+            a: glyph.color.a * box_drawing::opacity(glyph.content),
+            ..glyph.color
+        };
+        // Glyphs are laid out in device pixels, so their edges stay sharp at any scale factor
+        let scale_factor = self.config.scale_factor;
+        let thickness = (self.line_thickness() * scale_factor).round();
+        for rectangle in box_drawing::rectangles(glyph.content, bounds * scale_factor, thickness) {
+            fill_rectangle(renderer, rectangle * scale_factor.recip(), color);
+        }
+    }
+
+    // The bar covers the last row, or the first one while the match shown is on the last row
+    fn draw_search_bar<Renderer>(&self, renderer: &mut Renderer, query: &str, origin: Point)
+    where
+        Renderer: text::Renderer<Font = iced::Font>,
+    {
+        let last = self.buffer.rows - 1;
+        let row = match self
+            .selection
+            .and_then(|selection| selection.columns(last, self.buffer.cols))
+        {
+            Some(_) => 0,
+            None => last,
+        };
+        let bounds = self.cell_bounds(origin, row, 0, self.buffer.cols);
+        let palette = &self.config.palette;
+        fill_rectangle(renderer, bounds, palette.foreground);
+        let face = Face {
+            font: self.primary_font(),
+            shaping: Shaping::Advanced,
+        };
+        self.fill_text(
+            renderer,
+            &format!("Search: {}", query),
+            face,
+            bounds,
+            palette.background,
+            bounds,
+        );
+    }
+
+    fn draw_cursor<Renderer>(&self, renderer: &mut Renderer, origin: Point, clip: Rectangle)
+    where
+        Renderer: text::Renderer<Font = iced::Font>,
+    {
+        let Cursor { col, .. } = self.buffer.cursor;
+        let style = self.cursor.style;
+        let row = self.cursor_row();
+        if !self.cursor.visible || row >= self.buffer.rows || col >= self.buffer.cols {
+            return;
+        }
+
+        // The cursor covers both cells of a wide character, and nothing is drawn on a spacer
+        let cell = self.buffer[self.buffer.cursor];
+        let content = if cell.is_spacer() { ' ' } else { cell.content };
+        let bounds = self.cell_bounds(origin, row, col, char_width(content));
+        let color = self.cursor.color;
+        if !self.cursor.focused {
+            let thickness = self.line_thickness();
+            let edges = [
+                Rectangle {
+                    height: thickness,
+                    ..bounds
+                },
+                Rectangle {
+                    y: bounds.y + bounds.height - thickness,
+                    height: thickness,
+                    ..bounds
+                },
+                Rectangle {
+                    width: thickness,
+                    ..bounds
+                },
+                Rectangle {
+                    x: bounds.x + bounds.width - thickness,
+                    width: thickness,
+                    ..bounds
+                },
+            ];
+            for edge in edges {
+                fill_rectangle(renderer, edge, color);
+            }
+            return;
+        }
+
+        use CursorStyle::*;
+        match style {
+            BlinkingBlock | SteadyBlock => {
+                fill_rectangle(renderer, bounds, color);
+                if box_drawing::is_drawn(content) {
+                    let glyph = DrawnGlyph {
+                        col,
+                        content,
+                        color: self.config.palette.background,
+                    };
+                    self.draw_glyph(renderer, &glyph, bounds);
+                    return;
+                }
+                let content = content.to_string();
+                self.fill_text(
+                    renderer,
+                    &content,
+                    Face {
+                        font: self.primary_font(),
+                        shaping: Shaping::Advanced,
+                    },
+                    bounds,
+                    self.config.palette.background,
+                    clip,
+                );
+            }
+            BlinkingUnderline | SteadyUnderline => {
+                let height = (bounds.height * 0.1).max(1.0);
+                let line = Rectangle {
+                    y: bounds.y + bounds.height - height,
+                    height,
+                    ..bounds
+                };
+                fill_rectangle(renderer, line, color);
+            }
+            BlinkingBar | SteadyBar => {
+                let bar = Rectangle {
+                    width: (bounds.width * 0.1).max(1.0),
+                    ..bounds
+                };
+                fill_rectangle(renderer, bar, color);
+            }
+        }
+    }
+}
+
+impl<'a, Message, Theme, Renderer> Widget<Message, Theme, Renderer> for Grid<'a>
+where
+    Renderer: text::Renderer<Font = iced::Font>,
+{
+    fn size(&self) -> Size<Length> {
+        Size::new(Length::Fill, Length::Fill)
+    }
+
+    fn layout(&self, _tree: &mut Tree, _renderer: &Renderer, limits: &layout::Limits) -> layout::Node {
+        layout::Node::new(limits.max())
+    }
+
+    fn draw(
+        &self,
+        _tree: &Tree,
+        renderer: &mut Renderer,
+        _theme: &Theme,
+        _style: &renderer::Style,
+        layout: Layout<'_>,
+        _cursor: mouse::Cursor,
+        _viewport: &Rectangle,
+    ) {
+        let bounds = layout.bounds();
+        let origin = bounds.position();
+
+        for (y, row) in self.rows.rows().iter().enumerate() {
+            for run in &row.backgrounds {
+                fill_rectangle(renderer, self.cell_bounds(origin, y, run.col, run.len), run.color);
+            }
+            if let Some(columns) = self
+                .selection
+                .and_then(|selection| selection.columns(y, self.buffer.cols))
+            {
+                let selected = self.cell_bounds(origin, y, columns.start, columns.len());
+                fill_rectangle(renderer, selected, self.config.palette.selection);
+            }
+            for run in &row.texts {
+                // A ligature through the cursor would be drawn over by the single character
+                // under it, so the cursor cell is shaped on its own
+                let pieces = if self.cursor_row() == y {
+                    run.isolate(self.buffer.cursor.col)
+                } else {
+                    vec![run.clone()]
+                };
+                for piece in &pieces {
+                    let piece_bounds = self.cell_bounds(origin, y, piece.col, piece.cols());
+                    self.fill_run(renderer, piece, piece_bounds, bounds);
+                }
+            }
+            for glyph in &row.drawn {
+                self.draw_glyph(renderer, glyph, self.cell_bounds(origin, y, glyph.col, 1));
+            }
+            for run in &row.decorations {
+                self.draw_decorations(renderer, run, self.cell_bounds(origin, y, run.col, run.len));
+            }
+        }
+
+        self.draw_cursor(renderer, origin, bounds);
+        if let Some(query) = self.search {
+            self.draw_search_bar(renderer, query, origin);
+        }
+    }
+}
+
+impl<'a, Message, Theme, Renderer> From<Grid<'a>> for Element<'a, Message, Theme, Renderer>
+where
+    Renderer: text::Renderer<Font = iced::Font> + 'a,
+{
+    fn from(grid: Grid<'a>) -> Self {
+        Element::new(grid)
+    }
+}
+
+fn horizontal_line(bounds: Rectangle, y: f32, thickness: f32) -> Rectangle {
+    Rectangle {
+        y,
+        height: thickness,
+        ..bounds
+    }
+}
+
+// Dashes of `length` pixels with equally long gaps
+fn fill_dashes<Renderer>(renderer: &mut Renderer, bounds: Rectangle, y: f32, thickness: f32, length: f32, color: Color)
+where
+    Renderer: renderer::Renderer,
+{
+    let mut x = 0.0;
+    while x < bounds.width {
+        let dash = Rectangle {
+            x: bounds.x + x,
+            y,
+            width: length.min(bounds.width - x),
+            height: thickness,
+        };
+        fill_rectangle(renderer, dash, color);
+        x += 2.0 * length;
+    }
+}
+
+fn fill_rectangle<Renderer: renderer::Renderer>(renderer: &mut Renderer, bounds: Rectangle, color: Color) {
+    renderer.fill_quad(
+        Quad {
+            bounds,
+            ..Quad::default()
+        },
+        color,
+    );
+}
@@ -0,0 +1,8 @@
+pub mod box_drawing;
+pub mod cursor;
+pub mod faces;
+pub mod fonts;
+pub mod grid;
+pub mod rows;
+pub mod shaper;
+pub mod tab_bar;
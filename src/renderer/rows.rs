@@ -0,0 +1,484 @@
+use std::ops::Range;
+
+use iced::Color;
+
+use super::{box_drawing, fonts::FontChain, shaper::Shaper};
+use crate::{
+    palette::Palette,
+    structs::{
+        buffer::Buffer,
+        cell::{char_width, Cell, CellStyle, FontWeight, Underline},
+        damage::Damage,
+    },
+    term::colors::TerminalColor,
+};
+
+// Dim text is drawn with reduced opacity
+const DIM_ALPHA: f32 = 0.5;
+
+/// Background and text runs for every visible row. Building runs means resolving colors,
+/// fonts and collecting characters for every cell, so rows are only rebuilt when the buffer
+/// reports them as damaged, and scrolls just move the cached rows around.
+#[derive(Debug)]
+pub struct RowCache {
+    rows: Vec<RowRuns>,
+    fonts: FontChain,
+    // Only set when ligatures are enabled
+    shaper: Option<Shaper>,
+    palette: Palette,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RowRuns {
+    pub backgrounds: Vec<BackgroundRun>,
+    pub texts: Vec<TextRun>,
+    pub drawn: Vec<DrawnGlyph>,
+    pub decorations: Vec<DecorationRun>,
+}
+
+/// `len` cells starting at `col` sharing a background color
+#[derive(Debug, Clone, PartialEq)]
+pub struct BackgroundRun {
+    pub col: usize,
+    pub len: usize,
+    pub color: Color,
+}
+
+/// Consecutive characters starting at `col` sharing a text color and face, shaped together.
+/// `family` indexes the `FontChain`, and is `None` for characters no family has a glyph for.
+/// Only narrow characters from the primary family share a run, any other glyph is a run alone.
+/// Runs with `ligatures` were checked to stay on the grid when shaped with ligatures.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TextRun {
+    pub col: usize,
+    pub content: String,
+    pub color: Color,
+    pub bold: bool,
+    pub italic: bool,
+    pub family: Option<usize>,
+    pub ligatures: bool,
+}
+
+/// A box drawing, block, braille or Powerline character at `col`, drawn from rectangles
+/// instead of a font glyph
+#[derive(Debug, Clone, PartialEq)]
+pub struct DrawnGlyph {
+    pub col: usize,
+    pub content: char,
+    pub color: Color,
+}
+
+/// Lines drawn through `len` cells starting at `col`. Blanks are included, since an
+/// underlined space is still underlined.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DecorationRun {
+    pub col: usize,
+    pub len: usize,
+    pub color: Color,
+    pub underline: Underline,
+    pub underline_color: Color,
+    pub overline: bool,
+    pub strikethrough: bool,
+}
+
+impl RowCache {
+    pub fn new(fonts: FontChain, shaper: Option<Shaper>, palette: Palette) -> Self {
+        Self {
+            rows: Vec::new(),
+            fonts,
+            shaper,
+            palette,
+        }
+    }
+
+    pub fn update(&mut self, buffer: &Buffer<Cell>, damage: Damage) {
+        // Rows scrolled back into the history move with every line of output
+        if damage.is_full() || self.rows.len() != buffer.rows || buffer.display_offset() > 0 {
+            self.rows = buffer
+                .iter_rows()
+                .map(|row| RowRuns::new(row, &mut self.fonts, self.shaper.as_ref(), &self.palette))
+                .collect();
+            return;
+        }
+
+        for scroll in damage.scrolls() {
+            let region = &mut self.rows[scroll.top..=scroll.bottom];
+            let lines = scroll.lines.unsigned_abs().min(region.len());
+            if scroll.lines > 0 {
+                region.rotate_left(lines);
+            } else {
+                region.rotate_right(lines);
+            }
+        }
+
+        for (row, _) in damage.dirty_rows() {
+            self.rows[row] = RowRuns::new(buffer.row(row), &mut self.fonts, self.shaper.as_ref(), &self.palette);
+        }
+    }
+
+    // Swaps the shaper after the font changed, every row is rebuilt on the next update
+    pub fn set_shaper(&mut self, shaper: Option<Shaper>) {
+        self.shaper = shaper;
+        self.rows.clear();
+    }
+
+    pub fn rows(&self) -> &[RowRuns] {
+        &self.rows
+    }
+
+    pub fn fonts(&self) -> &FontChain {
+        &self.fonts
+    }
+}
+
+impl RowRuns {
+    pub fn new(cells: &[Cell], fonts: &mut FontChain, shaper: Option<&Shaper>, palette: &Palette) -> Self {
+        let mut backgrounds: Vec<BackgroundRun> = Vec::new();
+        let mut texts: Vec<TextRun> = Vec::new();
+        let mut text_open = false;
+        let mut drawn: Vec<DrawnGlyph> = Vec::new();
+        let mut decorations: Vec<DecorationRun> = Vec::new();
+
+        for (col, cell) in cells.iter().enumerate() {
+            let background = background_color(&cell.style, palette);
+            match backgrounds.last_mut() {
+                Some(run) if run.col + run.len == col && run.color == background => run.len += 1,
+                _ if background.a == 0.0 => {}
+                _ => backgrounds.push(BackgroundRun {
+                    col,
+                    len: 1,
+                    color: background,
+                }),
+            }
+
+            let foreground = text_color(&cell.style, palette);
+            let bold = cell.style.weight == FontWeight::Bold;
+            let italic = cell.style.italic;
+            let family = fonts.resolve(cell.content);
+            // Glyphs from other fonts have their own advances, and wide glyphs span two cells,
+            // so both are drawn on their own to stay on the grid
+            let alone = family != Some(0) || char_width(cell.content) > 1;
+            match texts.last_mut() {
+                // The wide character before is drawn over this cell
+                _ if cell.is_spacer() => text_open = false,
+                _ if box_drawing::is_drawn(cell.content) => {
+                    drawn.push(DrawnGlyph {
+                        col,
+                        content: cell.content,
+                        color: foreground,
+                    });
+                    text_open = false;
+                }
+                Some(run)
+                    if text_open
+                        && !alone
+                        && run.color == foreground
+                        && run.bold == bold
+                        && run.italic == italic
+                        && run.family == family =>
+                {
+                    run.content.push(cell.content)
+                }
+                // Runs never start with blanks, there is nothing to draw
+                _ if cell.content == ' ' => text_open = false,
+                _ => {
+                    texts.push(TextRun {
+                        col,
+                        content: cell.content.to_string(),
+                        color: foreground,
+                        bold,
+                        italic,
+                        family,
+                        ligatures: false,
+                    });
+                    text_open = !alone;
+                }
+            }
+
+            let style = &cell.style;
+            if style.underline == Underline::None && !style.overline && !style.strikethrough {
+                continue;
+            }
+            let decoration = DecorationRun {
+                col,
+                len: 1,
+                color: foreground,
+                underline: style.underline,
+                underline_color: style
+                    .underline_color
+                    .map_or(foreground, |color| palette.foreground_color(color)),
+                overline: style.overline,
+                strikethrough: style.strikethrough,
+            };
+            match decorations.last_mut() {
+                Some(run) if run.col + run.len == col && run.same_lines(&decoration) => run.len += 1,
+                _ => decorations.push(decoration),
+            }
+        }
+
+        for run in texts.iter_mut() {
+            run.content.truncate(run.content.trim_end().len());
+        }
+        if let Some(shaper) = shaper {
+            texts = texts.into_iter().flat_map(|run| run.shape(shaper, fonts)).collect();
+        }
+
+        Self {
+            backgrounds,
+            texts,
+            drawn,
+            decorations,
+        }
+    }
+}
+
+impl TextRun {
+    // Splits the run into pieces drawn with and without ligatures. Characters no family has a
+    // glyph for are left to cosmic-text's fallback, which is not checked against the grid.
+    fn shape(self, shaper: &Shaper, fonts: &FontChain) -> Vec<TextRun> {
+        let Some(family) = self.family else {
+            return vec![self];
+        };
+        shaper
+            .split(&self.content, fonts.family(family), self.bold, self.italic)
+            .into_iter()
+            .map(|(range, ligatures)| TextRun {
+                ligatures,
+                ..self.slice(range)
+            })
+            .collect()
+    }
+
+    // Cells the run covers on the grid
+    pub fn cols(&self) -> usize {
+        self.content.chars().map(char_width).sum()
+    }
+
+    // Splits off the cell at `col` so it is drawn on its own, without ligatures
+    pub fn isolate(&self, col: usize) -> Vec<TextRun> {
+        let len = self.content.chars().count();
+        if !self.ligatures || col < self.col || col >= self.col + len {
+            return vec![self.clone()];
+        }
+        let col = col - self.col;
+        [0..col, col..col + 1, col + 1..len]
+            .into_iter()
+            .filter(|range| !range.is_empty())
+            .map(|range| TextRun {
+                ligatures: range.len() > 1,
+                ..self.slice(range)
+            })
+            .collect()
+    }
+
+    // The characters in `range` as a run of their own
+    fn slice(&self, range: Range<usize>) -> TextRun {
+        TextRun {
+            col: self.col + range.start,
+            content: self.content.chars().skip(range.start).take(range.len()).collect(),
+            ..self.clone()
+        }
+    }
+}
+
+impl DecorationRun {
+    fn same_lines(&self, other: &DecorationRun) -> bool {
+        self.color == other.color
+            && self.underline == other.underline
+            && self.underline_color == other.underline_color
+            && self.overline == other.overline
+            && self.strikethrough == other.strikethrough
+    }
+}
+
+// Default backgrounds are left to the window, unless reverse video turns the default text color
+// into a background
+fn background_color(style: &CellStyle, palette: &Palette) -> Color {
+    match style.background_color() {
+        TerminalColor::Default if style.reversed => palette.foreground,
+        color => palette.background_color(color),
+    }
+}
+
+fn text_color(style: &CellStyle, palette: &Palette) -> Color {
+    let color = match style.foreground_color() {
+        TerminalColor::Default if style.reversed => palette.background,
+        color => palette.foreground_color(color),
+    };
+    if style.weight == FontWeight::Dim {
+        Color {
+            a: color.a * DIM_ALPHA,
+            ..color
+        }
+    } else {
+        color
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::{cell::CellStyle, cursor::Cursor};
+
+    fn fonts() -> FontChain {
+        FontChain::with_coverage(vec!["monospace"], [])
+    }
+
+    fn buffer_with(lines: &[&str]) -> Buffer<Cell> {
+        let cols = lines[0].chars().count();
+        let mut buffer = Buffer::new(lines.len(), cols, vec![Cell::default(); lines.len() * cols]);
+        for (row, line) in lines.iter().enumerate() {
+            for (col, c) in line.chars().enumerate() {
+                buffer.cursor.set_position(row, col);
+                buffer.write(c, CellStyle::default());
+            }
+        }
+        buffer.cursor = Cursor::default();
+        buffer
+    }
+
+    #[test]
+    fn equally_styled_cells_become_one_trimmed_run() {
+        let buffer = buffer_with(&["  ab c  "]);
+        let runs = RowRuns::new(buffer.row(0), &mut fonts(), None, &Palette::default());
+        assert_eq!(runs.texts.len(), 1);
+        assert_eq!(runs.texts[0].col, 2);
+        assert_eq!(runs.texts[0].content, "ab c");
+        assert!(runs.backgrounds.is_empty());
+    }
+
+    #[test]
+    fn color_changes_split_runs() {
+        let mut buffer = buffer_with(&["abcd"]);
+        let mut red = CellStyle::default();
+        red.background = TerminalColor::Red;
+        red.foreground = TerminalColor::Red;
+        buffer.cursor.set_position(0, 2);
+        buffer.write('c', red);
+
+        let runs = RowRuns::new(buffer.row(0), &mut fonts(), None, &Palette::default());
+        let texts: Vec<_> = runs.texts.iter().map(|run| (run.col, run.content.as_str())).collect();
+        assert_eq!(texts, vec![(0, "ab"), (2, "c"), (3, "d")]);
+        assert_eq!(runs.backgrounds.len(), 1);
+        assert_eq!((runs.backgrounds[0].col, runs.backgrounds[0].len), (2, 1));
+    }
+
+    #[test]
+    fn decorations_span_blanks_and_split_on_style() {
+        let mut buffer = buffer_with(&["a b "]);
+        let mut underlined = CellStyle::default();
+        underlined.underline = Underline::Curly;
+        for (col, c) in "a b".chars().enumerate() {
+            buffer.cursor.set_position(0, col);
+            buffer.write(c, underlined);
+        }
+        underlined.strikethrough = true;
+        buffer.cursor.set_position(0, 3);
+        buffer.write(' ', underlined);
+
+        let runs = RowRuns::new(buffer.row(0), &mut fonts(), None, &Palette::default());
+        let decorations: Vec<_> = runs.decorations.iter().map(|run| (run.col, run.len)).collect();
+        assert_eq!(decorations, vec![(0, 3), (3, 1)]);
+        assert_eq!(runs.texts.len(), 1);
+    }
+
+    #[test]
+    fn bold_and_dim_cells_get_their_own_runs() {
+        let mut buffer = buffer_with(&["abc"]);
+        let mut bold = CellStyle::default();
+        bold.weight = FontWeight::Bold;
+        buffer.cursor.set_position(0, 1);
+        buffer.write('b', bold);
+        let mut dim = CellStyle::default();
+        dim.weight = FontWeight::Dim;
+        buffer.cursor.set_position(0, 2);
+        buffer.write('c', dim);
+
+        let runs = RowRuns::new(buffer.row(0), &mut fonts(), None, &Palette::default());
+        assert_eq!(runs.texts.len(), 3);
+        assert!(runs.texts[1].bold);
+        assert!(runs.texts[2].color.a < runs.texts[0].color.a);
+    }
+
+    #[test]
+    fn glyphs_missing_from_every_family_get_their_own_run() {
+        let mut fonts = FontChain::with_coverage(vec!["monospace", "symbols"], [('★', Some(1))]);
+        let buffer = buffer_with(&["a★\u{1f600}b"]);
+        let runs = RowRuns::new(buffer.row(0), &mut fonts, None, &Palette::default());
+        let families: Vec<_> = runs.texts.iter().map(|run| run.family).collect();
+        assert_eq!(families, vec![Some(0), Some(1), None, Some(0)]);
+    }
+
+    #[test]
+    fn fallback_and_wide_glyphs_are_placed_one_per_cell() {
+        let mut fonts = FontChain::with_coverage(vec!["monospace", "symbols"], [('★', Some(1)), ('漢', Some(0))]);
+        let mut buffer = Buffer::new(1, 8, vec![Cell::default(); 8]);
+        for c in "ab★★漢c".chars() {
+            buffer.print(c, CellStyle::default(), true);
+        }
+        let runs = RowRuns::new(buffer.row(0), &mut fonts, None, &Palette::default());
+        let texts: Vec<_> = runs
+            .texts
+            .iter()
+            .map(|run| (run.col, run.content.as_str(), run.cols()))
+            .collect();
+        assert_eq!(
+            texts,
+            vec![(0, "ab", 2), (2, "★", 1), (3, "★", 1), (4, "漢", 2), (6, "c", 1)]
+        );
+    }
+
+    #[test]
+    fn drawn_glyphs_split_text_runs() {
+        let buffer = buffer_with(&["a─┐b"]);
+        let runs = RowRuns::new(buffer.row(0), &mut fonts(), None, &Palette::default());
+        let texts: Vec<_> = runs.texts.iter().map(|run| (run.col, run.content.as_str())).collect();
+        assert_eq!(texts, vec![(0, "a"), (3, "b")]);
+        let drawn: Vec<_> = runs.drawn.iter().map(|glyph| (glyph.col, glyph.content)).collect();
+        assert_eq!(drawn, vec![(1, '─'), (2, '┐')]);
+    }
+
+    #[test]
+    fn cursor_cell_is_isolated_from_ligatures() {
+        let buffer = buffer_with(&["a->b"]);
+        let runs = RowRuns::new(buffer.row(0), &mut fonts(), None, &Palette::default());
+        let run = TextRun {
+            ligatures: true,
+            ..runs.texts[0].clone()
+        };
+
+        let pieces: Vec<_> = run
+            .isolate(2)
+            .iter()
+            .map(|piece| (piece.col, piece.content.clone(), piece.ligatures))
+            .collect();
+        assert_eq!(
+            pieces,
+            vec![
+                (0, "a-".to_string(), true),
+                (2, ">".to_string(), false),
+                (3, "b".to_string(), false)
+            ]
+        );
+        assert_eq!(run.isolate(7), vec![run.clone()]);
+    }
+
+    #[test]
+    fn scrolled_rows_are_reused_and_exposed_rows_rebuilt() {
+        let mut buffer = buffer_with(&["a", "b", "c"]);
+        let mut cache = RowCache::new(fonts(), None, Palette::default());
+        let damage = buffer.take_damage();
+        cache.update(&buffer, damage);
+
+        buffer.shift_row();
+        let damage = buffer.take_damage();
+        cache.update(&buffer, damage);
+
+        let contents: Vec<_> = cache
+            .rows()
+            .iter()
+            .map(|row| row.texts.first().map(|run| run.content.clone()).unwrap_or_default())
+            .collect();
+        assert_eq!(contents, vec!["b", "c", ""]);
+    }
+}
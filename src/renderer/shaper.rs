@@ -0,0 +1,84 @@
+use std::ops::Range;
+
+use iced::advanced::graphics::text::{
+    cosmic_text::{Attrs, Buffer, Family, Metrics, Shaping, Style, Weight, Wrap},
+    font_system,
+};
+
+// Distance in pixels a glyph may be off its cell before it counts as misplaced
+const GRID_TOLERANCE: f32 = 0.5;
+
+/// Shapes text runs with ligatures and checks the shaped glyphs still line up with the grid.
+/// Monospace programming fonts keep every glyph, ligatures included, a whole number of cells
+/// wide, but fonts further down the chain may not, and those characters are drawn without
+/// ligatures so they cannot push the rest of the run off the grid.
+#[derive(Debug, Clone, Copy)]
+pub struct Shaper {
+    font_size: f32,
+    cell_width: f32,
+}
+
+impl Shaper {
+    pub fn new(font_size: f32, cell_width: f32) -> Self {
+        Self { font_size, cell_width }
+    }
+
+    // Splits `content` into ranges of characters, flagged with whether shaping them with
+    // ligatures keeps them on the grid
+    pub fn split(&self, content: &str, family: &str, bold: bool, italic: bool) -> Vec<(Range<usize>, bool)> {
+        let mut font_system = font_system().write().expect("Could not lock font system");
+        let font_system = font_system.raw();
+
+        let mut buffer = Buffer::new(font_system, Metrics::new(self.font_size, self.font_size));
+        buffer.set_wrap(font_system, Wrap::None);
+        buffer.set_size(font_system, None, None);
+        let mut attributes = Attrs::new().family(Family::Name(family));
+        if bold {
+            attributes = attributes.weight(Weight::BOLD);
+        }
+        if italic {
+            attributes = attributes.style(Style::Italic);
+        }
+        buffer.set_text(font_system, content, attributes, Shaping::Advanced);
+
+        let mut aligned = vec![true; content.chars().count()];
+        for run in buffer.layout_runs() {
+            for glyph in run.glyphs {
+                let col = content[..glyph.start].chars().count();
+                let cols = content[glyph.start..glyph.end].chars().count();
+                // Glyphs have to start on their first cell and advance by whole cells
+                let width = (glyph.w / self.cell_width).round() * self.cell_width;
+                let on_grid = (glyph.x - col as f32 * self.cell_width).abs() < GRID_TOLERANCE
+                    && (glyph.w - width).abs() < GRID_TOLERANCE;
+                if !on_grid {
+                    aligned[col..col + cols].iter_mut().for_each(|flag| *flag = false);
+                }
+            }
+        }
+        group(&aligned)
+    }
+}
+
+// Groups consecutive equal flags into ranges
+fn group(flags: &[bool]) -> Vec<(Range<usize>, bool)> {
+    let mut groups: Vec<(Range<usize>, bool)> = Vec::new();
+    for (index, &flag) in flags.iter().enumerate() {
+        match groups.last_mut() {
+            Some((range, last)) if *last == flag => range.end = index + 1,
+            _ => groups.push((index..index + 1, flag)),
+        }
+    }
+    groups
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn consecutive_flags_are_grouped() {
+        let groups = group(&[true, true, false, true]);
+        assert_eq!(groups, vec![(0..2, true), (2..3, false), (3..4, true)]);
+        assert!(group(&[]).is_empty());
+    }
+}
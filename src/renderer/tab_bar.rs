@@ -0,0 +1,93 @@
+use iced::{
+    border,
+    widget::{button, container, row, text, Row},
+    window::Id,
+    Background, Element, Length, Padding,
+};
+
+use crate::{
+    palette::{mix, Palette},
+    panes::Panes,
+    session::Session,
+    tabs::Tabs,
+    Message,
+};
+
+// Height of the bar above the grid, it is only shown with more than one tab
+pub const TAB_BAR_HEIGHT: f32 = 28.0;
+
+// Longer titles are cut short so every tab keeps some room
+const MAX_LABEL_CHARS: usize = 24;
+
+/// The bar of tabs in a window, one button per tab with the focused pane's title, and whether
+/// something happened in it since it was last shown
+pub fn tab_bar<'a>(
+    window: Id,
+    tabs: &'a Tabs<Panes<Session>>,
+    default_title: &'a str,
+    palette: &'a Palette,
+) -> Element<'a, Message> {
+    let active = tabs.active_index();
+    let buttons = tabs.iter().enumerate().map(|(index, panes)| {
+        let title = panes.focused().terminal.title().unwrap_or(default_title);
+        let mut label = match title.char_indices().nth(MAX_LABEL_CHARS) {
+            Some((end, _)) => format!("{}…", &title[..end]),
+            None => title.to_string(),
+        };
+        if panes.iter().any(|session| session.bell) {
+            label.insert_str(0, "🔔 ");
+        } else if panes.iter().any(|session| session.activity) {
+            label.insert_str(0, "● ");
+        }
+        let close = button(text("×"))
+            .padding(Padding::from([0, 4]))
+            .style(move |_theme, status| close_style(palette, status))
+            .on_press(Message::CloseTab(window, index));
+        let tab = row![text(label).width(Length::Fill), close].spacing(4);
+        button(tab)
+            .width(Length::Fill)
+            .padding(Padding::from([4, 8]))
+            .style(move |_theme, status| tab_style(palette, index == active, status))
+            .on_press(Message::SelectTab(window, index))
+            .into()
+    });
+    container(Row::with_children(buttons).spacing(1))
+        .height(TAB_BAR_HEIGHT)
+        .style(move |_theme| container::Style {
+            background: Some(Background::Color(palette.selection)),
+            ..container::Style::default()
+        })
+        .into()
+}
+
+// The active tab takes the terminal's colors, the others are dimmed
+fn tab_style(palette: &Palette, active: bool, status: button::Status) -> button::Style {
+    let background = match (active, status) {
+        (true, _) => palette.background,
+        (false, button::Status::Hovered) => mix(palette.background, palette.foreground, 0.15),
+        (false, _) => mix(palette.background, palette.foreground, 0.08),
+    };
+    let text_color = match active {
+        true => palette.foreground,
+        false => mix(palette.foreground, palette.background, 0.35),
+    };
+    button::Style {
+        background: Some(Background::Color(background)),
+        text_color,
+        border: border::rounded(0),
+        ..button::Style::default()
+    }
+}
+
+// Only the × shows, on whatever the tab has behind it
+fn close_style(palette: &Palette, status: button::Status) -> button::Style {
+    let text_color = match status {
+        button::Status::Hovered | button::Status::Pressed => palette.foreground,
+        _ => mix(palette.foreground, palette.background, 0.35),
+    };
+    button::Style {
+        background: None,
+        text_color,
+        ..button::Style::default()
+    }
+}
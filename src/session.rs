@@ -0,0 +1,186 @@
+use iced::{futures::Stream, Subscription, Task};
+
+use crate::{
+    config::Config,
+    mux::client,
+    renderer::{fonts::FontChain, rows::RowCache, shaper::Shaper},
+    structs::{selection::TextSelection, terminalsize::TerminalSize},
+    term::term::{self, Shell},
+    terminal::Terminal,
+    Message,
+};
+
+/// Text being looked for in the scrollback
+#[derive(Debug, Clone, Default)]
+pub struct Search {
+    pub query: String,
+    // Line and first column of the match shown, lines counted from the oldest one in the
+    // scrollback
+    shown: Option<(usize, usize)>,
+}
+
+/// One shell with what is drawn of it, shown in a tab of its own
+pub struct Session {
+    // Unique for the whole run, the shell's subscription and output are told apart by it
+    pub id: usize,
+    pub terminal: Terminal,
+    pub rows: RowCache,
+    pub selection: Option<TextSelection>,
+    pub search: Option<Search>,
+    // The shell is gone and the session held open, Enter starts a new one
+    pub ended: bool,
+    // Output came, or the bell rang, while another tab was shown
+    pub activity: bool,
+    pub bell: bool,
+    // The shell runs in the session server, fixed when the session starts as switching would end
+    // the shell
+    mux: bool,
+    // A session already running in the server, shown instead of starting a shell
+    attach: Option<u64>,
+    // Started instead of the configured shell
+    shell: Option<Shell>,
+}
+
+impl Session {
+    pub fn new(id: usize, size: TerminalSize, config: &Config) -> Self {
+        let mut session = Self {
+            id,
+            terminal: terminal(size, config),
+            rows: row_cache(config),
+            selection: None,
+            search: None,
+            ended: false,
+            activity: false,
+            bell: false,
+            mux: config.mux,
+            attach: None,
+            shell: None,
+        };
+        session.update_rows();
+        session
+    }
+
+    // Shows `session` from the session server, its screen is replayed once attached
+    pub fn attach(id: usize, size: TerminalSize, session: u64, config: &Config) -> Self {
+        Self {
+            mux: true,
+            attach: Some(session),
+            ..Self::new(id, size, config)
+        }
+    }
+
+    pub fn with_shell(self, shell: Option<Shell>) -> Self {
+        Self { shell, ..self }
+    }
+
+    pub fn subscription(&self, config: &Config) -> Subscription<Message> {
+        let size = self.terminal.size();
+        let shell = self.shell.clone().unwrap_or_else(|| config.shell());
+        match (self.attach, self.mux) {
+            (Some(session), _) => subscribe(self.id, client::attach(session, size, config.scrollback)),
+            (None, true) => subscribe(self.id, client::open(shell, size, config.scrollback)),
+            (None, false) => subscribe(self.id, term::Term::spawn(self.terminal.winsize(), shell)),
+        }
+    }
+
+    // Starts the shell again on a clean screen, under a new id so it gets a new subscription
+    pub fn restart(&mut self, id: usize, config: &Config) {
+        let shell = self.shell.take();
+        *self = Self::new(id, self.terminal.size(), config).with_shell(shell);
+    }
+
+    // Rebuilds the cached runs of rows the terminal changed since last time
+    pub fn update_rows(&mut self) {
+        let damage = self.terminal.take_damage();
+        self.rows.update(self.terminal.buffer(), damage);
+    }
+
+    // Scrolls the view through the scrollback, `lines` up or down for negative `lines`
+    pub fn scroll(&mut self, lines: isize) {
+        self.terminal.scroll_display(lines);
+        self.selection = None;
+        self.update_rows();
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.terminal.scroll_to_bottom();
+        self.selection = None;
+        self.update_rows();
+    }
+
+    // Selects the match of the search before the one shown, left of it on the same line or on a
+    // line above, or the last match if none is shown
+    pub fn find_previous(&mut self) {
+        let Some(search) = &mut self.search else {
+            return;
+        };
+        let before = search.shown.unwrap_or((usize::MAX, usize::MAX));
+        let Some((line, columns)) = self.terminal.find(&search.query, before) else {
+            return;
+        };
+        search.shown = Some((line, columns.start));
+        let row = self.terminal.show_line(line);
+        let mut selection = TextSelection::new(row, columns.start);
+        selection.extend(row, columns.end);
+        self.selection = Some(selection);
+        self.update_rows();
+    }
+
+    // The query changed, look again from the bottom
+    pub fn search_again(&mut self) {
+        if let Some(search) = &mut self.search {
+            search.shown = None;
+        }
+        self.selection = None;
+        self.find_previous();
+    }
+
+    pub fn resize(&mut self, size: TerminalSize) -> Task<Message> {
+        if size == self.terminal.size() {
+            return Task::none();
+        }
+        self.selection = None;
+        let task = self.terminal.resize(size);
+        self.update_rows();
+        task
+    }
+
+    // Redraws everything with a new font or colors
+    pub fn apply_config(&mut self, config: &Config) {
+        self.terminal.set_scrollback(config.scrollback);
+        self.rows = row_cache(config);
+        self.update_rows();
+    }
+
+    pub fn set_shaper(&mut self, config: &Config) {
+        self.rows.set_shaper(shaper(config));
+        self.update_rows();
+    }
+}
+
+fn subscribe(id: usize, events: impl Stream<Item = term::Event> + Send + 'static) -> Subscription<Message> {
+    Subscription::run_with_id(("terminal", id), events)
+        .with(id)
+        .map(|(id, event)| Message::TerminalOutput(id, event))
+}
+
+fn shaper(config: &Config) -> Option<Shaper> {
+    config
+        .ligatures
+        .then(|| Shaper::new(config.font_size, config.cell_size.width))
+}
+
+fn terminal(size: TerminalSize, config: &Config) -> Terminal {
+    let mut terminal = Terminal::new(size);
+    terminal.set_scrollback(config.scrollback);
+    terminal.set_c1_controls(config.c1_controls);
+    terminal
+}
+
+fn row_cache(config: &Config) -> RowCache {
+    RowCache::new(
+        FontChain::new(config.font_families.clone()),
+        shaper(config),
+        config.palette.clone(),
+    )
+}
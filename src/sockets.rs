@@ -0,0 +1,110 @@
+use std::{
+    fs,
+    io::{self, ErrorKind},
+    os::{
+        fd::AsRawFd,
+        unix::{
+            fs::{DirBuilderExt, MetadataExt, PermissionsExt},
+            net::{UnixListener, UnixStream},
+        },
+    },
+    path::{Path, PathBuf},
+};
+
+// Made in the runtime directory, holds the sockets of every terminal and session server we run
+const DIR_NAME: &str = "term";
+
+// Where our sockets go. The shared temp dir is no place for them, anyone could bind a socket
+// there first, so there is none without $XDG_RUNTIME_DIR.
+pub fn dir() -> io::Result<PathBuf> {
+    let runtime =
+        dirs::runtime_dir().ok_or_else(|| io::Error::new(ErrorKind::NotFound, "XDG_RUNTIME_DIR is not set"))?;
+    private_dir(&runtime.join(DIR_NAME))
+}
+
+// Makes `dir` only the user can enter, or checks that it is so when it was there already
+fn private_dir(dir: &Path) -> io::Result<PathBuf> {
+    match fs::DirBuilder::new().mode(0o700).create(dir) {
+        Err(error) if error.kind() != ErrorKind::AlreadyExists => return Err(error),
+        _ => {}
+    }
+    // Not followed, a link could point anywhere
+    let metadata = fs::symlink_metadata(dir)?;
+    if !metadata.is_dir() || metadata.uid() != unsafe { libc::getuid() } || metadata.mode() & 0o077 != 0 {
+        return Err(io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} is open to other users", dir.display()),
+        ));
+    }
+    Ok(dir.to_path_buf())
+}
+
+// Listens on `path` in our directory, the socket itself is also closed to others
+pub fn bind(path: &Path) -> io::Result<UnixListener> {
+    let listener = UnixListener::bind(path)?;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+    Ok(listener)
+}
+
+// Connects to `path`, but only to a process running as the user
+pub fn connect(path: &Path) -> io::Result<UnixStream> {
+    let stream = UnixStream::connect(path)?;
+    if peer_uid(&stream)? != unsafe { libc::getuid() } {
+        return Err(io::Error::new(
+            ErrorKind::PermissionDenied,
+            format!("{} belongs to another user", path.display()),
+        ));
+    }
+    Ok(stream)
+}
+
+fn peer_uid(stream: &UnixStream) -> io::Result<libc::uid_t> {
+    let mut credentials = libc::ucred { pid: 0, uid: 0, gid: 0 };
+    let mut length = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+    let result = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut credentials as *mut libc::ucred as *mut libc::c_void,
+            &mut length,
+        )
+    };
+    if result != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(credentials.uid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scratch(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("term-sockets-{}-{}", std::process::id(), name));
+        let _ = fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn the_directory_is_made_private_and_checked_again() {
+        let dir = scratch("private");
+        private_dir(&dir).unwrap();
+        assert_eq!(fs::metadata(&dir).unwrap().mode() & 0o777, 0o700);
+        assert!(private_dir(&dir).is_ok());
+
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o755)).unwrap();
+        assert_eq!(private_dir(&dir).unwrap_err().kind(), ErrorKind::PermissionDenied);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn sockets_are_closed_to_others_and_reach_our_own_processes() {
+        let dir = private_dir(&scratch("bind")).unwrap();
+        let path = dir.join("test.sock");
+        let _listener = bind(&path).unwrap();
+        assert_eq!(fs::metadata(&path).unwrap().mode() & 0o777, 0o600);
+        assert!(connect(&path).is_ok());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,20 +1,34 @@
 use crate::structs::cursor::Cursor;
-use std::ops::{Index, IndexMut};
+use std::{
+    collections::VecDeque,
+    ops::{Index, IndexMut, Range},
+};
 
 use super::{
-    cell::{Cell, CellStyle},
+    cell::{char_width, Cell, CellStyle, WIDE_SPACER},
     cursor::Direction,
+    damage::Damage,
 };
 
+/// Grid of rows × cols cells. Rows are stored as a ring in `data`: `offset` is the physical
+/// row holding the first visible row, so scrolling the whole screen only moves the offset
+/// instead of copying every cell.
 #[derive(Debug, Clone)]
 pub struct Buffer<T> {
     pub rows: usize,
     pub cols: usize,
     data: Vec<T>,
+    offset: usize,
     top: usize,
     bottom: usize,
     pub cursor: Cursor,
     saved_cursor: Option<Cursor>,
+    damage: Damage,
+    // Rows scrolled off the top of the screen, oldest first
+    history: VecDeque<Vec<T>>,
+    scrollback: usize,
+    // Rows of history shown above the screen while scrolled back, 0 shows the screen itself
+    display_offset: usize,
 }
 
 impl<T: Clone + Default + Copy> Buffer<T> {
@@ -24,74 +38,174 @@ impl<T: Clone + Default + Copy> Buffer<T> {
             rows,
             cols,
             data,
+            offset: 0,
             top: 0,
             bottom: rows - 1,
             cursor: Cursor::default(),
             saved_cursor: None,
+            damage: Damage::full(rows, cols),
+            history: VecDeque::new(),
+            scrollback: 0,
+            display_offset: 0,
+        }
+    }
+
+    // Keeps up to `lines` rows scrolled off the screen, dropping the oldest beyond that
+    pub fn set_scrollback(&mut self, lines: usize) {
+        self.scrollback = lines;
+        while self.history.len() > lines {
+            self.history.pop_front();
+        }
+        self.display_offset = self.display_offset.min(self.history.len());
+    }
+
+    pub fn display_offset(&self) -> usize {
+        self.display_offset
+    }
+
+    // Scrolls the view `lines` rows back into the history, or forward for negative `lines`
+    pub fn scroll_display(&mut self, lines: isize) {
+        let offset = self.display_offset.saturating_add_signed(lines).min(self.history.len());
+        if offset != self.display_offset {
+            self.display_offset = offset;
+            self.damage.mark_all();
         }
     }
 
+    // Row `row` of the view, which comes from the history while scrolled back. History rows keep
+    // the width they had when they scrolled off.
+    pub fn visible_row(&self, row: usize) -> &[T] {
+        if row < self.display_offset {
+            &self.history[self.history.len() - self.display_offset + row]
+        } else {
+            self.row(row - self.display_offset)
+        }
+    }
+
+    // Rows scrolled off the top of the screen, oldest first
+    pub fn history(&self) -> impl Iterator<Item = &[T]> {
+        self.history.iter().map(Vec::as_slice)
+    }
+
+    // Changes since the damage was last taken, see `Damage`
+    pub fn damage(&self) -> &Damage {
+        &self.damage
+    }
+
+    pub fn take_damage(&mut self) -> Damage {
+        std::mem::replace(&mut self.damage, Damage::new(self.rows, self.cols))
+    }
+
+    // Forces the renderer to redraw everything, e.g. when switching to another buffer
+    pub fn damage_all(&mut self) {
+        self.damage.mark_all();
+    }
+
     pub fn get(&mut self, cursor: Cursor) -> Option<&mut T> {
-        let index = cursor.row * self.cols + cursor.col;
+        if cursor.row >= self.rows || cursor.col >= self.cols {
+            return None;
+        }
+        let index = self.index_of(cursor);
+        self.damage.mark(cursor.row, cursor.col..cursor.col + 1);
         self.data.get_mut(index)
     }
 
-    // Iterate grid row by row
+    pub fn row(&self, row: usize) -> &[T] {
+        &self.data[self.row_range(row)]
+    }
+
+    pub fn row_mut(&mut self, row: usize) -> &mut [T] {
+        let range = self.row_range(row);
+        self.damage.mark_row(row);
+        &mut self.data[range]
+    }
+
+    // Iterate the view row by row, history rows first while scrolled back
     pub fn iter_rows(&self) -> impl Iterator<Item = &[T]> {
-        self.data.chunks(self.cols)
+        (0..self.rows).map(move |row| self.visible_row(row))
     }
 
     // Appends empty row last and removes first row inside scroll area
     pub fn shift_row(&mut self) {
-        let from = self.top * self.cols;
-        let to = (self.bottom + 1) * self.cols;
-        self.data.copy_within(from + self.cols..to, from);
-        self.data[(to - self.cols)..to].fill(T::default());
+        if self.scrolls_full_screen() {
+            if self.scrollback > 0 {
+                if self.history.len() == self.scrollback {
+                    self.history.pop_front();
+                }
+                self.history.push_back(self.row(0).to_vec());
+                // Keep showing the same rows while scrolled back
+                if self.display_offset > 0 {
+                    self.display_offset = (self.display_offset + 1).min(self.history.len());
+                }
+            }
+            self.offset = (self.offset + 1) % self.rows;
+        } else {
+            for row in self.top..self.bottom {
+                self.copy_row(row + 1, row);
+            }
+        }
+        self.damage.scroll(self.top, self.bottom, 1);
+        self.row_mut(self.bottom).fill(T::default());
     }
 
     // Prepends empty row first and removes last row inside scroll area
     pub fn unshift_row(&mut self) {
         if self.cursor.row == self.top {
-            let from = self.top * self.cols; // 0
-            let to = self.bottom * self.cols; // 8
-            self.data.copy_within(from..to, from + self.cols);
-            self.data[from..(from + self.cols)].fill(T::default());
+            if self.scrolls_full_screen() {
+                self.offset = (self.offset + self.rows - 1) % self.rows;
+            } else {
+                for row in (self.top..self.bottom).rev() {
+                    self.copy_row(row, row + 1);
+                }
+            }
+            self.damage.scroll(self.top, self.bottom, -1);
+            self.row_mut(self.top).fill(T::default());
         } else {
             self.cursor.up(1, self.rows - 1);
         }
     }
 
+    fn scrolls_full_screen(&self) -> bool {
+        self.top == 0 && self.bottom == self.rows - 1
+    }
+
+    fn copy_row(&mut self, from: usize, to: usize) {
+        let source = self.row_range(from);
+        let destination = self.row_range(to).start;
+        self.data.copy_within(source, destination);
+    }
+
     pub fn clear_selection(&mut self, selection: Selection) {
+        let Cursor { row, col, .. } = self.cursor;
+        let col = col.min(self.cols);
         match selection {
-            Selection::Line => {
-                let from = self.cursor.row * self.cols;
-                let to = (self.cursor.row + 1) * self.cols;
-                self[from..to].fill(T::default());
-            }
-            Selection::FromStartOfLine => {
-                let from = self.cursor.row * self.cols;
-                let to = self.cursor.row * self.cols + self.cursor.col;
-                self[from..to].fill(T::default());
-            }
-            Selection::ToEndOfLine => {
-                let from = self.cursor.row * self.cols + self.cursor.col;
-                let to = (self.cursor.row + 1) * self.cols;
-                self[from..to].fill(T::default());
-            }
+            Selection::Line => self.clear_cells(row, 0..self.cols),
+            Selection::FromStartOfLine => self.clear_cells(row, 0..col),
+            Selection::ToEndOfLine => self.clear_cells(row, col..self.cols),
             Selection::ToEndOfDisplay => {
-                let from = self.cursor.row * self.cols + self.cursor.col;
-                let to = self.data.len();
-                self[from..to].fill(T::default());
+                self.clear_cells(row, col..self.cols);
+                for row in (row + 1)..self.rows {
+                    self.clear_cells(row, 0..self.cols);
+                }
             }
             Selection::Characters(n) => {
-                let from = self.cursor.row * self.cols + self.cursor.col;
-                let to = from + n as usize;
-                self[from..to].fill(T::default());
+                let to = (col + n as usize).min(self.cols);
+                self.clear_cells(row, col..to);
             }
         }
     }
 
+    fn clear_cells(&mut self, row: usize, cols: Range<usize>) {
+        let start = self.row_range(row).start;
+        self.data[start + cols.start..start + cols.end].fill(T::default());
+        self.damage.mark(row, cols);
+    }
+
     pub fn resize(&mut self, rows: usize, cols: usize) {
+        // Resizing works on the flat grid, so put the rows back in visible order first
+        self.data.rotate_left(self.offset * self.cols);
+        self.offset = 0;
+
         if rows < self.rows {
             // When removing rows, we remove from the top
             self.data.drain(0..self.cols);
@@ -104,6 +218,11 @@ impl<T: Clone + Default + Copy> Buffer<T> {
             self.cols = cols;
         }
 
+        // The scroll region is reset to the full screen, like xterm does
+        self.top = 0;
+        self.bottom = self.rows - 1;
+        self.damage = Damage::full(self.rows, self.cols);
+
         if self.cursor.col >= self.cols {
             self.cursor.up(self.cursor.col - self.cols, self.rows);
         }
@@ -145,18 +264,16 @@ impl<T: Clone + Default + Copy> Buffer<T> {
         }
     }
 
-    pub fn set_top_bottom(&mut self, top: usize, bottom: usize) {
-        self.top = top;
-        self.bottom = bottom;
+    // First and last row of the scroll region
+    pub fn top_bottom(&self) -> (usize, usize) {
+        (self.top, self.bottom)
     }
 
-    pub fn advance_cursor(&mut self, wrap_on_end: bool) {
-        let cursor_at_end = self.cursor.col == (self.cols);
-        if cursor_at_end && wrap_on_end {
-            self.cursor.down(1, self.rows);
-            self.cursor.col = 0;
-        } else if !cursor_at_end {
-            self.cursor.right(1, self.cols);
+    pub fn set_top_bottom(&mut self, top: usize, bottom: usize) {
+        let bottom = bottom.min(self.rows - 1);
+        if top < bottom {
+            self.top = top;
+            self.bottom = bottom;
         }
     }
 
@@ -179,33 +296,69 @@ impl Buffer<Cell> {
             println!("Warning: tried printing outside grid");
         }
     }
-}
 
-impl<T> Index<Cursor> for Buffer<T> {
-    type Output = T;
+    // Writes `c` at the cursor and moves past it. The cursor may stop right of the last column,
+    // the next character then wraps to a new line when `auto_wrap` is set and overwrites the
+    // last column otherwise. Wide characters take two cells and wrap as a whole.
+    pub fn print(&mut self, c: char, cell_style: CellStyle, auto_wrap: bool) {
+        let width = char_width(c);
+        if width == 0 || width > self.cols {
+            return;
+        }
+        if self.cursor.col + width > self.cols {
+            if auto_wrap {
+                self.newline(false);
+                self.cursor.col = 0;
+            } else {
+                self.cursor.col = self.cols - width;
+            }
+        }
 
-    fn index(&self, index: Cursor) -> &Self::Output {
-        &self.data[index.row * self.cols + index.col]
+        let Cursor { row, col } = self.cursor;
+        // Half of a wide character left behind is blanked, it can not be drawn on its own
+        if self[Cursor { row, col }].is_spacer() && col > 0 {
+            self[Cursor { row, col: col - 1 }].content = ' ';
+        }
+        let after = Cursor { row, col: col + width };
+        if after.col < self.cols && self[after].is_spacer() {
+            self[after].content = ' ';
+        }
+
+        self.write(c, cell_style);
+        if width == 2 {
+            self[Cursor { row, col: col + 1 }] = Cell {
+                content: WIDE_SPACER,
+                style: cell_style,
+            };
+        }
+        self.cursor.col += width;
     }
 }
 
-impl<T> IndexMut<Cursor> for Buffer<T> {
-    fn index_mut(&mut self, index: Cursor) -> &mut Self::Output {
-        &mut self.data[index.row * self.cols + index.col]
+impl<T> Buffer<T> {
+    fn row_range(&self, row: usize) -> Range<usize> {
+        let start = ((self.offset + row) % self.rows) * self.cols;
+        start..start + self.cols
     }
-}
 
-impl<T> IndexMut<std::ops::Range<usize>> for Buffer<T> {
-    fn index_mut(&mut self, range: std::ops::Range<usize>) -> &mut Self::Output {
-        &mut self.data[range]
+    fn index_of(&self, cursor: Cursor) -> usize {
+        self.row_range(cursor.row).start + cursor.col
     }
 }
 
-impl<T> Index<std::ops::Range<usize>> for Buffer<T> {
-    type Output = [T];
+impl<T> Index<Cursor> for Buffer<T> {
+    type Output = T;
+
+    fn index(&self, index: Cursor) -> &Self::Output {
+        &self.data[self.index_of(index)]
+    }
+}
 
-    fn index(&self, range: std::ops::Range<usize>) -> &Self::Output {
-        &self.data[range]
+impl<T> IndexMut<Cursor> for Buffer<T> {
+    fn index_mut(&mut self, index: Cursor) -> &mut Self::Output {
+        self.damage.mark(index.row, index.col..index.col + 1);
+        let index = self.index_of(index);
+        &mut self.data[index]
     }
 }
 
@@ -220,6 +373,15 @@ pub enum Selection {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::structs::damage::Scroll;
+
+    fn cells<T: Copy + Default>(grid: &Buffer<T>) -> Vec<T> {
+        grid.iter_rows().flatten().copied().collect()
+    }
+
+    fn at(row: usize, col: usize) -> Cursor {
+        Cursor { row, col }
+    }
 
     #[test]
     fn creating_grid_retains_correct_width_height() {
@@ -299,6 +461,178 @@ mod tests {
     fn scrolling_one_line_down_should_work() {
         let mut grid = Buffer::new(3, 2, vec![1, 2, 3, 4, 5, 6]);
         grid.shift_row();
-        assert_eq!(grid.data, vec![3, 4, 5, 6, 0, 0]);
+        assert_eq!(cells(&grid), vec![3, 4, 5, 6, 0, 0]);
+    }
+
+    #[test]
+    fn scrolling_full_screen_repeatedly_keeps_row_order() {
+        let mut grid = Buffer::new(3, 2, vec![1, 2, 3, 4, 5, 6]);
+        grid.shift_row();
+        grid[at(2, 0)] = 7;
+        grid.shift_row();
+        assert_eq!(cells(&grid), vec![5, 6, 7, 0, 0, 0]);
+        assert_eq!(grid[at(1, 0)], 7);
+    }
+
+    #[test]
+    fn scrolling_inside_region_leaves_rows_outside_untouched() {
+        let mut grid = Buffer::new(4, 1, vec![1, 2, 3, 4]);
+        grid.set_top_bottom(1, 2);
+        grid.shift_row();
+        assert_eq!(cells(&grid), vec![1, 3, 0, 4]);
+
+        grid.cursor.row = 1;
+        grid.unshift_row();
+        assert_eq!(cells(&grid), vec![1, 0, 3, 4]);
+    }
+
+    #[test]
+    fn reverse_scrolling_full_screen_prepends_empty_row() {
+        let mut grid = Buffer::new(3, 2, vec![1, 2, 3, 4, 5, 6]);
+        grid.unshift_row();
+        assert_eq!(cells(&grid), vec![0, 0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn resizing_after_scrolling_keeps_visible_order() {
+        let mut grid = Buffer::new(3, 2, vec![1, 2, 3, 4, 5, 6]);
+        grid.shift_row();
+        grid.resize(4, 2);
+        assert_eq!(grid.data, vec![3, 4, 5, 6, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn clearing_to_end_of_display_after_scrolling() {
+        let mut grid = Buffer::new(3, 2, vec![1, 2, 3, 4, 5, 6]);
+        grid.shift_row();
+        grid.cursor.set_position(0, 1);
+        grid.clear_selection(Selection::ToEndOfDisplay);
+        assert_eq!(cells(&grid), vec![3, 0, 0, 0, 0, 0]);
+    }
+
+    #[test]
+    fn rows_scrolled_off_screen_are_kept_up_to_scrollback() {
+        let mut grid = Buffer::new(3, 1, vec![1, 2, 3]);
+        grid.set_scrollback(2);
+        grid.shift_row();
+        grid.shift_row();
+        grid.shift_row();
+        assert_eq!(grid.history, vec![vec![2], vec![3]]);
+
+        // Rows leaving a scroll region are not part of the history
+        grid.set_top_bottom(1, 2);
+        grid.shift_row();
+        assert_eq!(grid.history, vec![vec![2], vec![3]]);
+
+        grid.set_scrollback(1);
+        assert_eq!(grid.history, vec![vec![3]]);
+    }
+
+    #[test]
+    fn scrolled_back_view_shows_history_rows() {
+        let mut grid = Buffer::new(2, 1, vec![1, 2]);
+        grid.set_scrollback(10);
+        grid.shift_row();
+        grid[at(1, 0)] = 3;
+        grid.scroll_display(5);
+        assert_eq!(grid.display_offset(), 1);
+        assert_eq!((grid.visible_row(0), grid.visible_row(1)), (&[1][..], &[2][..]));
+
+        // New output keeps the view where it is
+        grid.shift_row();
+        assert_eq!(grid.display_offset(), 2);
+        assert_eq!(grid.visible_row(0), &[1]);
+
+        grid.scroll_display(-2);
+        assert_eq!((grid.visible_row(0), grid.visible_row(1)), (&[3][..], &[0][..]));
+    }
+
+    #[test]
+    fn writing_marks_only_touched_cells_as_damaged() {
+        let mut grid = Buffer::new(3, 4, vec![Cell::default(); 3 * 4]);
+        grid.take_damage();
+        grid.cursor.set_position(1, 1);
+        grid.write('a', CellStyle::default());
+        grid.cursor.set_position(1, 2);
+        grid.write('b', CellStyle::default());
+
+        let damage = grid.take_damage();
+        assert_eq!(damage.dirty_rows().collect::<Vec<_>>(), vec![(1, 1..3)]);
+        assert!(grid.damage().is_empty());
+    }
+
+    #[test]
+    fn scrolling_moves_damage_along_with_rows() {
+        let mut grid = Buffer::new(3, 2, vec![1, 2, 3, 4, 5, 6]);
+        grid.take_damage();
+        grid[at(1, 0)] = 7;
+        grid.shift_row();
+        grid.shift_row();
+
+        let damage = grid.take_damage();
+        assert_eq!(
+            damage.scrolls(),
+            &[Scroll {
+                top: 0,
+                bottom: 2,
+                lines: 2
+            }]
+        );
+        assert_eq!(damage.dirty_rows().collect::<Vec<_>>(), vec![(1, 0..2), (2, 0..2)]);
+    }
+
+    #[test]
+    fn resizing_damages_everything() {
+        let mut grid = Buffer::new(3, 2, vec![1; 3 * 2]);
+        grid.take_damage();
+        grid.resize(2, 2);
+        assert!(grid.damage().is_full());
+    }
+
+    fn printed(cols: usize, text: &str, auto_wrap: bool) -> Buffer<Cell> {
+        let mut grid = Buffer::new(2, cols, vec![Cell::default(); 2 * cols]);
+        for c in text.chars() {
+            grid.print(c, CellStyle::default(), auto_wrap);
+        }
+        grid
+    }
+
+    fn contents(grid: &Buffer<Cell>) -> String {
+        grid.iter_rows().flatten().map(|cell| cell.content).collect()
+    }
+
+    #[test]
+    fn printing_past_the_last_column_wraps_without_losing_characters() {
+        let grid = printed(3, "abcd", true);
+        assert_eq!(contents(&grid), "abcd  ");
+        assert_eq!(grid.cursor, at(1, 1));
+
+        let grid = printed(3, "abcd", false);
+        assert_eq!(contents(&grid), "abd   ");
+    }
+
+    #[test]
+    fn wide_characters_take_two_cells_and_wrap_as_a_whole() {
+        let grid = printed(3, "a漢字", true);
+        assert_eq!(contents(&grid), "a漢\0字\0 ");
+        assert_eq!(grid.cursor, at(1, 2));
+    }
+
+    #[test]
+    fn overwriting_half_of_a_wide_character_blanks_the_other_half() {
+        let mut grid = printed(4, "漢字", true);
+        grid.cursor.set_position(0, 1);
+        grid.print('a', CellStyle::default(), true);
+        assert_eq!(contents(&grid), " a字\0    ");
+
+        grid.cursor.set_position(0, 2);
+        grid.print('b', CellStyle::default(), true);
+        assert_eq!(contents(&grid), " ab     ");
+    }
+
+    #[test]
+    fn zero_width_characters_take_no_cell() {
+        let grid = printed(3, "e\u{301}x", true);
+        assert_eq!(contents(&grid), "ex    ");
     }
 }
@@ -1,4 +1,12 @@
-use crate::term::{colors::TerminalColor, graphics::Graphics};
+use unicode_width::UnicodeWidthChar;
+
+use crate::{
+    ansi_parser::SgrParameter,
+    term::{colors::TerminalColor, graphics::Graphics},
+};
+
+// Content of the cell right of a wide character, which is drawn over both cells
+pub const WIDE_SPACER: char = '\0';
 
 #[derive(Debug, Clone, Copy)]
 pub struct Cell {
@@ -6,6 +14,18 @@ pub struct Cell {
     pub style: CellStyle,
 }
 
+impl Cell {
+    pub fn is_spacer(&self) -> bool {
+        self.content == WIDE_SPACER
+    }
+}
+
+// Cells `c` takes on the grid: two for wide East Asian characters and emoji, none for
+// combining marks and other characters programs count as zero wide
+pub fn char_width(c: char) -> usize {
+    c.width().unwrap_or(1)
+}
+
 impl Default for Cell {
     fn default() -> Self {
         Self {
@@ -15,24 +35,50 @@ impl Default for Cell {
     }
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct CellStyle {
     pub foreground: TerminalColor,
     pub background: TerminalColor,
     pub reversed: bool,
     pub weight: FontWeight,
     pub italic: bool,
-    pub underline: bool,
+    pub underline: Underline,
+    pub underline_color: Option<TerminalColor>,
+    pub overline: bool,
     pub strikethrough: bool,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum FontWeight {
     Dim,
     Normal,
     Bold,
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Underline {
+    None,
+    Single,
+    Double,
+    Curly,
+    Dotted,
+    Dashed,
+}
+
+impl Underline {
+    // Style from the sub parameter in SGR 4:n
+    pub fn from_style(style: u8) -> Self {
+        match style {
+            1 => Underline::Single,
+            2 => Underline::Double,
+            3 => Underline::Curly,
+            4 => Underline::Dotted,
+            5 => Underline::Dashed,
+            _ => Underline::None,
+        }
+    }
+}
+
 impl CellStyle {
     pub fn default() -> Self {
         Self {
@@ -41,7 +87,9 @@ impl CellStyle {
             reversed: false,
             weight: FontWeight::Normal,
             italic: false,
-            underline: false,
+            underline: Underline::None,
+            underline_color: None,
+            overline: false,
             strikethrough: false,
         }
     }
@@ -62,11 +110,44 @@ impl CellStyle {
         }
     }
 
-    pub fn modify(&mut self, attributes: &[u8]) {
-        if attributes.is_empty() {
+    // Plain parameters are read as a run, so `38;5;n` can span several of them. One with sub
+    // parameters stands on its own.
+    pub fn modify(&mut self, parameters: &[SgrParameter]) {
+        if parameters.is_empty() {
             self.parse_attribute(Graphics::Reset);
-        } else {
-            self.modify_recursive(attributes);
+            return;
+        }
+        let mut attributes = Vec::new();
+        for parameter in parameters {
+            if let [attribute] = parameter[..] {
+                attributes.push(attribute);
+                continue;
+            }
+            self.modify_recursive(&attributes);
+            attributes.clear();
+            self.modify_with_sub_parameters(parameter);
+        }
+        self.modify_recursive(&attributes);
+    }
+
+    // `4:3` for a curly underline, `38:5:n` and `38:2::r:g:b` for colors, with or without the
+    // color space. Forms we don't know are ignored.
+    fn modify_with_sub_parameters(&mut self, parameter: &[u8]) {
+        match *parameter {
+            [4, style] => self.underline = Underline::from_style(style),
+            [38, 2, _, r, g, b] | [38, 2, r, g, b] => {
+                self.parse_attribute(Graphics::SetForeground(TerminalColor::TwentyFourBit(r, g, b)))
+            }
+            [48, 2, _, r, g, b] | [48, 2, r, g, b] => {
+                self.parse_attribute(Graphics::SetBackground(TerminalColor::TwentyFourBit(r, g, b)))
+            }
+            [58, 2, _, r, g, b] | [58, 2, r, g, b] => {
+                self.parse_attribute(Graphics::SetUnderlineColor(TerminalColor::TwentyFourBit(r, g, b)))
+            }
+            [38, 5, n] => self.parse_attribute(Graphics::SetForeground(TerminalColor::EightBit(n))),
+            [48, 5, n] => self.parse_attribute(Graphics::SetBackground(TerminalColor::EightBit(n))),
+            [58, 5, n] => self.parse_attribute(Graphics::SetUnderlineColor(TerminalColor::EightBit(n))),
+            _ => {}
         }
     }
 
@@ -100,6 +181,18 @@ impl CellStyle {
                 self.modify_recursive(rest);
             }
 
+            [58, 2, r, g, b, ref rest @ ..] => {
+                // parse 24 bit color, set as underline color
+                self.parse_attribute(Graphics::SetUnderlineColor(TerminalColor::TwentyFourBit(r, g, b)));
+                self.modify_recursive(rest);
+            }
+
+            [58, 5, n, ref rest @ ..] => {
+                // parse 8 bit color, set as underline color
+                self.parse_attribute(Graphics::SetUnderlineColor(TerminalColor::EightBit(n)));
+                self.modify_recursive(rest);
+            }
+
             [n, ref rest @ ..] => {
                 self.parse_attribute(Graphics::parse_ansi(&n));
                 self.modify_recursive(rest);
@@ -113,14 +206,51 @@ impl CellStyle {
             Graphics::Bold => self.weight = FontWeight::Bold,
             Graphics::Dim => self.weight = FontWeight::Dim,
             Graphics::Italic => self.italic = true,
-            Graphics::Underline => self.underline = true,
+            Graphics::Underline(underline) => self.underline = underline,
             Graphics::ReverseVideo => self.reversed = true,
             Graphics::Strikethrough => self.strikethrough = true,
             Graphics::SetFont(_font) => {}
-            Graphics::NotUnderlined => self.underline = false,
+            Graphics::NormalIntensity => self.weight = FontWeight::Normal,
+            Graphics::NotItalic => self.italic = false,
+            Graphics::NotUnderlined => self.underline = Underline::None,
             Graphics::NotReversed => self.reversed = false,
+            Graphics::NotStrikethrough => self.strikethrough = false,
             Graphics::SetForeground(color) => self.foreground = color,
             Graphics::SetBackground(color) => self.background = color,
+            Graphics::Overline => self.overline = true,
+            Graphics::NotOverline => self.overline = false,
+            Graphics::SetUnderlineColor(color) => self.underline_color = Some(color),
+            Graphics::DefaultUnderlineColor => self.underline_color = None,
+            Graphics::Unsupported => {}
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sgr(parameters: &[&[u8]]) -> CellStyle {
+        let parameters: Vec<SgrParameter> = parameters
+            .iter()
+            .map(|parameter| SgrParameter::from_slice(parameter).unwrap())
+            .collect();
+        let mut style = CellStyle::default();
+        style.modify(&parameters);
+        style
+    }
+
+    #[test]
+    fn sub_parameters_combine_with_plain_ones() {
+        let style = sgr(&[&[1], &[4, 3], &[38, 2, 0, 10, 20, 30], &[48], &[5], &[17]]);
+        assert_eq!(style.weight, FontWeight::Bold);
+        assert_eq!(style.underline, Underline::Curly);
+        assert_eq!(style.foreground, TerminalColor::TwentyFourBit(10, 20, 30));
+        assert_eq!(style.background, TerminalColor::EightBit(17));
+
+        let style = sgr(&[&[4, 3], &[4, 0]]);
+        assert_eq!(style.underline, Underline::None);
+        let style = sgr(&[&[58, 2, 1, 2, 3], &[5]]);
+        assert_eq!(style.underline_color, Some(TerminalColor::TwentyFourBit(1, 2, 3)));
+    }
+}
@@ -2,7 +2,6 @@
 pub struct Cursor {
     pub col: usize,
     pub row: usize,
-    pub style: CursorStyle,
 }
 
 impl Cursor {
@@ -26,9 +25,12 @@ impl Cursor {
     pub fn down(&mut self, steps: usize, max: usize) {
         self.row = (self.row + steps).clamp(0, max);
     }
+}
 
-    pub fn set_style(&mut self, style: u8) {
-        self.style = match style {
+impl CursorStyle {
+    // The shape DECSCUSR asks for
+    pub fn from_ansi(style: u8) -> Self {
+        match style {
             0 | 1 => CursorStyle::BlinkingBlock,
             2 => CursorStyle::SteadyBlock,
             3 => CursorStyle::BlinkingUnderline,
@@ -39,7 +41,39 @@ impl Cursor {
                 println!("Unknown cursor style {}", style);
                 CursorStyle::default()
             }
-        };
+        }
+    }
+
+    // The DECSCUSR parameter that asks for this shape
+    pub fn to_ansi(self) -> u8 {
+        match self {
+            CursorStyle::BlinkingBlock => 1,
+            CursorStyle::SteadyBlock => 2,
+            CursorStyle::BlinkingUnderline => 3,
+            CursorStyle::SteadyUnderline => 4,
+            CursorStyle::BlinkingBar => 5,
+            CursorStyle::SteadyBar => 6,
+        }
+    }
+
+    pub fn is_blinking(&self) -> bool {
+        matches!(
+            self,
+            CursorStyle::BlinkingBlock | CursorStyle::BlinkingUnderline | CursorStyle::BlinkingBar
+        )
+    }
+
+    // Same shape, blinking or steady
+    pub fn with_blinking(self, blinking: bool) -> Self {
+        use CursorStyle::*;
+        match self {
+            BlinkingBlock | SteadyBlock if blinking => BlinkingBlock,
+            BlinkingBlock | SteadyBlock => SteadyBlock,
+            BlinkingUnderline | SteadyUnderline if blinking => BlinkingUnderline,
+            BlinkingUnderline | SteadyUnderline => SteadyUnderline,
+            BlinkingBar | SteadyBar if blinking => BlinkingBar,
+            BlinkingBar | SteadyBar => SteadyBar,
+        }
     }
 }
 
@@ -0,0 +1,121 @@
+use std::ops::Range;
+
+/// Changes made to a `Buffer` since the renderer last took them. Scrolls are kept in the
+/// order they happened, and dirty cells are always given in the coordinates of the buffer
+/// after every scroll has been applied. A renderer replays the scrolls on whatever it has
+/// cached, then redraws the dirty cells. The cursor is not tracked, so the renderer has to
+/// redraw the old and new cursor cell itself.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Damage {
+    cols: usize,
+    full: bool,
+    rows: Vec<Option<Range<usize>>>,
+    scrolls: Vec<Scroll>,
+}
+
+/// Rows `top..=bottom` moved `lines` rows up when positive, or down when negative
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Scroll {
+    pub top: usize,
+    pub bottom: usize,
+    pub lines: isize,
+}
+
+impl Damage {
+    pub fn new(rows: usize, cols: usize) -> Self {
+        Self {
+            cols,
+            full: false,
+            rows: vec![None; rows],
+            scrolls: Vec::new(),
+        }
+    }
+
+    pub fn full(rows: usize, cols: usize) -> Self {
+        Self {
+            full: true,
+            ..Self::new(rows, cols)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        !self.full && self.scrolls.is_empty() && self.rows.iter().all(Option::is_none)
+    }
+
+    // When true, nothing cached by the renderer can be reused
+    pub fn is_full(&self) -> bool {
+        self.full
+    }
+
+    pub fn scrolls(&self) -> &[Scroll] {
+        &self.scrolls
+    }
+
+    // Dirty column range of every row that needs to be redrawn
+    pub fn dirty_rows(&self) -> impl Iterator<Item = (usize, Range<usize>)> + '_ {
+        self.rows.iter().enumerate().filter_map(|(row, range)| {
+            if self.full {
+                Some((row, 0..self.cols))
+            } else {
+                range.clone().map(|range| (row, range))
+            }
+        })
+    }
+
+    pub fn mark(&mut self, row: usize, cols: Range<usize>) {
+        if cols.is_empty() {
+            return;
+        }
+        if let Some(range) = self.rows.get_mut(row) {
+            *range = match range.take() {
+                Some(existing) => Some(existing.start.min(cols.start)..existing.end.max(cols.end)),
+                None => Some(cols),
+            };
+        }
+    }
+
+    pub fn mark_row(&mut self, row: usize) {
+        self.mark(row, 0..self.cols);
+    }
+
+    pub fn mark_all(&mut self) {
+        self.full = true;
+    }
+
+    // Records that rows `top..=bottom` scrolled `lines` rows. Dirty marks inside the region
+    // move along with their rows, and the rows scrolled into view are marked dirty.
+    pub fn scroll(&mut self, top: usize, bottom: usize, lines: isize) {
+        if self.full || lines == 0 {
+            return;
+        }
+
+        let region = &mut self.rows[top..=bottom];
+        let exposed = lines.unsigned_abs().min(region.len());
+        if lines > 0 {
+            let len = region.len();
+            region.rotate_left(exposed);
+            region[len - exposed..].fill(None);
+        } else {
+            region.rotate_right(exposed);
+            region[..exposed].fill(None);
+        }
+
+        let exposed_rows = if lines > 0 {
+            (bottom + 1 - exposed)..=bottom
+        } else {
+            top..=(top + exposed - 1)
+        };
+        for row in exposed_rows {
+            self.mark_row(row);
+        }
+
+        // Consecutive scrolls of the same region, like when printing a long log, are
+        // merged so the list does not grow with every line
+        match self.scrolls.last_mut() {
+            Some(last) if last.top == top && last.bottom == bottom && last.lines.signum() == lines.signum() => {
+                last.lines += lines;
+            }
+            _ => self.scrolls.push(Scroll { top, bottom, lines }),
+        }
+    }
+}
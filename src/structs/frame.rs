@@ -0,0 +1,131 @@
+use std::io::{self, ErrorKind, Read, Write};
+
+use crate::{structs::terminalsize::TerminalSize, term::encoding::Encoding};
+
+// Frames larger than this are taken for garbage rather than allocated
+const MAX_FRAME: usize = 64 * 1024 * 1024;
+
+pub fn invalid(message: String) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message)
+}
+
+/// A message for a socket being put together: its length, a tag saying what it is, and the
+/// fields in order
+#[derive(Default)]
+pub struct Frame {
+    body: Vec<u8>,
+}
+
+impl Frame {
+    pub fn u8(&mut self, value: u8) {
+        self.body.push(value);
+    }
+
+    pub fn u32(&mut self, value: u32) {
+        self.body.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn u64(&mut self, value: u64) {
+        self.body.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn bytes(&mut self, bytes: &[u8]) {
+        self.u32(bytes.len() as u32);
+        self.body.extend_from_slice(bytes);
+    }
+
+    pub fn string(&mut self, string: &str) {
+        self.bytes(string.as_bytes());
+    }
+
+    pub fn strings(&mut self, strings: &[String]) {
+        self.u32(strings.len() as u32);
+        for string in strings {
+            self.string(string);
+        }
+    }
+
+    pub fn size(&mut self, size: &TerminalSize) {
+        for value in [size.cols, size.rows, size.cell_width, size.cell_height] {
+            self.u32(value as u32);
+        }
+    }
+
+    pub fn write_to(self, writer: &mut impl Write) -> io::Result<()> {
+        let mut frame = (self.body.len() as u32).to_le_bytes().to_vec();
+        frame.extend_from_slice(&self.body);
+        writer.write_all(&frame)?;
+        writer.flush()
+    }
+}
+
+/// A message read from a socket, its fields taken off the front one by one
+pub struct Payload {
+    body: Vec<u8>,
+    position: usize,
+}
+
+impl Payload {
+    pub fn read_from(reader: &mut impl Read) -> io::Result<Option<Self>> {
+        let mut length = [0; 4];
+        match reader.read_exact(&mut length) {
+            Ok(()) => {}
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return Ok(None),
+            Err(error) => return Err(error),
+        }
+        let length = u32::from_le_bytes(length) as usize;
+        if length > MAX_FRAME {
+            return Err(invalid(format!("frame of {} bytes", length)));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        Ok(Some(Self { body, position: 0 }))
+    }
+
+    pub fn take(&mut self, length: usize) -> io::Result<&[u8]> {
+        let end = self.position + length;
+        let bytes = self
+            .body
+            .get(self.position..end)
+            .ok_or_else(|| invalid("frame ends early".to_string()))?;
+        self.position = end;
+        Ok(bytes)
+    }
+
+    pub fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().expect("Took 4 bytes")))
+    }
+
+    pub fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64::from_le_bytes(self.take(8)?.try_into().expect("Took 8 bytes")))
+    }
+
+    pub fn bytes(&mut self) -> io::Result<Vec<u8>> {
+        let length = self.u32()? as usize;
+        Ok(self.take(length)?.to_vec())
+    }
+
+    pub fn string(&mut self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?).map_err(|error| invalid(error.to_string()))
+    }
+
+    pub fn strings(&mut self) -> io::Result<Vec<String>> {
+        (0..self.u32()?).map(|_| self.string()).collect()
+    }
+
+    pub fn encoding(&mut self) -> io::Result<Encoding> {
+        let name = self.string()?;
+        Encoding::from_name(&name).ok_or_else(|| invalid(format!("unknown encoding {}", name)))
+    }
+
+    pub fn size(&mut self) -> io::Result<TerminalSize> {
+        let cols = self.u32()? as usize;
+        let rows = self.u32()? as usize;
+        let (width, height) = (self.u32()? as usize, self.u32()? as usize);
+        Ok(TerminalSize::new(cols, rows).with_cell_size(width, height))
+    }
+}
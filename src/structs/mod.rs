@@ -1,4 +1,7 @@
 pub mod buffer;
 pub mod cell;
 pub mod cursor;
+pub mod damage;
+pub mod frame;
+pub mod selection;
 pub mod terminalsize;
@@ -0,0 +1,92 @@
+use std::ops::Range;
+
+use super::cell::Cell;
+
+/// Text selected with the mouse, from where the button went down to where the pointer is.
+/// Positions are a row of the view and a column boundary, 0 being left of the first cell, so
+/// dragging across half a cell selects it and a click selects nothing.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextSelection {
+    anchor: (usize, usize),
+    head: (usize, usize),
+}
+
+impl TextSelection {
+    pub fn new(row: usize, col: usize) -> Self {
+        Self {
+            anchor: (row, col),
+            head: (row, col),
+        }
+    }
+
+    pub fn extend(&mut self, row: usize, col: usize) {
+        self.head = (row, col);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.anchor == self.head
+    }
+
+    // Selected columns of `row` in a view `cols` wide, rows in between are selected in full
+    pub fn columns(&self, row: usize, cols: usize) -> Option<Range<usize>> {
+        let (start, end) = if self.anchor <= self.head {
+            (self.anchor, self.head)
+        } else {
+            (self.head, self.anchor)
+        };
+        if row < start.0 || row > end.0 {
+            return None;
+        }
+        let from = if row == start.0 { start.1 } else { 0 };
+        let to = if row == end.0 { end.1 } else { cols };
+        (from < to).then_some(from..to.min(cols))
+    }
+
+    // The selected characters, one line per row without trailing blanks
+    pub fn text<'a>(&self, rows: impl Iterator<Item = &'a [Cell]>) -> String {
+        let lines: Vec<String> = rows
+            .enumerate()
+            .filter_map(|(row, cells)| {
+                let columns = self.columns(row, cells.len())?;
+                let line: String = cells[columns]
+                    .iter()
+                    .filter(|cell| !cell.is_spacer())
+                    .map(|cell| cell.content)
+                    .collect();
+                Some(line.trim_end().to_string())
+            })
+            .collect();
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structs::cell::CellStyle;
+
+    fn row(text: &str) -> Vec<Cell> {
+        text.chars()
+            .map(|content| Cell {
+                content,
+                style: CellStyle::default(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn selections_span_rows_in_either_direction() {
+        let rows = [row("hello   "), row("big     "), row("world   ")];
+        let mut selection = TextSelection::new(2, 3);
+        selection.extend(0, 2);
+        assert_eq!(selection.columns(1, 8), Some(0..8));
+        assert_eq!(selection.text(rows.iter().map(Vec::as_slice)), "llo\nbig\nwor");
+    }
+
+    #[test]
+    fn clicks_select_nothing() {
+        let selection = TextSelection::new(0, 2);
+        assert!(selection.is_empty());
+        assert_eq!(selection.columns(0, 8), None);
+    }
+}
@@ -1,22 +1,50 @@
 use rustix_openpty::rustix::termios::Winsize;
 
-#[derive(Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct TerminalSize {
     pub cols: usize,
     pub rows: usize,
+    // Cell size in device pixels, 0 when unknown
+    pub cell_width: usize,
+    pub cell_height: usize,
 }
 
 impl TerminalSize {
     pub fn new(cols: usize, rows: usize) -> Self {
-        Self { cols, rows }
+        Self {
+            cols,
+            rows,
+            cell_width: 0,
+            cell_height: 0,
+        }
+    }
+
+    pub fn with_cell_size(mut self, width: usize, height: usize) -> Self {
+        self.cell_width = width;
+        self.cell_height = height;
+        self
     }
 
     pub fn winsize(&self) -> Winsize {
         Winsize {
             ws_col: u16::try_from(self.cols).expect("Terminal is too wide for Winsize"),
             ws_row: u16::try_from(self.rows).expect("Terminal is too tall for Winsize"),
-            ws_xpixel: 0,
-            ws_ypixel: 0,
+            // Programs drawing images size them with these, so report what is really on screen
+            ws_xpixel: u16::try_from(self.cols * self.cell_width).unwrap_or(u16::MAX),
+            ws_ypixel: u16::try_from(self.rows * self.cell_height).unwrap_or(u16::MAX),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn winsize_reports_pixels_of_the_whole_grid() {
+        let winsize = TerminalSize::new(80, 24).with_cell_size(9, 18).winsize();
+        assert_eq!((winsize.ws_col, winsize.ws_row), (80, 24));
+        assert_eq!((winsize.ws_xpixel, winsize.ws_ypixel), (720, 432));
+        assert_eq!(TerminalSize::new(80, 24).winsize().ws_xpixel, 0);
+    }
+}
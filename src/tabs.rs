@@ -0,0 +1,133 @@
+/// Tabs of a window in the order they are shown, one of them active. There is always at least
+/// one, closing the last tab is up to whoever owns them.
+pub struct Tabs<T> {
+    tabs: Vec<T>,
+    active: usize,
+}
+
+impl<T> Tabs<T> {
+    pub fn new(first: T) -> Self {
+        Self {
+            tabs: vec![first],
+            active: 0,
+        }
+    }
+
+    pub fn active(&self) -> &T {
+        &self.tabs[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut T {
+        &mut self.tabs[self.active]
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn len(&self) -> usize {
+        self.tabs.len()
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut T> {
+        self.tabs.get_mut(index)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &T> {
+        self.tabs.iter()
+    }
+
+    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> {
+        self.tabs.iter_mut()
+    }
+
+    // New tabs open next to the active one and become active
+    pub fn open(&mut self, tab: T) {
+        self.active += 1;
+        self.tabs.insert(self.active, tab);
+    }
+
+    // The last tab stays, there would be nothing to show without it
+    pub fn close(&mut self, index: usize) -> Option<T> {
+        if self.tabs.len() == 1 || index >= self.tabs.len() {
+            return None;
+        }
+        let tab = self.tabs.remove(index);
+        if index < self.active || self.active == self.tabs.len() {
+            self.active -= 1;
+        }
+        Some(tab)
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.tabs.len() {
+            self.active = index;
+        }
+    }
+
+    // Goes `offset` tabs to the right, or left for a negative `offset`, around the ends
+    pub fn cycle(&mut self, offset: isize) {
+        let len = self.tabs.len() as isize;
+        self.active = (self.active as isize + offset).rem_euclid(len) as usize;
+    }
+
+    // Moves the active tab `offset` places, stopping at the ends
+    pub fn move_active(&mut self, offset: isize) {
+        let last = self.tabs.len() as isize - 1;
+        let index = (self.active as isize + offset).clamp(0, last) as usize;
+        let tab = self.tabs.remove(self.active);
+        self.tabs.insert(index, tab);
+        self.active = index;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tabs(names: &[&'static str], active: usize) -> Tabs<&'static str> {
+        let mut tabs = Tabs::new(names[0]);
+        for name in &names[1..] {
+            tabs.open(name);
+        }
+        tabs.select(active);
+        tabs
+    }
+
+    fn names(tabs: &Tabs<&'static str>) -> Vec<&'static str> {
+        tabs.iter().copied().collect()
+    }
+
+    #[test]
+    fn new_tabs_open_after_the_active_one() {
+        let mut tabs = tabs(&["a", "b", "c"], 0);
+        tabs.open("d");
+        assert_eq!(names(&tabs), ["a", "d", "b", "c"]);
+        assert_eq!(*tabs.active(), "d");
+    }
+
+    #[test]
+    fn closing_keeps_a_neighbour_active() {
+        let mut tabs = tabs(&["a", "b", "c"], 2);
+        assert_eq!(tabs.close(2), Some("c"));
+        assert_eq!(*tabs.active(), "b");
+        assert_eq!(tabs.close(0), Some("a"));
+        assert_eq!(*tabs.active(), "b");
+        assert_eq!(tabs.close(0), None);
+    }
+
+    #[test]
+    fn cycling_wraps_and_moving_stops_at_the_ends() {
+        let mut tabs = tabs(&["a", "b", "c"], 0);
+        tabs.cycle(-1);
+        assert_eq!(*tabs.active(), "c");
+        tabs.cycle(1);
+        assert_eq!(*tabs.active(), "a");
+
+        tabs.move_active(1);
+        assert_eq!(names(&tabs), ["b", "a", "c"]);
+        tabs.move_active(5);
+        assert_eq!(names(&tabs), ["b", "c", "a"]);
+        assert_eq!(tabs.active_index(), 2);
+    }
+}
@@ -0,0 +1,168 @@
+mod pipe;
+mod pty;
+mod serial;
+
+use std::{
+    io::{self, ErrorKind, Write},
+    os::fd::{AsRawFd, OwnedFd},
+    process::Child,
+    sync::atomic::{AtomicBool, Ordering},
+    time::Duration,
+};
+
+use rustix_openpty::rustix::termios::Winsize;
+
+pub use serial::{FlowControl, Parity, SerialSettings};
+
+use super::term::Shell;
+use crate::structs::terminalsize::TerminalSize;
+
+/// What a session's terminal is connected to
+#[derive(Debug, Clone, Default, PartialEq)]
+pub enum BackendKind {
+    // The program runs on a PTY of its own, like in any terminal
+    #[default]
+    Pty,
+    // The program reads and writes plain pipes, it sees no terminal
+    Pipe,
+    // A serial device, like the console of a board, instead of a program
+    Serial(SerialSettings),
+}
+
+// Names as written in the config, the serial device's settings come from `[serial]`
+const NAMES: &[&str] = &["pty", "pipe", "serial"];
+
+// How often a write waiting for room checks whether the session ended
+const WRITE_CHECK_INTERVAL: Duration = Duration::from_millis(200);
+
+impl BackendKind {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "pty" => Some(BackendKind::Pty),
+            "pipe" => Some(BackendKind::Pipe),
+            "serial" => Some(BackendKind::Serial(SerialSettings::default())),
+            _ => None,
+        }
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        NAMES.iter().copied()
+    }
+}
+
+/// A source and sink of bytes for a terminal: what is read from `output` is shown, what is typed
+/// is written to it
+pub trait Backend: Send {
+    // Polled and read on a thread of its own, without blocking. Reads come back empty, or fail
+    // with EIO, once the other side is gone.
+    fn output(&self) -> io::Result<OwnedFd>;
+
+    // What is typed is written to it on a thread of its own, without blocking, so a program not
+    // reading or a line held up by flow control only holds up that thread
+    fn input(&self) -> io::Result<OwnedFd>;
+
+    // Only a PTY tells the program its size, the others have nothing to tell
+    fn resize(&mut self, size: TerminalSize) -> io::Result<()>;
+
+    // The program started for the terminal, waited for once its output ends. A device has none.
+    fn take_child(&mut self) -> Option<Child>;
+}
+
+// Starts the program, or opens the device, `shell` asks for
+pub fn open(shell: &Shell, winsize: Winsize) -> io::Result<Box<dyn Backend>> {
+    Ok(match &shell.backend {
+        BackendKind::Pty => Box::new(pty::Pty::start(shell, winsize)?),
+        BackendKind::Pipe => Box::new(pipe::Pipe::start(shell)?),
+        BackendKind::Serial(settings) => Box::new(serial::Serial::open(settings)?),
+    })
+}
+
+// Output is polled before it is read and input before it is written, neither must wait
+fn set_nonblocking(fd: &impl AsRawFd) {
+    unsafe {
+        let flags = libc::fcntl(fd.as_raw_fd(), libc::F_GETFL);
+        libc::fcntl(fd.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK);
+    }
+}
+
+// Writes all of `bytes` to a file set to O_NONBLOCK, waiting for room whenever the other side
+// has not read enough yet, like when a long paste outruns the shell. Gives up once `ended` is
+// set, a serial line held up by flow control may never have room again.
+pub fn write_all_nonblocking<W: Write + AsRawFd>(
+    writer: &mut W,
+    mut bytes: &[u8],
+    ended: &AtomicBool,
+) -> io::Result<()> {
+    while !bytes.is_empty() {
+        if ended.load(Ordering::Relaxed) {
+            return Err(ErrorKind::BrokenPipe.into());
+        }
+        match writer.write(bytes) {
+            Ok(0) => return Err(ErrorKind::WriteZero.into()),
+            Ok(written) => bytes = &bytes[written..],
+            Err(error) if error.kind() == ErrorKind::Interrupted => {}
+            Err(error) if error.kind() == ErrorKind::WouldBlock => wait_writable(writer)?,
+            Err(error) => return Err(error),
+        }
+    }
+    Ok(())
+}
+
+// Waits for room to write, but no longer than `WRITE_CHECK_INTERVAL` so `ended` is seen
+fn wait_writable(fd: &impl AsRawFd) -> io::Result<()> {
+    let mut poll_fd = libc::pollfd {
+        fd: fd.as_raw_fd(),
+        events: libc::POLLOUT,
+        revents: 0,
+    };
+    let timeout = WRITE_CHECK_INTERVAL.as_millis() as libc::c_int;
+    if unsafe { libc::poll(&mut poll_fd, 1, timeout) } < 0 {
+        let error = io::Error::last_os_error();
+        if error.kind() != ErrorKind::Interrupted {
+            return Err(error);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs::File, io::Read, os::fd::FromRawFd, sync::Arc, thread};
+
+    use super::*;
+
+    #[test]
+    fn nonblocking_writes_wait_for_the_reader() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let (mut reader, mut writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        set_nonblocking(&writer);
+        // Far more than a pipe holds
+        let bytes = vec![b'x'; 1 << 20];
+        let reading = thread::spawn(move || {
+            let mut read = Vec::new();
+            reader.read_to_end(&mut read).unwrap();
+            read.len()
+        });
+        write_all_nonblocking(&mut writer, &bytes, &AtomicBool::new(false)).unwrap();
+        drop(writer);
+        assert_eq!(reading.join().unwrap(), bytes.len());
+    }
+
+    #[test]
+    fn writes_waiting_for_room_give_up_once_the_session_ended() {
+        let mut fds = [0; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        let (_reader, mut writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+        set_nonblocking(&writer);
+        let ended = Arc::new(AtomicBool::new(false));
+        let ending = ended.clone();
+        thread::spawn(move || {
+            thread::sleep(Duration::from_millis(50));
+            ending.store(true, Ordering::Relaxed);
+        });
+        // Nothing reads, so the pipe fills up and never has room again
+        let error = write_all_nonblocking(&mut writer, &vec![b'x'; 1 << 20], &ended).unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::BrokenPipe);
+    }
+}
@@ -0,0 +1,73 @@
+use std::{
+    io,
+    os::fd::{AsFd, FromRawFd, OwnedFd},
+    process::{Child, Command, Stdio},
+};
+
+use super::{set_nonblocking, Backend};
+use crate::{control, structs::terminalsize::TerminalSize, term::term::Shell};
+
+/// A program on plain pipes, its output and errors coming through the same one. It sees no
+/// terminal, so it gets no size and no signals from typed controls.
+pub struct Pipe {
+    input: OwnedFd,
+    output: OwnedFd,
+    child: Option<Child>,
+}
+
+impl Pipe {
+    pub fn start(shell: &Shell) -> io::Result<Self> {
+        let (output, writer) = pipe()?;
+        let mut command = Command::new(&shell.program);
+        command.args(&shell.args);
+        if let Some(directory) = &shell.working_directory {
+            command.current_dir(directory);
+        }
+        command.env("TERM", "dumb");
+        if let Some(socket) = &shell.control_socket {
+            command.env(control::SOCKET_VARIABLE, socket);
+        }
+        command.stdin(Stdio::piped());
+        command.stdout(writer.try_clone()?);
+        command.stderr(writer);
+        let mut child = command.spawn()?;
+        // Our copies of the write end are gone with the command, reads end with the program's
+        drop(command);
+
+        let input = OwnedFd::from(child.stdin.take().expect("Stdin was piped"));
+        set_nonblocking(&input);
+        set_nonblocking(&output);
+        Ok(Self {
+            input,
+            output,
+            child: Some(child),
+        })
+    }
+}
+
+// Both ends are closed in the programs we start, but for the copies given to them
+fn pipe() -> io::Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) })
+}
+
+impl Backend for Pipe {
+    fn output(&self) -> io::Result<OwnedFd> {
+        self.output.as_fd().try_clone_to_owned()
+    }
+
+    fn input(&self) -> io::Result<OwnedFd> {
+        self.input.try_clone()
+    }
+
+    fn resize(&mut self, _size: TerminalSize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn take_child(&mut self) -> Option<Child> {
+        self.child.take()
+    }
+}
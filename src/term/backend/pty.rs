@@ -0,0 +1,105 @@
+use std::{
+    fs::File,
+    io::{self, Error},
+    os::{
+        fd::{AsFd, AsRawFd, OwnedFd},
+        unix::process::CommandExt,
+    },
+    process::{Child, Command},
+};
+
+use libc::{winsize, TIOCSCTTY, TIOCSWINSZ};
+use rustix_openpty::rustix::termios::Winsize;
+
+use super::{set_nonblocking, Backend};
+use crate::{control, structs::terminalsize::TerminalSize, term::term::Shell};
+
+/// A shell on a PTY of its own. Only the master side is kept: with our copy of the slave closed,
+/// reads fail once the shell and everything it started are gone.
+pub struct Pty {
+    master: File,
+    child: Option<Child>,
+}
+
+impl Pty {
+    pub fn start(shell: &Shell, winsize: Winsize) -> io::Result<Self> {
+        // Ask OS for a PTY
+        let pty = rustix_openpty::openpty(None, Some(&winsize))?;
+        set_nonblocking(&pty.controller);
+        let child = spawn_shell(&pty.user, shell)?;
+        Ok(Self {
+            master: File::from(pty.controller),
+            child: Some(child),
+        })
+    }
+}
+
+impl Backend for Pty {
+    fn output(&self) -> io::Result<OwnedFd> {
+        self.master.as_fd().try_clone_to_owned()
+    }
+
+    // The master is non-blocking already, for the reader
+    fn input(&self) -> io::Result<OwnedFd> {
+        self.master.as_fd().try_clone_to_owned()
+    }
+
+    fn resize(&mut self, size: TerminalSize) -> io::Result<()> {
+        let requested = size.winsize();
+        let size = winsize {
+            ws_row: requested.ws_row,
+            ws_col: requested.ws_col,
+            ws_xpixel: requested.ws_xpixel,
+            ws_ypixel: requested.ws_ypixel,
+        };
+        let result = unsafe { libc::ioctl(self.master.as_raw_fd(), TIOCSWINSZ, &size) };
+
+        if result == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    }
+
+    fn take_child(&mut self) -> Option<Child> {
+        self.child.take()
+    }
+}
+
+fn spawn_shell(slave: &OwnedFd, shell: &Shell) -> io::Result<Child> {
+    let mut command = Command::new(&shell.program);
+    command.args(&shell.args);
+    if let Some(directory) = &shell.working_directory {
+        command.current_dir(directory);
+    }
+    command.env("TERM", "xterm-256color");
+    if let Some(socket) = &shell.control_socket {
+        command.env(control::SOCKET_VARIABLE, socket);
+    }
+    command.stdin(slave.try_clone()?);
+    command.stdout(slave.try_clone()?);
+    command.stderr(slave.try_clone()?);
+
+    let slave_fd = slave.as_raw_fd();
+    unsafe {
+        command.pre_exec(move || {
+            // Become leader of new session
+            let err = libc::setsid();
+            if err == -1 {
+                return Err(Error::new(io::ErrorKind::Other, "Failed to set session ID"));
+            }
+
+            // Set controlling terminal
+            let res = libc::ioctl(slave_fd, TIOCSCTTY as _, 0);
+            if res < 0 {
+                return Err(Error::new(io::ErrorKind::Other, "Failed to set controlling terminal"));
+            }
+
+            libc::close(slave_fd);
+
+            Ok(())
+        });
+    }
+
+    command.spawn()
+}
@@ -0,0 +1,324 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, ErrorKind},
+    os::{
+        fd::{AsFd, AsRawFd, OwnedFd},
+        unix::fs::OpenOptionsExt,
+    },
+    path::PathBuf,
+    process::Child,
+    sync::Mutex,
+};
+
+use super::{set_nonblocking, Backend};
+use crate::structs::terminalsize::TerminalSize;
+
+// Opened when no device is configured, the usual USB serial adapter
+const DEFAULT_DEVICE: &str = "/dev/ttyUSB0";
+
+// Devices a session has open. Two sessions on one device would each get part of what it sends.
+static OPEN_DEVICES: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// How to talk to a serial device, 115200 baud 8N1 without flow control unless set otherwise
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerialSettings {
+    pub device: PathBuf,
+    pub baud: u32,
+    pub parity: Parity,
+    // 5 to 8
+    pub data_bits: u8,
+    // 1 or 2
+    pub stop_bits: u8,
+    pub flow_control: FlowControl,
+}
+
+impl Default for SerialSettings {
+    fn default() -> Self {
+        Self {
+            device: PathBuf::from(DEFAULT_DEVICE),
+            baud: 115200,
+            parity: Parity::None,
+            data_bits: 8,
+            stop_bits: 1,
+            flow_control: FlowControl::None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Parity {
+    None,
+    Even,
+    Odd,
+}
+
+impl Parity {
+    const NAMES: &[(&str, Parity)] = &[("none", Parity::None), ("even", Parity::Even), ("odd", Parity::Odd)];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        from_name(Self::NAMES, name)
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        Self::NAMES.iter().map(|(name, _)| *name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FlowControl {
+    None,
+    // RTS and CTS
+    Hardware,
+    // XON and XOFF
+    Software,
+}
+
+impl FlowControl {
+    const NAMES: &[(&str, FlowControl)] = &[
+        ("none", FlowControl::None),
+        ("hardware", FlowControl::Hardware),
+        ("software", FlowControl::Software),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        from_name(Self::NAMES, name)
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        Self::NAMES.iter().map(|(name, _)| *name)
+    }
+}
+
+fn from_name<T: Copy>(names: &[(&str, T)], name: &str) -> Option<T> {
+    let name = name.trim().to_ascii_lowercase();
+    names.iter().find(|(known, _)| *known == name).map(|(_, value)| *value)
+}
+
+/// A serial device in raw mode, what comes in is shown as is and what is typed goes out as is
+pub struct Serial {
+    // Opened twice, so the writer can wait for room on its own
+    reader: File,
+    writer: File,
+    // Last, so the device is closed before another session may open it
+    _claim: DeviceClaim,
+}
+
+impl Serial {
+    // Fails if another session has the device open already
+    pub fn open(settings: &SerialSettings) -> io::Result<Self> {
+        let claim = DeviceClaim::new(&settings.device)?;
+        // Without O_NONBLOCK opening waits for the carrier, until CLOCAL says not to
+        let reader = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_NOCTTY | libc::O_NONBLOCK)
+            .open(&settings.device)?;
+        configure(&reader, settings)?;
+        let writer = OpenOptions::new()
+            .write(true)
+            .custom_flags(libc::O_NOCTTY)
+            .open(&settings.device)?;
+        set_nonblocking(&writer);
+        Ok(Self {
+            reader,
+            writer,
+            _claim: claim,
+        })
+    }
+}
+
+/// A device taken for one session, given back when dropped
+struct DeviceClaim(PathBuf);
+
+impl DeviceClaim {
+    fn new(device: &PathBuf) -> io::Result<Self> {
+        // The same device may be named through a link, like /dev/serial/by-id
+        let device = fs::canonicalize(device).unwrap_or_else(|_| device.clone());
+        let mut open = OPEN_DEVICES.lock().unwrap();
+        if open.contains(&device) {
+            return Err(io::Error::new(
+                ErrorKind::AddrInUse,
+                format!("{} is open in another session", device.display()),
+            ));
+        }
+        open.push(device.clone());
+        Ok(Self(device))
+    }
+}
+
+impl Drop for DeviceClaim {
+    fn drop(&mut self) {
+        OPEN_DEVICES.lock().unwrap().retain(|device| *device != self.0);
+    }
+}
+
+impl Backend for Serial {
+    fn output(&self) -> io::Result<OwnedFd> {
+        self.reader.as_fd().try_clone_to_owned()
+    }
+
+    fn input(&self) -> io::Result<OwnedFd> {
+        self.writer.as_fd().try_clone_to_owned()
+    }
+
+    fn resize(&mut self, _size: TerminalSize) -> io::Result<()> {
+        Ok(())
+    }
+
+    fn take_child(&mut self) -> Option<Child> {
+        None
+    }
+}
+
+// Raw mode with the line settings, replacing whatever the device was left with
+fn configure(device: &File, settings: &SerialSettings) -> io::Result<()> {
+    let fd = device.as_raw_fd();
+    let speed = baud_rate(settings.baud).ok_or_else(|| {
+        io::Error::new(
+            ErrorKind::InvalidInput,
+            format!("{} baud is not supported", settings.baud),
+        )
+    })?;
+    let mut termios = unsafe { std::mem::zeroed::<libc::termios>() };
+    if unsafe { libc::tcgetattr(fd, &mut termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    unsafe {
+        libc::cfmakeraw(&mut termios);
+        libc::cfsetispeed(&mut termios, speed);
+        libc::cfsetospeed(&mut termios, speed);
+    }
+
+    termios.c_cflag |= libc::CLOCAL | libc::CREAD;
+    termios.c_cflag &= !libc::CSIZE;
+    termios.c_cflag |= match settings.data_bits {
+        5 => libc::CS5,
+        6 => libc::CS6,
+        7 => libc::CS7,
+        _ => libc::CS8,
+    };
+    match settings.parity {
+        Parity::None => termios.c_cflag &= !(libc::PARENB | libc::PARODD),
+        Parity::Even => {
+            termios.c_cflag = (termios.c_cflag | libc::PARENB) & !libc::PARODD;
+            termios.c_iflag |= libc::INPCK;
+        }
+        Parity::Odd => {
+            termios.c_cflag |= libc::PARENB | libc::PARODD;
+            termios.c_iflag |= libc::INPCK;
+        }
+    }
+    match settings.stop_bits {
+        2 => termios.c_cflag |= libc::CSTOPB,
+        _ => termios.c_cflag &= !libc::CSTOPB,
+    }
+    termios.c_cflag &= !libc::CRTSCTS;
+    termios.c_iflag &= !(libc::IXON | libc::IXOFF);
+    match settings.flow_control {
+        FlowControl::None => {}
+        FlowControl::Hardware => termios.c_cflag |= libc::CRTSCTS,
+        FlowControl::Software => termios.c_iflag |= libc::IXON | libc::IXOFF,
+    }
+
+    if unsafe { libc::tcsetattr(fd, libc::TCSANOW, &termios) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+fn baud_rate(baud: u32) -> Option<libc::speed_t> {
+    let speed = match baud {
+        1200 => libc::B1200,
+        2400 => libc::B2400,
+        4800 => libc::B4800,
+        9600 => libc::B9600,
+        19200 => libc::B19200,
+        38400 => libc::B38400,
+        57600 => libc::B57600,
+        115200 => libc::B115200,
+        230400 => libc::B230400,
+        460800 => libc::B460800,
+        500000 => libc::B500000,
+        921600 => libc::B921600,
+        1000000 => libc::B1000000,
+        1500000 => libc::B1500000,
+        2000000 => libc::B2000000,
+        3000000 => libc::B3000000,
+        4000000 => libc::B4000000,
+        _ => return None,
+    };
+    Some(speed)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        fs,
+        io::{Read, Write},
+        sync::atomic::AtomicBool,
+        thread,
+        time::Duration,
+    };
+
+    use super::*;
+    use crate::term::backend::write_all_nonblocking;
+
+    // A PTY stands in for the device like a socat pair would, the master is the board's end.
+    // The slave is opened again by its path, the first one only keeps it around.
+    fn device() -> (File, OwnedFd, PathBuf) {
+        let pty = rustix_openpty::openpty(None, None).unwrap();
+        let path = fs::read_link(format!("/proc/self/fd/{}", pty.user.as_raw_fd())).unwrap();
+        (File::from(pty.controller), pty.user, path)
+    }
+
+    #[test]
+    fn bytes_pass_through_unchanged() {
+        let (mut board, _slave, path) = device();
+        let settings = SerialSettings {
+            device: path,
+            baud: 9600,
+            parity: Parity::Even,
+            ..SerialSettings::default()
+        };
+        let serial = Serial::open(&settings).unwrap();
+
+        // Raw mode, nothing echoed back or turned into signals
+        let mut input = File::from(serial.input().unwrap());
+        write_all_nonblocking(&mut input, b"ls\r\x03", &AtomicBool::new(false)).unwrap();
+        let mut typed = [0; 4];
+        board.read_exact(&mut typed).unwrap();
+        assert_eq!(&typed, b"ls\r\x03");
+
+        board.write_all(b"U-Boot\n").unwrap();
+        thread::sleep(Duration::from_millis(50));
+        let mut output = File::from(serial.output().unwrap());
+        let mut shown = [0; 7];
+        output.read_exact(&mut shown).unwrap();
+        assert_eq!(&shown, b"U-Boot\n");
+    }
+
+    #[test]
+    fn a_device_is_open_in_one_session_at_a_time() {
+        let (_board, _slave, path) = device();
+        let settings = SerialSettings {
+            device: path,
+            ..SerialSettings::default()
+        };
+        let serial = Serial::open(&settings).unwrap();
+        let error = Serial::open(&settings).err().unwrap();
+        assert_eq!(error.kind(), ErrorKind::AddrInUse);
+        drop(serial);
+        assert!(Serial::open(&settings).is_ok());
+    }
+
+    #[test]
+    fn unknown_baud_rates_are_refused() {
+        let (_board, _slave, path) = device();
+        let settings = SerialSettings {
+            device: path,
+            baud: 12345,
+            ..SerialSettings::default()
+        };
+        assert!(Serial::open(&settings).is_err());
+    }
+}
@@ -0,0 +1,275 @@
+use std::fmt;
+
+/// How the program in the PTY writes text, and how we write what is typed to it. Anything but
+/// UTF-8 is one byte per character: ASCII, then 128 characters from a table.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    // ISO-8859-1, western European
+    Latin1,
+    // ISO-8859-2, central European
+    Latin2,
+    // ISO-8859-5
+    Cyrillic,
+    // ISO-8859-7
+    Greek,
+    // ISO-8859-9
+    Turkish,
+    // ISO-8859-15, Latin-1 with the euro sign
+    Latin9,
+    // The IBM PC's code page, with the box drawing characters DOS programs draw with
+    Cp437,
+}
+
+// Names the encodings go by, the first one is how we write them
+const NAMES: &[(Encoding, &[&str])] = &[
+    (Encoding::Utf8, &["utf-8", "utf8"]),
+    (Encoding::Latin1, &["iso-8859-1", "latin1", "latin-1"]),
+    (Encoding::Latin2, &["iso-8859-2", "latin2", "latin-2"]),
+    (Encoding::Cyrillic, &["iso-8859-5"]),
+    (Encoding::Greek, &["iso-8859-7"]),
+    (Encoding::Turkish, &["iso-8859-9", "latin5", "latin-5"]),
+    (Encoding::Latin9, &["iso-8859-15", "latin9", "latin-9"]),
+    (Encoding::Cp437, &["cp437", "ibm437"]),
+];
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (_, names) = NAMES
+            .iter()
+            .find(|(encoding, _)| encoding == self)
+            .expect("Every encoding has a name");
+        formatter.write_str(names[0])
+    }
+}
+
+impl Encoding {
+    // Case, `-` and `_` don't matter, `ISO_8859_1` is `iso-8859-1`
+    pub fn from_name(name: &str) -> Option<Self> {
+        let normalize = |name: &str| name.trim().to_ascii_lowercase().replace(['-', '_'], "");
+        let name = normalize(name);
+        NAMES
+            .iter()
+            .find(|(_, names)| names.iter().any(|known| normalize(known) == name))
+            .map(|(encoding, _)| *encoding)
+    }
+
+    // The encoding of a locale like `en_US.ISO-8859-15@euro`, or of the encoding's name alone
+    pub fn from_locale(locale: &str) -> Option<Self> {
+        let locale = locale.split('@').next().unwrap_or(locale);
+        Self::from_name(locale.rsplit('.').next().unwrap_or(locale))
+    }
+
+    pub fn names() -> impl Iterator<Item = &'static str> {
+        NAMES.iter().map(|(_, names)| names[0])
+    }
+
+    // The characters for bytes 0x80 to 0xff. Latin-1 has them in the same order as Unicode.
+    fn high_half(self) -> Option<&'static [char; 128]> {
+        match self {
+            Encoding::Utf8 | Encoding::Latin1 => None,
+            Encoding::Latin2 => Some(&LATIN2),
+            Encoding::Cyrillic => Some(&CYRILLIC),
+            Encoding::Greek => Some(&GREEK),
+            Encoding::Turkish => Some(&TURKISH),
+            Encoding::Latin9 => Some(&LATIN9),
+            Encoding::Cp437 => Some(&CP437),
+        }
+    }
+
+    // Text from a single byte encoding, UTF-8 is decoded by the PTY reader as it comes
+    pub fn decode(self, bytes: &[u8]) -> String {
+        match (self, self.high_half()) {
+            (Encoding::Utf8, _) => String::from_utf8_lossy(bytes).into_owned(),
+            (_, None) => bytes.iter().map(|byte| char::from(*byte)).collect(),
+            (_, Some(table)) => bytes
+                .iter()
+                .map(|byte| match byte {
+                    0x00..=0x7f => char::from(*byte),
+                    _ => table[(byte - 0x80) as usize],
+                })
+                .collect(),
+        }
+    }
+
+    // Turns UTF-8 from us into this encoding. Bytes that are not UTF-8, like 8-bit controls, are
+    // passed on as they are.
+    pub fn transcode(self, bytes: &[u8]) -> Vec<u8> {
+        if self == Encoding::Utf8 {
+            return bytes.to_vec();
+        }
+        let mut transcoded = Vec::with_capacity(bytes.len());
+        for chunk in bytes.utf8_chunks() {
+            transcoded.extend(self.encode(chunk.valid()));
+            transcoded.extend_from_slice(chunk.invalid());
+        }
+        transcoded
+    }
+
+    // Characters the encoding has no byte for are sent as `?`
+    pub fn encode(self, text: &str) -> Vec<u8> {
+        match (self, self.high_half()) {
+            (Encoding::Utf8, _) => text.as_bytes().to_vec(),
+            (_, None) => text.chars().map(|c| u8::try_from(c).unwrap_or(b'?')).collect(),
+            (_, Some(table)) => text
+                .chars()
+                .map(|c| match c {
+                    '\0'..='\x7f' => c as u8,
+                    _ => table
+                        .iter()
+                        .position(|known| *known == c)
+                        .map_or(b'?', |index| index as u8 + 0x80),
+                })
+                .collect(),
+        }
+    }
+}
+
+const LATIN2: [char; 128] = [
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{0085}', '\u{0086}', '\u{0087}', '\u{0088}',
+    '\u{0089}', '\u{008a}', '\u{008b}', '\u{008c}', '\u{008d}', '\u{008e}', '\u{008f}', '\u{0090}', '\u{0091}',
+    '\u{0092}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0097}', '\u{0098}', '\u{0099}', '\u{009a}',
+    '\u{009b}', '\u{009c}', '\u{009d}', '\u{009e}', '\u{009f}', '\u{00a0}', '\u{0104}', '\u{02d8}', '\u{0141}',
+    '\u{00a4}', '\u{013d}', '\u{015a}', '\u{00a7}', '\u{00a8}', '\u{0160}', '\u{015e}', '\u{0164}', '\u{0179}',
+    '\u{00ad}', '\u{017d}', '\u{017b}', '\u{00b0}', '\u{0105}', '\u{02db}', '\u{0142}', '\u{00b4}', '\u{013e}',
+    '\u{015b}', '\u{02c7}', '\u{00b8}', '\u{0161}', '\u{015f}', '\u{0165}', '\u{017a}', '\u{02dd}', '\u{017e}',
+    '\u{017c}', '\u{0154}', '\u{00c1}', '\u{00c2}', '\u{0102}', '\u{00c4}', '\u{0139}', '\u{0106}', '\u{00c7}',
+    '\u{010c}', '\u{00c9}', '\u{0118}', '\u{00cb}', '\u{011a}', '\u{00cd}', '\u{00ce}', '\u{010e}', '\u{0110}',
+    '\u{0143}', '\u{0147}', '\u{00d3}', '\u{00d4}', '\u{0150}', '\u{00d6}', '\u{00d7}', '\u{0158}', '\u{016e}',
+    '\u{00da}', '\u{0170}', '\u{00dc}', '\u{00dd}', '\u{0162}', '\u{00df}', '\u{0155}', '\u{00e1}', '\u{00e2}',
+    '\u{0103}', '\u{00e4}', '\u{013a}', '\u{0107}', '\u{00e7}', '\u{010d}', '\u{00e9}', '\u{0119}', '\u{00eb}',
+    '\u{011b}', '\u{00ed}', '\u{00ee}', '\u{010f}', '\u{0111}', '\u{0144}', '\u{0148}', '\u{00f3}', '\u{00f4}',
+    '\u{0151}', '\u{00f6}', '\u{00f7}', '\u{0159}', '\u{016f}', '\u{00fa}', '\u{0171}', '\u{00fc}', '\u{00fd}',
+    '\u{0163}', '\u{02d9}',
+];
+
+const CYRILLIC: [char; 128] = [
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{0085}', '\u{0086}', '\u{0087}', '\u{0088}',
+    '\u{0089}', '\u{008a}', '\u{008b}', '\u{008c}', '\u{008d}', '\u{008e}', '\u{008f}', '\u{0090}', '\u{0091}',
+    '\u{0092}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0097}', '\u{0098}', '\u{0099}', '\u{009a}',
+    '\u{009b}', '\u{009c}', '\u{009d}', '\u{009e}', '\u{009f}', '\u{00a0}', '\u{0401}', '\u{0402}', '\u{0403}',
+    '\u{0404}', '\u{0405}', '\u{0406}', '\u{0407}', '\u{0408}', '\u{0409}', '\u{040a}', '\u{040b}', '\u{040c}',
+    '\u{00ad}', '\u{040e}', '\u{040f}', '\u{0410}', '\u{0411}', '\u{0412}', '\u{0413}', '\u{0414}', '\u{0415}',
+    '\u{0416}', '\u{0417}', '\u{0418}', '\u{0419}', '\u{041a}', '\u{041b}', '\u{041c}', '\u{041d}', '\u{041e}',
+    '\u{041f}', '\u{0420}', '\u{0421}', '\u{0422}', '\u{0423}', '\u{0424}', '\u{0425}', '\u{0426}', '\u{0427}',
+    '\u{0428}', '\u{0429}', '\u{042a}', '\u{042b}', '\u{042c}', '\u{042d}', '\u{042e}', '\u{042f}', '\u{0430}',
+    '\u{0431}', '\u{0432}', '\u{0433}', '\u{0434}', '\u{0435}', '\u{0436}', '\u{0437}', '\u{0438}', '\u{0439}',
+    '\u{043a}', '\u{043b}', '\u{043c}', '\u{043d}', '\u{043e}', '\u{043f}', '\u{0440}', '\u{0441}', '\u{0442}',
+    '\u{0443}', '\u{0444}', '\u{0445}', '\u{0446}', '\u{0447}', '\u{0448}', '\u{0449}', '\u{044a}', '\u{044b}',
+    '\u{044c}', '\u{044d}', '\u{044e}', '\u{044f}', '\u{2116}', '\u{0451}', '\u{0452}', '\u{0453}', '\u{0454}',
+    '\u{0455}', '\u{0456}', '\u{0457}', '\u{0458}', '\u{0459}', '\u{045a}', '\u{045b}', '\u{045c}', '\u{00a7}',
+    '\u{045e}', '\u{045f}',
+];
+
+const GREEK: [char; 128] = [
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{0085}', '\u{0086}', '\u{0087}', '\u{0088}',
+    '\u{0089}', '\u{008a}', '\u{008b}', '\u{008c}', '\u{008d}', '\u{008e}', '\u{008f}', '\u{0090}', '\u{0091}',
+    '\u{0092}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0097}', '\u{0098}', '\u{0099}', '\u{009a}',
+    '\u{009b}', '\u{009c}', '\u{009d}', '\u{009e}', '\u{009f}', '\u{00a0}', '\u{2018}', '\u{2019}', '\u{00a3}',
+    '\u{20ac}', '\u{20af}', '\u{00a6}', '\u{00a7}', '\u{00a8}', '\u{00a9}', '\u{037a}', '\u{00ab}', '\u{00ac}',
+    '\u{00ad}', '\u{fffd}', '\u{2015}', '\u{00b0}', '\u{00b1}', '\u{00b2}', '\u{00b3}', '\u{0384}', '\u{0385}',
+    '\u{0386}', '\u{00b7}', '\u{0388}', '\u{0389}', '\u{038a}', '\u{00bb}', '\u{038c}', '\u{00bd}', '\u{038e}',
+    '\u{038f}', '\u{0390}', '\u{0391}', '\u{0392}', '\u{0393}', '\u{0394}', '\u{0395}', '\u{0396}', '\u{0397}',
+    '\u{0398}', '\u{0399}', '\u{039a}', '\u{039b}', '\u{039c}', '\u{039d}', '\u{039e}', '\u{039f}', '\u{03a0}',
+    '\u{03a1}', '\u{fffd}', '\u{03a3}', '\u{03a4}', '\u{03a5}', '\u{03a6}', '\u{03a7}', '\u{03a8}', '\u{03a9}',
+    '\u{03aa}', '\u{03ab}', '\u{03ac}', '\u{03ad}', '\u{03ae}', '\u{03af}', '\u{03b0}', '\u{03b1}', '\u{03b2}',
+    '\u{03b3}', '\u{03b4}', '\u{03b5}', '\u{03b6}', '\u{03b7}', '\u{03b8}', '\u{03b9}', '\u{03ba}', '\u{03bb}',
+    '\u{03bc}', '\u{03bd}', '\u{03be}', '\u{03bf}', '\u{03c0}', '\u{03c1}', '\u{03c2}', '\u{03c3}', '\u{03c4}',
+    '\u{03c5}', '\u{03c6}', '\u{03c7}', '\u{03c8}', '\u{03c9}', '\u{03ca}', '\u{03cb}', '\u{03cc}', '\u{03cd}',
+    '\u{03ce}', '\u{fffd}',
+];
+
+const TURKISH: [char; 128] = [
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{0085}', '\u{0086}', '\u{0087}', '\u{0088}',
+    '\u{0089}', '\u{008a}', '\u{008b}', '\u{008c}', '\u{008d}', '\u{008e}', '\u{008f}', '\u{0090}', '\u{0091}',
+    '\u{0092}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0097}', '\u{0098}', '\u{0099}', '\u{009a}',
+    '\u{009b}', '\u{009c}', '\u{009d}', '\u{009e}', '\u{009f}', '\u{00a0}', '\u{00a1}', '\u{00a2}', '\u{00a3}',
+    '\u{00a4}', '\u{00a5}', '\u{00a6}', '\u{00a7}', '\u{00a8}', '\u{00a9}', '\u{00aa}', '\u{00ab}', '\u{00ac}',
+    '\u{00ad}', '\u{00ae}', '\u{00af}', '\u{00b0}', '\u{00b1}', '\u{00b2}', '\u{00b3}', '\u{00b4}', '\u{00b5}',
+    '\u{00b6}', '\u{00b7}', '\u{00b8}', '\u{00b9}', '\u{00ba}', '\u{00bb}', '\u{00bc}', '\u{00bd}', '\u{00be}',
+    '\u{00bf}', '\u{00c0}', '\u{00c1}', '\u{00c2}', '\u{00c3}', '\u{00c4}', '\u{00c5}', '\u{00c6}', '\u{00c7}',
+    '\u{00c8}', '\u{00c9}', '\u{00ca}', '\u{00cb}', '\u{00cc}', '\u{00cd}', '\u{00ce}', '\u{00cf}', '\u{011e}',
+    '\u{00d1}', '\u{00d2}', '\u{00d3}', '\u{00d4}', '\u{00d5}', '\u{00d6}', '\u{00d7}', '\u{00d8}', '\u{00d9}',
+    '\u{00da}', '\u{00db}', '\u{00dc}', '\u{0130}', '\u{015e}', '\u{00df}', '\u{00e0}', '\u{00e1}', '\u{00e2}',
+    '\u{00e3}', '\u{00e4}', '\u{00e5}', '\u{00e6}', '\u{00e7}', '\u{00e8}', '\u{00e9}', '\u{00ea}', '\u{00eb}',
+    '\u{00ec}', '\u{00ed}', '\u{00ee}', '\u{00ef}', '\u{011f}', '\u{00f1}', '\u{00f2}', '\u{00f3}', '\u{00f4}',
+    '\u{00f5}', '\u{00f6}', '\u{00f7}', '\u{00f8}', '\u{00f9}', '\u{00fa}', '\u{00fb}', '\u{00fc}', '\u{0131}',
+    '\u{015f}', '\u{00ff}',
+];
+
+const LATIN9: [char; 128] = [
+    '\u{0080}', '\u{0081}', '\u{0082}', '\u{0083}', '\u{0084}', '\u{0085}', '\u{0086}', '\u{0087}', '\u{0088}',
+    '\u{0089}', '\u{008a}', '\u{008b}', '\u{008c}', '\u{008d}', '\u{008e}', '\u{008f}', '\u{0090}', '\u{0091}',
+    '\u{0092}', '\u{0093}', '\u{0094}', '\u{0095}', '\u{0096}', '\u{0097}', '\u{0098}', '\u{0099}', '\u{009a}',
+    '\u{009b}', '\u{009c}', '\u{009d}', '\u{009e}', '\u{009f}', '\u{00a0}', '\u{00a1}', '\u{00a2}', '\u{00a3}',
+    '\u{20ac}', '\u{00a5}', '\u{0160}', '\u{00a7}', '\u{0161}', '\u{00a9}', '\u{00aa}', '\u{00ab}', '\u{00ac}',
+    '\u{00ad}', '\u{00ae}', '\u{00af}', '\u{00b0}', '\u{00b1}', '\u{00b2}', '\u{00b3}', '\u{017d}', '\u{00b5}',
+    '\u{00b6}', '\u{00b7}', '\u{017e}', '\u{00b9}', '\u{00ba}', '\u{00bb}', '\u{0152}', '\u{0153}', '\u{0178}',
+    '\u{00bf}', '\u{00c0}', '\u{00c1}', '\u{00c2}', '\u{00c3}', '\u{00c4}', '\u{00c5}', '\u{00c6}', '\u{00c7}',
+    '\u{00c8}', '\u{00c9}', '\u{00ca}', '\u{00cb}', '\u{00cc}', '\u{00cd}', '\u{00ce}', '\u{00cf}', '\u{00d0}',
+    '\u{00d1}', '\u{00d2}', '\u{00d3}', '\u{00d4}', '\u{00d5}', '\u{00d6}', '\u{00d7}', '\u{00d8}', '\u{00d9}',
+    '\u{00da}', '\u{00db}', '\u{00dc}', '\u{00dd}', '\u{00de}', '\u{00df}', '\u{00e0}', '\u{00e1}', '\u{00e2}',
+    '\u{00e3}', '\u{00e4}', '\u{00e5}', '\u{00e6}', '\u{00e7}', '\u{00e8}', '\u{00e9}', '\u{00ea}', '\u{00eb}',
+    '\u{00ec}', '\u{00ed}', '\u{00ee}', '\u{00ef}', '\u{00f0}', '\u{00f1}', '\u{00f2}', '\u{00f3}', '\u{00f4}',
+    '\u{00f5}', '\u{00f6}', '\u{00f7}', '\u{00f8}', '\u{00f9}', '\u{00fa}', '\u{00fb}', '\u{00fc}', '\u{00fd}',
+    '\u{00fe}', '\u{00ff}',
+];
+
+const CP437: [char; 128] = [
+    '\u{00c7}', '\u{00fc}', '\u{00e9}', '\u{00e2}', '\u{00e4}', '\u{00e0}', '\u{00e5}', '\u{00e7}', '\u{00ea}',
+    '\u{00eb}', '\u{00e8}', '\u{00ef}', '\u{00ee}', '\u{00ec}', '\u{00c4}', '\u{00c5}', '\u{00c9}', '\u{00e6}',
+    '\u{00c6}', '\u{00f4}', '\u{00f6}', '\u{00f2}', '\u{00fb}', '\u{00f9}', '\u{00ff}', '\u{00d6}', '\u{00dc}',
+    '\u{00a2}', '\u{00a3}', '\u{00a5}', '\u{20a7}', '\u{0192}', '\u{00e1}', '\u{00ed}', '\u{00f3}', '\u{00fa}',
+    '\u{00f1}', '\u{00d1}', '\u{00aa}', '\u{00ba}', '\u{00bf}', '\u{2310}', '\u{00ac}', '\u{00bd}', '\u{00bc}',
+    '\u{00a1}', '\u{00ab}', '\u{00bb}', '\u{2591}', '\u{2592}', '\u{2593}', '\u{2502}', '\u{2524}', '\u{2561}',
+    '\u{2562}', '\u{2556}', '\u{2555}', '\u{2563}', '\u{2551}', '\u{2557}', '\u{255d}', '\u{255c}', '\u{255b}',
+    '\u{2510}', '\u{2514}', '\u{2534}', '\u{252c}', '\u{251c}', '\u{2500}', '\u{253c}', '\u{255e}', '\u{255f}',
+    '\u{255a}', '\u{2554}', '\u{2569}', '\u{2566}', '\u{2560}', '\u{2550}', '\u{256c}', '\u{2567}', '\u{2568}',
+    '\u{2564}', '\u{2565}', '\u{2559}', '\u{2558}', '\u{2552}', '\u{2553}', '\u{256b}', '\u{256a}', '\u{2518}',
+    '\u{250c}', '\u{2588}', '\u{2584}', '\u{258c}', '\u{2590}', '\u{2580}', '\u{03b1}', '\u{00df}', '\u{0393}',
+    '\u{03c0}', '\u{03a3}', '\u{03c3}', '\u{00b5}', '\u{03c4}', '\u{03a6}', '\u{0398}', '\u{03a9}', '\u{03b4}',
+    '\u{221e}', '\u{03c6}', '\u{03b5}', '\u{2229}', '\u{2261}', '\u{00b1}', '\u{2265}', '\u{2264}', '\u{2320}',
+    '\u{2321}', '\u{00f7}', '\u{2248}', '\u{00b0}', '\u{2219}', '\u{00b7}', '\u{221a}', '\u{207f}', '\u{00b2}',
+    '\u{25a0}', '\u{00a0}',
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_matched_loosely() {
+        assert_eq!(Encoding::from_name("ISO_8859_15"), Some(Encoding::Latin9));
+        assert_eq!(Encoding::from_name("latin1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::from_name("iso88592"), Some(Encoding::Latin2));
+        assert_eq!(Encoding::from_name("UTF8"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_name("ebcdic"), None);
+        assert_eq!(Encoding::from_locale("en_US.ISO-8859-15@euro"), Some(Encoding::Latin9));
+        assert_eq!(Encoding::from_locale("de_DE.UTF-8"), Some(Encoding::Utf8));
+        assert_eq!(Encoding::from_locale("latin1"), Some(Encoding::Latin1));
+        assert_eq!(Encoding::Cp437.to_string(), "cp437");
+    }
+
+    #[test]
+    fn single_byte_text_round_trips() {
+        let cp437 = b"\xc9\xcd\xbb ok \xdb";
+        assert_eq!(Encoding::Cp437.decode(cp437), "╔═╗ ok █");
+        assert_eq!(Encoding::Cp437.encode("╔═╗ ok █"), cp437);
+        assert_eq!(Encoding::Latin1.decode(b"caf\xe9"), "café");
+        assert_eq!(Encoding::Latin9.decode(b"5\xa4"), "5€");
+        assert_eq!(Encoding::Latin9.encode("5€"), b"5\xa4");
+    }
+
+    #[test]
+    fn missing_characters_become_question_marks() {
+        assert_eq!(Encoding::Latin1.encode("5€"), b"5?");
+        assert_eq!(Encoding::Cp437.encode("✓"), b"?");
+    }
+
+    #[test]
+    fn controls_pass_through_transcoding() {
+        assert_eq!(Encoding::Latin1.transcode(b"\x9bI caf\xc3\xa9"), b"\x9bI caf\xe9");
+        assert_eq!(Encoding::Utf8.transcode(b"\x9bI"), b"\x9bI");
+    }
+}
@@ -1,3 +1,4 @@
+use crate::structs::cell::Underline;
 use crate::term::colors::TerminalColor;
 use crate::term::font::Font;
 
@@ -9,14 +10,23 @@ pub enum Graphics {
     Bold,
     Dim,
     Italic,
-    Underline,
+    Underline(Underline),
     ReverseVideo,
     Strikethrough,
     SetFont(Font),
+    NormalIntensity,
+    NotItalic,
     NotUnderlined,
     NotReversed,
+    NotStrikethrough,
     SetForeground(TerminalColor),
     SetBackground(TerminalColor),
+    Overline,
+    NotOverline,
+    SetUnderlineColor(TerminalColor),
+    DefaultUnderlineColor,
+    // Blinking, concealed text and the like are not drawn
+    Unsupported,
 }
 
 impl Graphics {
@@ -26,12 +36,16 @@ impl Graphics {
             1 => Self::Bold,
             2 => Self::Dim,
             3 => Self::Italic,
-            4 => Self::Underline,
+            4 => Self::Underline(Underline::Single),
             7 => Self::ReverseVideo,
             9 => Self::Strikethrough,
             10..=19 => Self::SetFont(Font::Monospace), // TODO: Research how this works
+            21 => Self::Underline(Underline::Double),
+            22 => Self::NormalIntensity,
+            23 => Self::NotItalic,
             24 => Self::NotUnderlined,
             27 => Self::NotReversed,
+            29 => Self::NotStrikethrough,
 
             // Regular foreground colors
             30 => Self::SetForeground(TerminalColor::Black),
@@ -55,27 +69,31 @@ impl Graphics {
             47 => Self::SetBackground(TerminalColor::White),
             49 => Self::SetBackground(TerminalColor::Default),
 
+            53 => Self::Overline,
+            55 => Self::NotOverline,
+            59 => Self::DefaultUnderlineColor,
+
             // Bright foreground colors
-            90 => Self::SetForeground(TerminalColor::Black),
-            91 => Self::SetForeground(TerminalColor::Red),
-            92 => Self::SetForeground(TerminalColor::Green),
-            93 => Self::SetForeground(TerminalColor::Yellow),
-            94 => Self::SetForeground(TerminalColor::Blue),
-            95 => Self::SetForeground(TerminalColor::Magenta),
-            96 => Self::SetForeground(TerminalColor::Cyan),
-            97 => Self::SetForeground(TerminalColor::White),
+            90 => Self::SetForeground(TerminalColor::EightBit(8)),
+            91 => Self::SetForeground(TerminalColor::EightBit(9)),
+            92 => Self::SetForeground(TerminalColor::EightBit(10)),
+            93 => Self::SetForeground(TerminalColor::EightBit(11)),
+            94 => Self::SetForeground(TerminalColor::EightBit(12)),
+            95 => Self::SetForeground(TerminalColor::EightBit(13)),
+            96 => Self::SetForeground(TerminalColor::EightBit(14)),
+            97 => Self::SetForeground(TerminalColor::EightBit(15)),
 
             // Bright background colors
-            100 => Self::SetBackground(TerminalColor::Black),
-            101 => Self::SetBackground(TerminalColor::Red),
-            102 => Self::SetBackground(TerminalColor::Green),
-            103 => Self::SetBackground(TerminalColor::Yellow),
-            104 => Self::SetBackground(TerminalColor::Blue),
-            105 => Self::SetBackground(TerminalColor::Magenta),
-            106 => Self::SetBackground(TerminalColor::Cyan),
-            107 => Self::SetBackground(TerminalColor::White),
+            100 => Self::SetBackground(TerminalColor::EightBit(8)),
+            101 => Self::SetBackground(TerminalColor::EightBit(9)),
+            102 => Self::SetBackground(TerminalColor::EightBit(10)),
+            103 => Self::SetBackground(TerminalColor::EightBit(11)),
+            104 => Self::SetBackground(TerminalColor::EightBit(12)),
+            105 => Self::SetBackground(TerminalColor::EightBit(13)),
+            106 => Self::SetBackground(TerminalColor::EightBit(14)),
+            107 => Self::SetBackground(TerminalColor::EightBit(15)),
 
-            _ => unreachable!("Unimplemented SGR value found: {:?}", value),
+            _ => Self::Unsupported,
         }
     }
 }
@@ -1,4 +1,6 @@
+pub mod backend;
 pub mod colors;
+pub mod encoding;
 mod font;
 pub mod graphics;
 mod pty_reader;
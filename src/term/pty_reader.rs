@@ -1,10 +1,28 @@
 use std::io::{ErrorKind, Read};
 
-use super::terminal_output::TerminalOutput;
+use super::{encoding::Encoding, terminal_output::TerminalOutput};
+
+/// How bytes from the PTY become text
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Decoding {
+    pub encoding: Encoding,
+    // Bytes 0x80 to 0x9f outside of characters are C1 controls, like 0x9b for `ESC [`
+    pub c1_controls: bool,
+}
+
+// Bytes asked for per read, the most a PTY hands out at once on Linux
+pub const CHUNK_SIZE: usize = 4096;
+
+// OSC 701 sets the encoding of the output after it, and most bytes one with its locale takes
+const SET_ENCODING: &[u8] = b"\x1b]701;";
+const MAX_SET_ENCODING: usize = 128;
 
 pub struct PtyReader<R: Read> {
     inner: R,
     buffer: Vec<u8>,
+    decoding: Decoding,
+    // Nothing more comes, a character cut off at the end will never be finished
+    closed: bool,
 }
 
 impl<R: Read> PtyReader<R> {
@@ -12,77 +30,304 @@ impl<R: Read> PtyReader<R> {
         Self {
             inner,
             buffer: Vec::new(),
+            decoding: Decoding::default(),
+            closed: false,
         }
     }
 
+    pub fn set_decoding(&mut self, decoding: Decoding) {
+        self.decoding = decoding;
+    }
+
+    // Changed by OSC 701 in the output
+    pub fn decoding(&self) -> Decoding {
+        self.decoding
+    }
+
     pub fn read_chunk(&mut self) -> PtyReaderResult {
-        let mut chunk = [0u8; 1024];
+        let result = self.read();
+        self.closed |= matches!(result, PtyReaderResult::Closed);
+        result
+    }
+
+    fn read(&mut self) -> PtyReaderResult {
+        let mut chunk = [0u8; CHUNK_SIZE];
         match self.inner.read(&mut chunk) {
+            // The other end is closed, some systems say so with EOF instead of EIO
+            Ok(0) => PtyReaderResult::Closed,
             Ok(n) => {
                 self.buffer.extend_from_slice(&chunk[..n]);
                 PtyReaderResult::MoreLeft
             }
-            Err(e) => {
+            Err(e) => match e.kind() {
                 // WouldBlock is expected when there is no input
-                if e.kind() == ErrorKind::WouldBlock {
-                    PtyReaderResult::EndOfInput
-                } else {
+                ErrorKind::WouldBlock => PtyReaderResult::EndOfInput,
+                ErrorKind::Interrupted => PtyReaderResult::MoreLeft,
+                // EIO once the shell and everything it started closed the PTY
+                _ if e.raw_os_error() == Some(libc::EIO) => PtyReaderResult::Closed,
+                _ => {
                     eprintln!("Error reading from PTY: {:?}", e);
-                    panic!();
+                    PtyReaderResult::Closed
                 }
-            }
+            },
         }
     }
 
+    // Bytes read but not processed yet
+    pub fn buffered(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // Decodes what was read so far. A UTF-8 character cut off by the end of the buffer waits for
+    // the rest of it in the next read, or comes out as U+FFFD once the reader is closed. Bytes
+    // that are not UTF-8 come out as U+FFFD. Output after OSC 701 is decoded in the encoding it
+    // sets, an OSC 701 cut off by the end of the buffer waits for the rest like a character does.
     pub fn process_buffer(&mut self) -> Option<Vec<TerminalOutput>> {
-        if self.buffer.len() > 0 {
-            let mut byte_sequence: Vec<u8> = vec![];
-            let mut output: Vec<TerminalOutput> = vec![];
-            for byte in self.buffer.iter() {
-                match byte {
-                    b'\x08' => {
-                        if byte_sequence.len() > 0 {
-                            let _s = byte_sequence.drain(0..).collect();
-                            let str_sequence = String::from_utf8(_s).unwrap();
-                            output.push(TerminalOutput::Text(str_sequence));
-                        }
-                        output.push(TerminalOutput::Backspace);
-                    }
-                    b'\n' => {
-                        if byte_sequence.len() > 0 {
-                            let _s = byte_sequence.drain(0..).collect();
-                            let str_sequence = String::from_utf8(_s).unwrap();
-                            output.push(TerminalOutput::Text(str_sequence));
-                        }
-                        output.push(TerminalOutput::NewLine);
-                    }
-                    b'\r' => {
-                        if byte_sequence.len() > 0 {
-                            let _s = byte_sequence.drain(0..).collect();
-                            let str_sequence = String::from_utf8(_s).unwrap();
-                            output.push(TerminalOutput::Text(str_sequence));
-                        }
-                        output.push(TerminalOutput::CarriageReturn);
+        let mut text = String::new();
+        while let Some((end, locale)) = find_set_encoding(&self.buffer) {
+            text.push_str(&self.decode(end));
+            if let Some(encoding) = Encoding::from_locale(&locale) {
+                self.decoding.encoding = encoding;
+            }
+        }
+        // The rest of an OSC 701 cut off by the end of the read may still come
+        let end = match find_unterminated_set_encoding(&self.buffer) {
+            Some(start) if !self.closed => start,
+            _ => self.buffer.len(),
+        };
+        text.push_str(&self.decode(end));
+        if self.closed && !self.buffer.is_empty() {
+            self.buffer.clear();
+            text.push(char::REPLACEMENT_CHARACTER);
+        }
+        if text.is_empty() {
+            return None;
+        }
+
+        Some(TerminalOutput::split(&text))
+    }
+
+    // Decodes the first `length` bytes of the buffer, but for a UTF-8 character cut off at the
+    // end, which stays in the buffer
+    fn decode(&mut self, length: usize) -> String {
+        let bytes = &self.buffer[..length];
+        let c1_controls = self.decoding.c1_controls;
+        let (text, used) = match self.decoding.encoding {
+            Encoding::Utf8 => decode_utf8(bytes, c1_controls),
+            encoding => {
+                let text = encoding.decode(bytes);
+                let text = if c1_controls { expand_c1_controls(&text) } else { text };
+                (text, length)
+            }
+        };
+        self.buffer.drain(..used);
+        text
+    }
+}
+
+// Where the first complete OSC 701 in `bytes` ends, with the locale it sets. Its bytes are ASCII
+// in every encoding there is a name for.
+fn find_set_encoding(bytes: &[u8]) -> Option<(usize, String)> {
+    let start = bytes
+        .windows(SET_ENCODING.len())
+        .position(|window| window == SET_ENCODING)?
+        + SET_ENCODING.len();
+    let rest = &bytes[start..];
+    let (length, terminator) = rest.iter().enumerate().find_map(|(index, byte)| match byte {
+        b'\x07' => Some((index, 1)),
+        b'\x1b' if rest.get(index + 1) == Some(&b'\\') => Some((index, 2)),
+        _ => None,
+    })?;
+    let locale = String::from_utf8_lossy(&rest[..length]).into_owned();
+    Some((start + length + terminator, locale))
+}
+
+// Where an OSC 701 starts that has not ended yet, or may be starting at the very end of `bytes`.
+// One longer than any locale name is not waited for.
+fn find_unterminated_set_encoding(bytes: &[u8]) -> Option<usize> {
+    if let Some(start) = bytes
+        .windows(SET_ENCODING.len())
+        .position(|window| window == SET_ENCODING)
+    {
+        return (bytes.len() - start <= MAX_SET_ENCODING).then_some(start);
+    }
+    let from = bytes.len().saturating_sub(SET_ENCODING.len() - 1);
+    (from..bytes.len()).find(|&start| SET_ENCODING.starts_with(&bytes[start..]))
+}
+
+// Decodes `bytes` up to an incomplete character at the end, if there is one. Returns the text
+// and how many bytes it took.
+fn decode_utf8(bytes: &[u8], c1_controls: bool) -> (String, usize) {
+    let mut text = String::with_capacity(bytes.len());
+    let mut rest = bytes;
+    loop {
+        match std::str::from_utf8(rest) {
+            Ok(valid) => {
+                text.push_str(valid);
+                return (text, bytes.len());
+            }
+            Err(error) => {
+                let (valid, invalid) = rest.split_at(error.valid_up_to());
+                text.push_str(std::str::from_utf8(valid).expect("Bytes up to the error are valid"));
+                match error.error_len() {
+                    Some(1) if c1_controls && is_c1_control(invalid[0]) => {
+                        text.push_str(&c1_escape(invalid[0]));
+                        rest = &invalid[1..];
                     }
-                    b => {
-                        byte_sequence.push(*b);
+                    Some(length) => {
+                        text.push(char::REPLACEMENT_CHARACTER);
+                        rest = &invalid[length..];
                     }
+                    // Only the end is missing, it may still come
+                    None => return (text, bytes.len() - invalid.len()),
                 }
             }
-            let _s = String::from_utf8(byte_sequence.clone()).unwrap();
-            output.push(TerminalOutput::Text(_s));
+        }
+    }
+}
+
+fn is_c1_control(byte: u8) -> bool {
+    (0x80..=0x9f).contains(&byte)
+}
 
-            // Buffer is consumed, clear it
-            self.buffer.drain(..);
+// The 7-bit form of a C1 control, 0x9b is `ESC [`
+fn c1_escape(byte: u8) -> String {
+    ['\x1b', char::from(byte - 0x40)].iter().collect()
+}
 
-            Some(output)
-        } else {
-            None
+// Single byte encodings decode C1 controls to U+0080 to U+009F
+fn expand_c1_controls(text: &str) -> String {
+    let mut expanded = String::with_capacity(text.len());
+    for c in text.chars() {
+        match u8::try_from(c) {
+            Ok(byte) if is_c1_control(byte) => expanded.push_str(&c1_escape(byte)),
+            _ => expanded.push(c),
         }
     }
+    expanded
 }
 
 pub enum PtyReaderResult {
     MoreLeft,
     EndOfInput,
+    // Nothing more will come, what is in the buffer is the last output
+    Closed,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn end_of_file_closes_the_reader() {
+        let mut reader = PtyReader::new(&b"bye\r\n"[..]);
+        assert!(matches!(reader.read_chunk(), PtyReaderResult::MoreLeft));
+        assert!(matches!(reader.read_chunk(), PtyReaderResult::Closed));
+        assert!(reader.process_buffer().is_some());
+    }
+
+    #[test]
+    fn a_character_cut_off_by_closing_is_replaced() {
+        let mut reader = PtyReader::new(&b"ok \xe2\x9c"[..]);
+        assert!(matches!(reader.read_chunk(), PtyReaderResult::MoreLeft));
+        let output = reader.process_buffer().unwrap();
+        assert_eq!(TerminalOutput::join(&output), "ok ");
+        assert!(matches!(reader.read_chunk(), PtyReaderResult::Closed));
+        let output = reader.process_buffer().unwrap();
+        assert_eq!(TerminalOutput::join(&output), "\u{fffd}");
+        assert_eq!(reader.buffered(), 0);
+    }
+
+    // Feeds `bytes` in two reads split at `offset` and joins the text of the output
+    fn decode_split(bytes: &[u8], offset: usize) -> String {
+        let mut reader = PtyReader::new(std::io::empty());
+        let mut text = String::new();
+        for part in [&bytes[..offset], &bytes[offset..]] {
+            reader.buffer.extend_from_slice(part);
+            text.push_str(&TerminalOutput::join(&reader.process_buffer().unwrap_or_default()));
+        }
+        text
+    }
+
+    #[test]
+    fn characters_split_between_reads_are_joined() {
+        let text = "héllo\r\nwörld ✓ 🎉\x08!";
+        for offset in 0..=text.len() {
+            assert_eq!(decode_split(text.as_bytes(), offset), text, "split at {}", offset);
+        }
+    }
+
+    #[test]
+    fn legacy_encodings_are_decoded() {
+        let mut reader = PtyReader::new(std::io::empty());
+        reader.set_decoding(Decoding {
+            encoding: Encoding::Cp437,
+            c1_controls: false,
+        });
+        reader.buffer.extend_from_slice(b"\xda\xc4\xbf\r\n");
+        let output = reader.process_buffer().unwrap();
+        assert!(matches!(&output[0], TerminalOutput::Text(text) if text == "┌─┐"));
+        assert_eq!(output.len(), 3);
+    }
+
+    #[test]
+    fn c1_controls_become_escapes_when_enabled() {
+        let text = |encoding, c1_controls, bytes: &[u8]| {
+            let mut reader = PtyReader::new(std::io::empty());
+            reader.set_decoding(Decoding { encoding, c1_controls });
+            reader.buffer.extend_from_slice(bytes);
+            match reader.process_buffer().unwrap().as_slice() {
+                [TerminalOutput::Text(text)] => text.clone(),
+                output => panic!("{:?}", output),
+            }
+        };
+        // U+009B written as UTF-8 is a character, not a control
+        let bytes = b"\x9b1m\x9d12;red\x9c\xc2\x9b";
+        assert_eq!(text(Encoding::Utf8, true, bytes), "\x1b[1m\x1b]12;red\x1b\\\u{9b}");
+        assert_eq!(text(Encoding::Utf8, false, b"\x9b1m"), "\u{fffd}1m");
+        assert_eq!(text(Encoding::Latin1, true, b"\x901\x9c"), "\x1bP1\x1b\\");
+        assert_eq!(text(Encoding::Latin1, false, b"\x9b"), "\u{9b}");
+    }
+
+    #[test]
+    fn set_encoding_switches_in_the_middle_of_the_output() {
+        let mut reader = PtyReader::new(std::io::empty());
+        reader
+            .buffer
+            .extend_from_slice(b"\xc3\xa9\x1b]701;fr_FR.ISO-8859-15@euro\x07\xe9\xa4");
+        let output = reader.process_buffer().unwrap();
+        assert!(TerminalOutput::join(&output).ends_with("\x07é€"));
+        assert!(TerminalOutput::join(&output).starts_with("é\x1b]701;"));
+        assert_eq!(reader.decoding().encoding, Encoding::Latin9);
+    }
+
+    #[test]
+    fn set_encoding_split_between_reads_still_switches() {
+        let bytes = b"\x1b]701;fr_FR.ISO-8859-15\x07\xe9\xa4";
+        for offset in 0..=bytes.len() {
+            let mut reader = PtyReader::new(std::io::empty());
+            let mut text = String::new();
+            for part in [&bytes[..offset], &bytes[offset..]] {
+                reader.buffer.extend_from_slice(part);
+                text.push_str(&TerminalOutput::join(&reader.process_buffer().unwrap_or_default()));
+            }
+            assert_eq!(text, "\x1b]701;fr_FR.ISO-8859-15\x07é€", "split at {}", offset);
+            assert_eq!(reader.decoding().encoding, Encoding::Latin9);
+        }
+    }
+
+    #[test]
+    fn invalid_bytes_are_replaced() {
+        // A stray continuation byte, a lead byte without its continuation, and bytes never
+        // found in UTF-8
+        let bytes = b"a\x80b\xe2\x9cc\xff\xfe\n";
+        for offset in 0..=bytes.len() {
+            assert_eq!(
+                decode_split(bytes, offset),
+                "a\u{fffd}b\u{fffd}c\u{fffd}\u{fffd}\n",
+                "split at {}",
+                offset
+            );
+        }
+    }
 }
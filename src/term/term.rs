@@ -1,83 +1,146 @@
 use iced::futures::{
-    channel::mpsc::{self, Sender},
+    channel::mpsc::{self, Receiver, Sender},
+    executor::block_on,
     SinkExt, Stream, StreamExt,
 };
-use libc::{winsize, TIOCSCTTY, TIOCSWINSZ};
 pub use rustix_openpty::rustix::termios::Winsize;
 use std::{
     fs::File,
-    io::{self, Error, Write},
-    os::{
-        fd::{AsRawFd, OwnedFd},
-        unix::process::CommandExt,
+    io::Read,
+    os::fd::{AsRawFd, OwnedFd, RawFd},
+    path::PathBuf,
+    process::{Child, ExitStatus},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
     },
-    process::{Child, Command},
-    time::Duration,
+    thread,
+    time::{Duration, Instant},
 };
 
 use super::{
-    pty_reader::{PtyReader, PtyReaderResult},
+    backend::{self, Backend, BackendKind},
+    encoding::Encoding,
+    pty_reader::{Decoding, PtyReader, PtyReaderResult},
     terminal_output::TerminalOutput,
 };
+use crate::structs::terminalsize::TerminalSize;
 
-pub struct Term {}
+// Batches of output waiting for the terminal, the reader stops reading when they are full
+const OUTPUT_QUEUE: usize = 4;
 
-impl Term {
-    pub fn spawn(winsize: Winsize) -> impl Stream<Item = Event> {
-        let pty = open_pty(winsize).expect("Could not get PTY");
-        let master = File::from(pty.master.try_clone().unwrap());
-
-        iced::stream::channel(100, |mut output| async move {
-            let shell = std::env::var("SHELL").expect("$SHELL is not set");
-            read_output(&pty.master, output.clone());
-            let _ = spawn_shell(&pty.slave, shell.as_str());
-            let (sender, mut receiver) = mpsc::channel(100);
-            output
-                .send(Event::Ready(sender))
-                .await
-                .expect("Could not send Message::Ready");
+// Most output sent to the terminal at once, and longest time spent reading it
+const MAX_BATCH_BYTES: usize = 64 * 1024;
+const MAX_BATCH_TIME: Duration = Duration::from_millis(10);
 
-            loop {
-                let input = receiver.select_next_some().await;
-                match input {
-                    TermMessage::Bytes(bytes) => {
-                        write_bytes(&master, &bytes);
-                    }
+// How often a quiet PTY checks whether the shell is still running
+const EXIT_CHECK_INTERVAL: Duration = Duration::from_millis(200);
 
-                    TermMessage::WindowResized(columns, rows) => match resize(&master, columns, rows) {
-                        Ok(_) => {
-                            // Everything OK, do nothing
-                        }
-                        Err(err) => {
-                            println!("Error resizing: {:?}", err);
-                        }
-                    },
+// How long a hung up shell gets to exit before it is killed, and how often it is checked on
+const HANG_UP_GRACE: Duration = Duration::from_secs(2);
+const HANG_UP_CHECK_INTERVAL: Duration = Duration::from_millis(20);
+
+pub struct Term {}
+
+impl Term {
+    pub fn spawn(winsize: Winsize, shell: Shell) -> impl Stream<Item = Event> {
+        iced::stream::channel(OUTPUT_QUEUE, move |mut output| async move {
+            let mut backend = match backend::open(&shell, winsize) {
+                Ok(backend) => backend,
+                Err(error) => {
+                    let message = format!("could not start {}: {}", shell.name(), error);
+                    let _ = output.send(Event::Failed(message)).await;
+                    return;
                 }
+            };
+            let reader = match backend.output() {
+                Ok(reader) => reader,
+                Err(error) => {
+                    let message = format!("could not read from {}: {}", shell.name(), error);
+                    let _ = output.send(Event::Failed(message)).await;
+                    return;
+                }
+            };
+            // Switched by the terminal while the shell runs, reading and writing follow along
+            let decoding = Arc::new(Mutex::new(Decoding {
+                encoding: shell.encoding,
+                c1_controls: shell.c1_controls,
+            }));
+            let input = match backend.input() {
+                Ok(input) => input,
+                Err(error) => {
+                    let message = format!("could not write to {}: {}", shell.name(), error);
+                    let _ = output.send(Event::Failed(message)).await;
+                    return;
+                }
+            };
+            // Set once the output ends or the terminal is gone
+            let ended = Arc::new(AtomicBool::new(false));
+            read_output(
+                reader,
+                backend.take_child(),
+                decoding.clone(),
+                output.clone(),
+                ended.clone(),
+            );
+            let (sender, receiver) = mpsc::channel(100);
+            if output.send(Event::Ready(sender)).await.is_err() {
+                return;
             }
+            write_input(backend, input, decoding, ended, receiver);
         })
     }
 }
 
-fn write_bytes(mut master: &File, content: &[u8]) {
-    master.write_all(content).unwrap();
-    master.flush().unwrap();
+/// Program started in the PTY, with its arguments, the directory it starts in and the encoding
+/// it writes text in. The backend can run it on plain pipes instead, or open a serial device in
+/// its place.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Shell {
+    pub program: String,
+    pub args: Vec<String>,
+    // Inherited from us when not set
+    pub working_directory: Option<PathBuf>,
+    pub encoding: Encoding,
+    // Read 8-bit C1 controls from the start, programs can also turn them on with S8C1T
+    pub c1_controls: bool,
+    pub backend: BackendKind,
+    // Where `term msg` run in the program reaches the terminal showing it. Set for each program
+    // on its own, a session server starts programs for more than one terminal.
+    pub control_socket: Option<PathBuf>,
 }
 
-fn resize(master: &File, columns: usize, rows: usize) -> Result<(), std::io::Error> {
-    let size = winsize {
-        ws_row: rows as u16,
-        ws_col: columns as u16,
-        ws_xpixel: 0,
-        ws_ypixel: 0,
-    };
-    println!("New size in term: {:?}", size);
+// Started when $SHELL is not set
+const FALLBACK_SHELL: &str = "/bin/sh";
+
+impl Shell {
+    pub fn new(program: String, args: Vec<String>) -> Self {
+        Self {
+            program,
+            args,
+            working_directory: None,
+            encoding: Encoding::default(),
+            c1_controls: false,
+            backend: BackendKind::default(),
+            control_socket: None,
+        }
+    }
 
-    let result = unsafe { libc::ioctl(master.as_raw_fd(), TIOCSWINSZ, &size) };
+    // What runs, for messages about it
+    pub fn name(&self) -> String {
+        match &self.backend {
+            BackendKind::Serial(serial) => serial.device.display().to_string(),
+            BackendKind::Pty | BackendKind::Pipe => self.program.clone(),
+        }
+    }
 
-    if result == 0 {
-        Ok(())
-    } else {
-        Err(std::io::Error::last_os_error())
+    // The user's shell from $SHELL
+    pub fn from_env() -> Self {
+        let program = std::env::var("SHELL")
+            .ok()
+            .filter(|shell| !shell.is_empty())
+            .unwrap_or_else(|| FALLBACK_SHELL.to_string());
+        Self::new(program, Vec::new())
     }
 }
 
@@ -85,87 +148,292 @@ fn resize(master: &File, columns: usize, rows: usize) -> Result<(), std::io::Err
 pub enum Event {
     Ready(mpsc::Sender<TermMessage>),
     Output(Vec<TerminalOutput>),
+    // The shell exited, after all its output was sent
+    Exited(ExitStatus),
+    // The shell could not be started or waited for
+    Failed(String),
 }
 
-type Columns = usize;
-type Rows = usize;
-
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum TermMessage {
     Bytes(Vec<u8>),
-    WindowResized(Columns, Rows),
+    SetEncoding(Encoding),
+    SetC1Controls(bool),
+    WindowResized(TerminalSize),
 }
 
-struct Pty {
-    master: OwnedFd,
-    slave: OwnedFd,
+// Reads the shell's output on a thread of its own, woken up by the PTY having something to
+// read. Sending waits while the terminal is behind, so a flood fills the PTY and blocks the
+// shell instead of piling up in memory.
+fn read_output(
+    output: OwnedFd,
+    child: Option<Child>,
+    decoding: Arc<Mutex<Decoding>>,
+    sender: Sender<Event>,
+    ended: Arc<AtomicBool>,
+) {
+    thread::spawn(move || {
+        read_until_closed(output, child, decoding, sender);
+        // Nothing is shown anymore, a write still waiting for room gives up
+        ended.store(true, Ordering::Relaxed);
+    });
 }
 
-fn open_pty(winsize: Winsize) -> Result<Pty, Error> {
-    // Ask OS for a PTY
-    let pty = rustix_openpty::openpty(None, Some(&winsize))?;
+fn read_until_closed(
+    output: OwnedFd,
+    mut child: Option<Child>,
+    decoding: Arc<Mutex<Decoding>>,
+    mut sender: Sender<Event>,
+) {
+    let fd = output.as_raw_fd();
+    let mut reader = PtyReader::new(File::from(output));
 
-    // Make reads on master non-blockinginput
-    unsafe { libc::fcntl(pty.controller.as_raw_fd(), libc::F_SETFL, libc::O_NONBLOCK) };
+    loop {
+        let readable = wait_readable(fd, EXIT_CHECK_INTERVAL);
+        let closed = readable && read_batch(&mut reader);
+        let output = {
+            let mut decoding = decoding.lock().unwrap();
+            reader.set_decoding(*decoding);
+            let output = reader.process_buffer();
+            // OSC 701 in the output switched it, for what is typed as well
+            *decoding = reader.decoding();
+            output
+        };
+        if let Some(output) = output {
+            if block_on(sender.send(Event::Output(output))).is_err() {
+                return hang_up(reader, child);
+            }
+        }
+        if readable && !closed {
+            continue;
+        }
+        // The terminal is gone, like a closed tab, while the shell was quiet
+        if sender.is_closed() {
+            return hang_up(reader, child);
+        }
 
-    // Return a struct with master and slave side of PTY
-    Ok(Pty {
-        master: pty.controller,
-        slave: pty.user,
-    })
+        // A device has no program to wait for, it is gone once it closes
+        let Some(process) = &mut child else {
+            if closed {
+                let _ = block_on(sender.send(Event::Failed("the device was closed".to_string())));
+                return;
+            }
+            continue;
+        };
+        // Processes the shell left in the background can keep the PTY open after it exits
+        let status = if closed {
+            process.wait().map(Some)
+        } else {
+            process.try_wait()
+        };
+        let event = match status {
+            Ok(Some(status)) => Event::Exited(status),
+            Ok(None) => continue,
+            Err(error) => Event::Failed(format!("could not wait for the shell: {}", error)),
+        };
+        let _ = block_on(sender.send(event));
+        return;
+    }
 }
 
-fn read_output(master: &OwnedFd, mut sender: Sender<Event>) {
-    let master_clone = master.try_clone().unwrap();
-
-    let _a = async_std::task::spawn(async move {
-        let file = File::from(master_clone);
-        let mut reader = PtyReader::new(file);
-
-        loop {
-            async_std::task::sleep(Duration::from_millis(16)).await;
-            match reader.read_chunk() {
-                PtyReaderResult::MoreLeft => {}
-                PtyReaderResult::EndOfInput => match reader.process_buffer() {
-                    Some(output) => {
-                        sender
-                            .send(Event::Output(output))
-                            .await
-                            .expect("Could not send output to terminal GUI");
+// Writes what is typed to the shell, and passes on resizes, on a thread of its own, so a shell
+// that is not reading a long paste holds up this thread instead of the terminal. Ends once the
+// terminal drops its sender, after the shell exited.
+fn write_input(
+    mut backend: Box<dyn Backend>,
+    input: OwnedFd,
+    decoding: Arc<Mutex<Decoding>>,
+    ended: Arc<AtomicBool>,
+    mut receiver: Receiver<TermMessage>,
+) {
+    thread::spawn(move || {
+        let mut writer = File::from(input);
+        while let Some(input) = block_on(receiver.next()) {
+            match input {
+                TermMessage::Bytes(bytes) => {
+                    // What we send is UTF-8, what the shell reads may not be
+                    let bytes = decoding.lock().unwrap().encoding.transcode(&bytes);
+                    if let Err(err) = backend::write_all_nonblocking(&mut writer, &bytes, &ended) {
+                        eprintln!("Error writing to the shell: {:?}", err);
+                    }
+                }
+
+                TermMessage::SetEncoding(encoding) => decoding.lock().unwrap().encoding = encoding,
+                TermMessage::SetC1Controls(c1_controls) => decoding.lock().unwrap().c1_controls = c1_controls,
+
+                TermMessage::WindowResized(size) => {
+                    if let Err(err) = backend.resize(size) {
+                        eprintln!("Error resizing: {:?}", err);
                     }
-                    None => {}
-                },
+                }
             }
         }
     });
 }
-fn spawn_shell(slave: &OwnedFd, shell: &str) -> io::Result<Child> {
-    let mut command = Command::new(shell);
-    command.env("TERM", "xterm-256color");
-    command.stdin(slave.try_clone()?);
-    command.stdout(slave.try_clone()?);
-    command.stderr(slave.try_clone()?);
-
-    let slave_fd = slave.as_raw_fd();
-    unsafe {
-        command.pre_exec(move || {
-            // Become leader of new session
-            let err = libc::setsid();
-            if err == -1 {
-                return Err(Error::new(io::ErrorKind::Other, "Failed to set session ID"));
-            }
 
-            // Set controlling terminal
-            let res = libc::ioctl(slave_fd, TIOCSCTTY as _, 0);
-            if res < 0 {
-                return Err(Error::new(io::ErrorKind::Other, "Failed to set controlling terminal"));
+// Closing our side of the PTY sends the shell SIGHUP, a program on pipes gets it from us.
+// Waiting for it keeps it from lingering as a zombie, and one that ignores SIGHUP is killed
+// after a grace period.
+fn hang_up<R: Read>(reader: PtyReader<R>, child: Option<Child>) {
+    drop(reader);
+    let Some(mut child) = child else {
+        return;
+    };
+    unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGHUP) };
+    let deadline = Instant::now() + HANG_UP_GRACE;
+    while Instant::now() < deadline {
+        match child.try_wait() {
+            Ok(None) => thread::sleep(HANG_UP_CHECK_INTERVAL),
+            Ok(Some(_)) | Err(_) => return,
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+}
+
+// Waits up to `timeout` for output, or for the PTY to be closed
+fn wait_readable(fd: RawFd, timeout: Duration) -> bool {
+    let mut poll_fd = libc::pollfd {
+        fd,
+        events: libc::POLLIN,
+        revents: 0,
+    };
+    let result = unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as libc::c_int) };
+    result > 0
+}
+
+// Reads what the PTY has for now, but no more than one batch, so a shell that never stops
+// writing still gets its output on screen. Returns whether the PTY was closed.
+fn read_batch<R: Read>(reader: &mut PtyReader<R>) -> bool {
+    let started = Instant::now();
+    loop {
+        match reader.read_chunk() {
+            PtyReaderResult::MoreLeft if reader.buffered() >= MAX_BATCH_BYTES => return false,
+            PtyReaderResult::MoreLeft if started.elapsed() >= MAX_BATCH_TIME => return false,
+            PtyReaderResult::MoreLeft => {}
+            PtyReaderResult::EndOfInput => return false,
+            PtyReaderResult::Closed => return true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::term::pty_reader::CHUNK_SIZE;
+
+    fn sh(script: &str) -> Shell {
+        Shell::new("/bin/sh".to_string(), vec!["-c".to_string(), script.to_string()])
+    }
+
+    // Runs `shell`, typing `input` once it is ready. Gives the text of every batch of output
+    // and how the shell exited.
+    fn run(shell: Shell, input: &[u8]) -> (Vec<String>, ExitStatus) {
+        let winsize = Winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        block_on(async {
+            let mut events = Box::pin(Term::spawn(winsize, shell));
+            let mut batches = Vec::new();
+            loop {
+                match events.next().await.expect("The shell should exit") {
+                    Event::Output(tokens) => batches.push(
+                        tokens
+                            .iter()
+                            .map(|token| match token {
+                                TerminalOutput::Text(text) => text.as_str(),
+                                TerminalOutput::NewLine => "\n",
+                                TerminalOutput::CarriageReturn => "\r",
+                                TerminalOutput::Backspace => "\x08",
+                            })
+                            .collect(),
+                    ),
+                    Event::Exited(status) => return (batches, status),
+                    Event::Failed(error) => panic!("{}", error),
+                    Event::Ready(mut sender) => sender.send(TermMessage::Bytes(input.to_vec())).await.unwrap(),
+                }
             }
+        })
+    }
+
+    #[test]
+    fn exit_status_follows_the_last_output() {
+        let (batches, status) = run(sh("echo bye; exit 3"), b"");
+        assert_eq!(batches.concat(), "bye\r\n");
+        assert_eq!(status.code(), Some(3));
+    }
+
+    #[test]
+    fn legacy_encodings_are_transcoded_both_ways() {
+        let mut shell = sh("printf '\\333\\n'; read line; printf '%s' \"$line\" | od -An -tx1");
+        shell.encoding = Encoding::Cp437;
+        let (batches, _) = run(shell, "█\r".as_bytes());
+        let output = batches.concat();
+        assert!(output.starts_with("█\r\n"), "{:?}", output);
+        assert!(output.trim_end().ends_with("db"), "{:?}", output);
+    }
+
+    #[test]
+    fn pipes_pass_bytes_without_a_terminal() {
+        let mut shell = sh("read line; echo \"got $line\"; [ -t 0 ] || echo no tty >&2");
+        shell.backend = BackendKind::Pipe;
+        let (batches, status) = run(shell, b"hi\n");
+        // No line discipline, so nothing is echoed and newlines are not turned into CRLF
+        assert_eq!(batches.concat(), "got hi\nno tty\n");
+        assert_eq!(status.code(), Some(0));
+    }
 
-            libc::close(slave_fd);
+    #[test]
+    fn the_control_socket_is_set_per_shell() {
+        let mut shell = sh("echo \"$TERM_CONTROL_SOCKET\"");
+        shell.control_socket = Some(PathBuf::from("/tmp/term-control-1.sock"));
+        let (batches, _) = run(shell, b"");
+        assert_eq!(batches.concat(), "/tmp/term-control-1.sock\r\n");
+    }
+
+    #[test]
+    fn a_shell_not_reading_its_input_does_not_hold_up_its_output() {
+        let (batches, status) = run(sh("sleep 0.2; echo done"), &vec![b'x'; 1 << 20]);
+        // The PTY echoes what fits of the input around the output
+        assert!(batches.concat().contains("done\r\n"));
+        assert_eq!(status.code(), Some(0));
+    }
 
-            Ok(())
+    #[test]
+    fn shells_ignoring_sighup_are_killed_after_the_grace_period() {
+        let winsize = Winsize {
+            ws_row: 24,
+            ws_col: 80,
+            ws_xpixel: 0,
+            ws_ypixel: 0,
+        };
+        let pid = block_on(async {
+            let mut events = Box::pin(Term::spawn(
+                winsize,
+                sh("trap '' HUP; echo $$; while :; do sleep 0.1; done"),
+            ));
+            loop {
+                if let Some(Event::Output(tokens)) = events.next().await {
+                    if let Some(TerminalOutput::Text(pid)) = tokens.first() {
+                        return pid.parse::<libc::pid_t>().unwrap();
+                    }
+                }
+            }
         });
+        thread::sleep(HANG_UP_GRACE + EXIT_CHECK_INTERVAL + Duration::from_millis(500));
+        // Reaped, so the pid is gone
+        assert_eq!(unsafe { libc::kill(pid, 0) }, -1);
     }
 
-    command.spawn()
+    #[test]
+    fn floods_come_in_bounded_batches() {
+        let (batches, status) = run(sh("head -c 500000 /dev/zero | tr '\\0' a"), b"");
+        assert_eq!(status.code(), Some(0));
+        assert_eq!(batches.iter().map(String::len).sum::<usize>(), 500000);
+        assert!(batches.len() > 1);
+        assert!(batches.iter().all(|batch| batch.len() <= MAX_BATCH_BYTES + CHUNK_SIZE));
+    }
 }
@@ -5,3 +5,40 @@ pub enum TerminalOutput {
     CarriageReturn,
     Backspace,
 }
+
+impl TerminalOutput {
+    // Splits decoded text at the controls the terminal handles apart from the rest
+    pub fn split(text: &str) -> Vec<TerminalOutput> {
+        let mut output = Vec::new();
+        let mut rest = text;
+        while let Some(index) = rest.find(['\x08', '\n', '\r']) {
+            if index > 0 {
+                output.push(TerminalOutput::Text(rest[..index].to_string()));
+            }
+            output.push(match rest.as_bytes()[index] {
+                b'\x08' => TerminalOutput::Backspace,
+                b'\n' => TerminalOutput::NewLine,
+                _ => TerminalOutput::CarriageReturn,
+            });
+            rest = &rest[index + 1..];
+        }
+        if !rest.is_empty() {
+            output.push(TerminalOutput::Text(rest.to_string()));
+        }
+        output
+    }
+
+    // The text `split` took apart
+    pub fn join(output: &[TerminalOutput]) -> String {
+        let mut text = String::new();
+        for token in output {
+            match token {
+                TerminalOutput::Text(part) => text.push_str(part),
+                TerminalOutput::NewLine => text.push('\n'),
+                TerminalOutput::CarriageReturn => text.push('\r'),
+                TerminalOutput::Backspace => text.push('\x08'),
+            }
+        }
+        text
+    }
+}
@@ -1,21 +1,28 @@
+use std::ops::Range;
+
 use crate::{
-    ansi_parser::{self, AnsiParser, CSISequence, ESCSequence},
-    structs::cursor::Direction,
+    ansi_parser::{self, AnsiParser, CSISequence, ESCSequence, OSCSequence},
+    bindings::Modes,
+    structs::cursor::{CursorStyle, Direction},
 };
 use iced::{
     futures::{channel::mpsc, SinkExt},
-    Task,
+    Color, Task,
 };
 use rustix_openpty::rustix::termios::Winsize;
 
 use crate::{
     structs::{
         buffer::{Buffer, Selection},
-        cell::{Cell, CellStyle},
+        cell::{char_width, Cell, CellStyle},
+        damage::Damage,
+        selection::TextSelection,
         terminalsize::TerminalSize,
     },
     term::{
         self,
+        colors::parse_color_spec,
+        encoding::Encoding,
         term::{Event, TermMessage},
         terminal_output::TerminalOutput,
     },
@@ -23,16 +30,31 @@ use crate::{
 };
 
 pub struct Terminal {
-    application_mode: bool, // Changes how cursor keys are coded
-    newline_mode: bool,     // Interprets \n as NL LF instead of just NL
-    focus_mode: bool,       // When enabled, sends \e[I on focus and \e[O on defocus
-    auto_wrap_mode: bool,   // Automatically wraps to next line when cursor is at end of line
+    application_mode: bool,     // Changes how cursor keys are coded
+    newline_mode: bool,         // Interprets \n as NL LF instead of just NL
+    focus_mode: bool,           // When enabled, sends \e[I on focus and \e[O on defocus
+    auto_wrap_mode: bool,       // Automatically wraps to next line when cursor is at end of line
+    bracketed_paste_mode: bool, // Pasted text is wrapped in \e[200~ and \e[201~
+    eight_bit_controls: bool,   // Our sequences start with 8-bit C1 controls, like 0x9b for \e[
+    // The config reads 8-bit C1 controls in the output from the start, S7C1T leaves them on then
+    c1_controls: bool,
+    // Mouse reporting and its encoding as the program asked for them, like 1002 and 1006. Not
+    // reported yet, but kept for windows attaching to the session later.
+    mouse_tracking: Option<u32>,
+    mouse_encoding: Option<u32>,
     size: TerminalSize,
     cursor_visible: bool,
+    // Kept across screens, the alternate screen shares it with the normal one
+    cursor_style: CursorStyle,
+    cursor_color: Option<Color>, // Set by OSC 12, the renderer picks a color otherwise
+    title: Option<String>,       // Set by OSC 0 and 2
+    bell: bool,                  // BEL arrived since the last `take_bell`
     buffer: Buffer<Cell>,
     alternate_buffer: Option<Buffer<Cell>>,
     current_cell_style: CellStyle,
     sender: Option<mpsc::Sender<term::term::TermMessage>>,
+    // Messages for the PTY that came up while parsing its output, sent once it is parsed
+    pending: Vec<TermMessage>,
 }
 
 impl Terminal {
@@ -45,12 +67,22 @@ impl Terminal {
             newline_mode: false,
             focus_mode: false,
             auto_wrap_mode: true,
+            bracketed_paste_mode: false,
+            eight_bit_controls: false,
+            c1_controls: false,
+            mouse_tracking: None,
+            mouse_encoding: None,
             size,
             cursor_visible: true,
+            cursor_style: CursorStyle::default(),
+            cursor_color: None,
+            title: None,
+            bell: false,
             buffer: Buffer::new(rows, cols, vec![Cell::default(); rows * cols]),
             alternate_buffer: None,
             current_cell_style: CellStyle::default(),
             sender: None,
+            pending: Vec::new(),
         }
     }
 
@@ -62,6 +94,11 @@ impl Terminal {
         }
     }
 
+    // The normal screen, behind the alternate screen while that is shown
+    pub fn primary_buffer(&self) -> &Buffer<Cell> {
+        &self.buffer
+    }
+
     fn buffer_mut(&mut self) -> &mut Buffer<Cell> {
         if let Some(buffer) = &mut self.alternate_buffer {
             buffer
@@ -74,7 +111,8 @@ impl Terminal {
         if let Some(sender) = self.sender.clone() {
             let f = async move {
                 let mut sender = sender;
-                sender.send(message).await.expect("Could not send TermMessage");
+                // The shell may have exited since
+                let _ = sender.send(message).await;
             };
             Task::perform(f, |_| Message::TerminalInput)
         } else {
@@ -82,6 +120,22 @@ impl Terminal {
         }
     }
 
+    // Sends what parsing asked for in one go, so it arrives in order
+    fn send_pending(&mut self) -> Task<Message> {
+        let pending = std::mem::take(&mut self.pending);
+        match self.sender.clone() {
+            Some(mut sender) if !pending.is_empty() => {
+                let f = async move {
+                    for message in pending {
+                        let _ = sender.send(message).await;
+                    }
+                };
+                Task::perform(f, |_| Message::TerminalInput)
+            }
+            _ => Task::none(),
+        }
+    }
+
     pub fn parse(&mut self, event: Event) -> Task<Message> {
         match event {
             term::term::Event::Ready(sender) => {
@@ -106,9 +160,23 @@ impl Terminal {
                         }
                     }
                 }
-                Task::none()
+                self.send_pending()
             }
+            // Whether the window stays is up to the application, which calls `end` if it does
+            term::term::Event::Exited(_) | term::term::Event::Failed(_) => Task::none(),
+        }
+    }
+
+    // The shell is gone, nothing reads what we would send anymore. `notice` is written below its
+    // output to say how it ended.
+    pub fn end(&mut self, notice: &str) {
+        self.sender = None;
+        let newline_mode = self.newline_mode;
+        if self.buffer().cursor.col > 0 {
+            self.buffer_mut().carriage_return();
+            self.buffer_mut().newline(newline_mode);
         }
+        self.handle_ansi(notice);
     }
 
     fn handle_ansi(&mut self, ansi_text: &str) {
@@ -116,16 +184,30 @@ impl Terminal {
         for block in parsed.into_iter() {
             match block {
                 ansi_parser::Output::TextBlock(text) => text.chars().for_each(|c| {
+                    if c == '\x07' {
+                        self.bell = true;
+                        return;
+                    }
                     let current_cell_style = self.current_cell_style.clone();
                     let auto_wrap_mode = self.auto_wrap_mode;
-                    self.buffer_mut().write(c, current_cell_style);
-                    self.buffer_mut().advance_cursor(auto_wrap_mode);
+                    self.buffer_mut().print(c, current_cell_style, auto_wrap_mode);
                 }),
 
                 ansi_parser::Output::AnsiSequence(code) => match code {
-                    ansi_parser::AnsiSequence::OSC(_osc) => {
-                        // do nothing as of now
-                    }
+                    ansi_parser::AnsiSequence::OSC(osc) => match osc {
+                        OSCSequence::SetTitle(title) => self.title = Some(title).filter(|title| !title.is_empty()),
+                        OSCSequence::ResetCursorColor => self.cursor_color = None,
+                        OSCSequence::SetCursorColor(spec) => match parse_color_spec(&spec) {
+                            Some(color) => self.cursor_color = Some(color),
+                            None => eprintln!("Unknown cursor color {}", spec),
+                        },
+                        // A locale like `en_US.ISO-8859-1`, or just the encoding. The reader has
+                        // switched already, this is for the input.
+                        OSCSequence::SetEncoding(locale) => match Encoding::from_locale(&locale) {
+                            Some(encoding) => self.pending.push(TermMessage::SetEncoding(encoding)),
+                            None => eprintln!("Unknown encoding {}", locale),
+                        },
+                    },
 
                     ansi_parser::AnsiSequence::ESC(esc) => match esc {
                         ESCSequence::SetAlternateKeypad | ESCSequence::SetNumericKeypad => {
@@ -139,6 +221,18 @@ impl Terminal {
                             self.buffer_mut().unshift_row();
                         }
 
+                        // Programs asking for 8-bit replies send 8-bit controls too
+                        ESCSequence::EightBitControls => {
+                            self.eight_bit_controls = true;
+                            self.pending.push(TermMessage::SetC1Controls(true));
+                        }
+                        ESCSequence::SevenBitControls => {
+                            self.eight_bit_controls = false;
+                            if !self.c1_controls {
+                                self.pending.push(TermMessage::SetC1Controls(false));
+                            }
+                        }
+
                         _ => {
                             println!("Unimplemented ESC code: {:?}", esc);
                         }
@@ -209,8 +303,12 @@ impl Terminal {
                         CSISequence::DecPrivateModeSet(n) => match n {
                             1 => self.application_mode = true,
                             7 => self.auto_wrap_mode = true,
-                            25 => self.cursor_visible = false,
+                            12 => self.cursor_style = self.cursor_style.with_blinking(true),
+                            25 => self.cursor_visible = true,
+                            9 | 1000 | 1002 | 1003 => self.mouse_tracking = Some(n),
+                            1005 | 1006 | 1015 => self.mouse_encoding = Some(n),
                             1004 => self.focus_mode = true,
+                            2004 => self.bracketed_paste_mode = true,
                             1049 => {
                                 let rows = self.buffer().rows;
                                 let cols = self.buffer().cols;
@@ -223,9 +321,16 @@ impl Terminal {
                         CSISequence::DecPrivateModeReset(n) => match n {
                             1 => self.application_mode = false,
                             7 => self.auto_wrap_mode = false,
-                            25 => self.cursor_visible = true,
+                            12 => self.cursor_style = self.cursor_style.with_blinking(false),
+                            25 => self.cursor_visible = false,
+                            9 | 1000 | 1002 | 1003 => self.mouse_tracking = None,
+                            1005 | 1006 | 1015 => self.mouse_encoding = None,
                             1004 => self.focus_mode = false,
-                            1049 => self.alternate_buffer = None,
+                            2004 => self.bracketed_paste_mode = false,
+                            1049 => {
+                                self.alternate_buffer = None;
+                                self.buffer.damage_all();
+                            }
                             n => println!("Unimplemented DecPrivateModeReset value {}", n),
                         },
 
@@ -238,11 +343,14 @@ impl Terminal {
                         }
 
                         CSISequence::CursorStyle(style) => {
-                            self.buffer_mut().cursor.set_style(style);
+                            self.cursor_style = CursorStyle::from_ansi(style);
                         }
 
                         CSISequence::SetTopAndBottom(top, bottom) => {
-                            self.buffer_mut().set_top_bottom(top as usize, bottom as usize);
+                            // Scroll region rows start at 1 in terminal, while grid starts at 0
+                            let top = (top as usize).saturating_sub(1);
+                            let bottom = (bottom as usize).saturating_sub(1);
+                            self.buffer_mut().set_top_bottom(top, bottom);
                         }
 
                         _ => {
@@ -254,9 +362,20 @@ impl Terminal {
         }
     }
 
+    // A sequence of ours as bytes, with the escape at its start turned into its 8-bit C1 control
+    // if the program asked for those
+    fn control(&self, sequence: &str) -> Vec<u8> {
+        match sequence.as_bytes() {
+            [0x1b, introducer @ 0x40..=0x5f, rest @ ..] if self.eight_bit_controls => {
+                [&[introducer + 0x40], rest].concat()
+            }
+            bytes => bytes.to_vec(),
+        }
+    }
+
     pub fn focus(&self) -> Task<Message> {
         if self.focus_mode {
-            self.send(TermMessage::Bytes("\x1b[I".into()))
+            self.send(TermMessage::Bytes(self.control("\x1b[I")))
         } else {
             Task::none()
         }
@@ -264,18 +383,268 @@ impl Terminal {
 
     pub fn unfocus(&self) -> Task<Message> {
         if self.focus_mode {
-            self.send(TermMessage::Bytes("\x1b[O".into()))
+            self.send(TermMessage::Bytes(self.control("\x1b[O")))
         } else {
             Task::none()
         }
     }
 
+    // Sends pasted text, bracketed when the program asked for it so it can tell pasted text from
+    // typed text. Escapes are dropped so the text can't end the bracket early.
+    pub fn paste(&self, text: &str) -> Task<Message> {
+        let bytes = if self.bracketed_paste_mode {
+            let text = text.replace(['\x1b', '\u{9b}'], "");
+            [self.control("\x1b[200~"), text.into_bytes(), self.control("\x1b[201~")].concat()
+        } else {
+            text.replace("\r\n", "\r").replace('\n', "\r").into_bytes()
+        };
+        self.send(TermMessage::Bytes(bytes))
+    }
+
+    pub fn modes(&self) -> Modes {
+        Modes {
+            alt_screen: self.alternate_buffer.is_some(),
+            app_cursor: self.application_mode,
+        }
+    }
+
+    // The DEC private modes a program sets, with whether they are on. Cursor visibility and the
+    // alternate screen are left to their own getters.
+    pub fn private_modes(&self) -> Vec<(u32, bool)> {
+        let mut modes = vec![
+            (1, self.application_mode),
+            (7, self.auto_wrap_mode),
+            (1004, self.focus_mode),
+            (2004, self.bracketed_paste_mode),
+        ];
+        modes.extend(self.mouse_tracking.map(|mode| (mode, true)));
+        modes.extend(self.mouse_encoding.map(|mode| (mode, true)));
+        modes
+    }
+
+    pub fn eight_bit_controls(&self) -> bool {
+        self.eight_bit_controls
+    }
+
+    // Whether the shell's output is read with C1 controls from the start, as configured
+    pub fn set_c1_controls(&mut self, c1_controls: bool) {
+        self.c1_controls = c1_controls;
+    }
+
+    // Scrolls the view through the scrollback, the alternate screen has none
+    pub fn scroll_display(&mut self, lines: isize) {
+        self.buffer_mut().scroll_display(lines);
+    }
+
+    pub fn scroll_to_bottom(&mut self) {
+        self.buffer_mut().scroll_display(isize::MIN);
+    }
+
+    // The last match of `query` starting before `before`, a line and column with lines counted
+    // from the oldest one in the scrollback. Returns the line and columns of the match.
+    pub fn find(&self, query: &str, before: (usize, usize)) -> Option<(usize, Range<usize>)> {
+        let query: Vec<char> = query.chars().collect();
+        if query.is_empty() {
+            return None;
+        }
+        let buffer = self.buffer();
+        let screen = (0..buffer.rows).map(|row| buffer.row(row));
+        let lines: Vec<&[Cell]> = buffer
+            .history()
+            .chain(screen)
+            .take(before.0.saturating_add(1))
+            .collect();
+        lines.iter().enumerate().rev().find_map(|(line, cells)| {
+            let end = if line == before.0 { before.1 } else { usize::MAX };
+            // A wide character is one character over two cells
+            let chars: Vec<(usize, char)> = cells
+                .iter()
+                .enumerate()
+                .filter(|(_, cell)| !cell.is_spacer())
+                .map(|(col, cell)| (col, cell.content))
+                .collect();
+            let start = chars.windows(query.len()).rposition(|window| {
+                window[0].0 < end && window.iter().zip(&query).all(|((_, content), c)| content == c)
+            })?;
+            let (last, content) = chars[start + query.len() - 1];
+            Some((line, chars[start].0..last + char_width(content)))
+        })
+    }
+
+    // Scrolls the view just enough to show `line`, counted like in `find`, and returns its row in
+    // the view
+    pub fn show_line(&mut self, line: usize) -> usize {
+        let buffer = self.buffer();
+        let history = buffer.history().count();
+        let offset = buffer.display_offset();
+        let top = history - offset;
+        let wanted = if line < top {
+            history - line
+        } else if line >= top + buffer.rows {
+            history.saturating_sub(line + 1 - buffer.rows)
+        } else {
+            offset
+        };
+        self.scroll_display(wanted as isize - offset as isize);
+        line + wanted - history
+    }
+
+    pub fn selected_text(&self, selection: &TextSelection) -> String {
+        selection.text(self.buffer().iter_rows())
+    }
+
     pub fn resize(&mut self, new_size: TerminalSize) -> Task<Message> {
-        self.buffer_mut().resize(new_size.rows, new_size.cols);
-        self.send(TermMessage::WindowResized(new_size.cols, new_size.rows))
+        // Only the pixel size changes when the scale factor does, keep the grid as it is then
+        if new_size.rows != self.size.rows || new_size.cols != self.size.cols {
+            self.buffer_mut().resize(new_size.rows, new_size.cols);
+        }
+        self.size = new_size;
+        self.send(TermMessage::WindowResized(new_size))
+    }
+
+    // Changes to the visible buffer since last call, for the renderer to redraw
+    pub fn take_damage(&mut self) -> Damage {
+        self.buffer_mut().take_damage()
+    }
+
+    // Only the primary screen keeps rows scrolled off it
+    pub fn set_scrollback(&mut self, lines: usize) {
+        self.buffer.set_scrollback(lines);
+    }
+
+    pub fn cursor_visible(&self) -> bool {
+        self.cursor_visible
+    }
+
+    pub fn cursor_style(&self) -> CursorStyle {
+        self.cursor_style
+    }
+
+    pub fn cursor_color(&self) -> Option<Color> {
+        self.cursor_color
+    }
+
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    // Until the program sets another one, an empty title goes back to the default
+    pub fn set_title(&mut self, title: String) {
+        self.title = Some(title).filter(|title| !title.is_empty());
+    }
+
+    // The screen's lines without trailing blanks, after the scrollback if `scrollback` is set
+    pub fn text(&self, scrollback: bool) -> Vec<String> {
+        let buffer = self.buffer();
+        let history = buffer.history().filter(|_| scrollback);
+        let screen = (0..buffer.rows).map(|row| buffer.row(row));
+        history
+            .chain(screen)
+            .map(|row| {
+                row.iter()
+                    .filter(|cell| !cell.is_spacer())
+                    .map(|cell| cell.content)
+                    .collect::<String>()
+                    .trim_end()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    pub fn take_bell(&mut self) -> bool {
+        std::mem::take(&mut self.bell)
+    }
+
+    pub fn size(&self) -> TerminalSize {
+        self.size
     }
 
     pub fn winsize(&self) -> Winsize {
         self.size.winsize()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn terminal(output: &str) -> Terminal {
+        let mut terminal = Terminal::new(TerminalSize::new(20, 3));
+        feed(&mut terminal, output);
+        terminal
+    }
+
+    fn feed(terminal: &mut Terminal, output: &str) {
+        let _ = terminal.parse(Event::Output(TerminalOutput::split(output)));
+    }
+
+    #[test]
+    fn dectcem_hides_and_shows_the_cursor() {
+        let mut terminal = terminal("\x1b[?25l");
+        assert!(!terminal.cursor_visible());
+        feed(&mut terminal, "\x1b[?25h");
+        assert!(terminal.cursor_visible());
+    }
+
+    #[test]
+    fn blink_mode_changes_only_the_blinking() {
+        let mut terminal = terminal("\x1b[4 q");
+        assert_eq!(terminal.cursor_style(), CursorStyle::SteadyUnderline);
+        feed(&mut terminal, "\x1b[?12h");
+        assert_eq!(terminal.cursor_style(), CursorStyle::BlinkingUnderline);
+        feed(&mut terminal, "\x1b[?12l");
+        assert_eq!(terminal.cursor_style(), CursorStyle::SteadyUnderline);
+    }
+
+    #[test]
+    fn cursor_style_survives_the_alternate_screen() {
+        let mut terminal = terminal("\x1b[6 q\x1b[?12h\x1b[?1049h");
+        assert_eq!(terminal.cursor_style(), CursorStyle::BlinkingBar);
+        feed(&mut terminal, "\x1b[?12l\x1b[?1049l");
+        assert_eq!(terminal.cursor_style(), CursorStyle::SteadyBar);
+    }
+
+    #[test]
+    fn s7c1t_stops_reading_c1_controls_unless_configured() {
+        let mut unconfigured = terminal("");
+        unconfigured.handle_ansi("\x1b G\x1b F");
+        assert_eq!(
+            unconfigured.pending,
+            [TermMessage::SetC1Controls(true), TermMessage::SetC1Controls(false)]
+        );
+        assert!(!unconfigured.eight_bit_controls());
+
+        let mut configured = terminal("");
+        configured.set_c1_controls(true);
+        configured.handle_ansi("\x1b G\x1b F");
+        assert_eq!(configured.pending, [TermMessage::SetC1Controls(true)]);
+    }
+
+    #[test]
+    fn search_walks_up_through_the_scrollback() {
+        let mut terminal = terminal("");
+        terminal.set_scrollback(10);
+        feed(&mut terminal, "one cat\r\ntwo\r\ncat cat\r\nfour\r\nfive");
+        let end = (usize::MAX, usize::MAX);
+        assert_eq!(terminal.find("cat", end), Some((2, 4..7)));
+        // Earlier matches on the same line come before the lines above
+        assert_eq!(terminal.find("cat", (2, 4)), Some((2, 0..3)));
+        assert_eq!(terminal.find("cat", (2, 0)), Some((0, 4..7)));
+        assert_eq!(terminal.find("cat", (0, 4)), None);
+        assert_eq!(terminal.find("dog", end), None);
+
+        // The first line is two rows above the screen
+        assert_eq!(terminal.show_line(0), 0);
+        assert_eq!(terminal.buffer().display_offset(), 2);
+        assert_eq!(terminal.show_line(2), 2);
+        assert_eq!(terminal.show_line(4), 2);
+        assert_eq!(terminal.buffer().display_offset(), 0);
+    }
+
+    #[test]
+    fn search_matches_wide_characters_over_both_cells() {
+        let mut terminal = terminal("");
+        feed(&mut terminal, "a漢字b");
+        assert_eq!(terminal.find("字b", (usize::MAX, usize::MAX)), Some((0, 3..6)));
+    }
+}
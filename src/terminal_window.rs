@@ -0,0 +1,627 @@
+use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+use iced::{
+    keyboard::{self, key::Named},
+    mouse,
+    widget::{
+        column, container,
+        pane_grid::{self, Axis, Direction, Pane, PaneGrid},
+    },
+    window::Id,
+    Background, Element, Point, Rectangle, Size, Task,
+};
+
+use crate::{
+    bindings::Action,
+    config::Config,
+    palette::mix,
+    panes::{Panes, PANE_SPACING},
+    renderer::{
+        cursor::{Blink, CursorAppearance},
+        grid::Grid,
+        tab_bar::{tab_bar, TAB_BAR_HEIGHT},
+    },
+    session::{Search, Session},
+    structs::{selection::TextSelection, terminalsize::TerminalSize},
+    tabs::Tabs,
+    term::term::{self, Shell},
+    window::{Window, WindowFocus},
+    Message,
+};
+
+/// A window of the application with its tabs, and the panes of those
+pub struct TerminalWindow {
+    window: Window,
+    tabs: Tabs<Panes<Session>>,
+    focused: bool,
+    blink: Blink,
+    // A mouse button is down and dragging extends the selection
+    selecting: bool,
+    // Where the mouse pointer was last seen, mouse button events don't say
+    pointer: Point,
+}
+
+impl TerminalWindow {
+    // Starts out with one tab running a shell as session `id`, or showing the session server's
+    // session `attach`
+    pub fn new(window: Window, id: usize, attach: Option<u64>, config: &Config) -> Self {
+        let size = grid_size(Size::new(window.content_width(), window.content_height()), config);
+        let session = match attach {
+            Some(session) => Session::attach(id, size, session, config),
+            None => Session::new(id, size, config),
+        };
+        Self {
+            window,
+            tabs: Tabs::new(Panes::new(session)),
+            focused: true,
+            blink: Blink::new(),
+            selecting: false,
+            pointer: Point::ORIGIN,
+        }
+    }
+
+    pub fn id(&self) -> Id {
+        self.window.id()
+    }
+
+    pub fn view<'a>(&'a self, config: &'a Config) -> Element<'a, Message> {
+        let id = self.id();
+        let tab = self.tabs.active();
+        let background = config.palette.background;
+        let panes = PaneGrid::new(tab.state(), |pane, session, _zoomed| {
+            let grid = Grid::new(
+                session.terminal.buffer(),
+                &session.rows,
+                config,
+                self.cursor_appearance(session, pane == tab.focused_pane(), config),
+                session.selection,
+            )
+            .search(session.search.as_ref().map(|search| search.query.as_str()));
+            pane_grid::Content::new(grid).style(move |_theme| container::Style {
+                background: Some(Background::Color(background)),
+                ..container::Style::default()
+            })
+        })
+        .spacing(PANE_SPACING)
+        .on_resize(0, move |event| Message::PaneResized(id, event));
+        // Shows between the panes
+        let border = mix(background, config.palette.foreground, 0.25);
+        let panes = container(container(panes).style(move |_theme| container::Style {
+            background: Some(Background::Color(border)),
+            ..container::Style::default()
+        }))
+        .padding(self.window.padding);
+        if self.tabs.len() == 1 {
+            return panes.into();
+        }
+        column![tab_bar(id, &self.tabs, &config.title, &config.palette), panes].into()
+    }
+
+    // The focused pane's title, as its shell set it, or what is searched for
+    pub fn title(&self, config: &Config) -> String {
+        if let Some(search) = &self.session().search {
+            return format!("Search: {}", search.query);
+        }
+        let title = self.session().terminal.title();
+        title.unwrap_or(&config.title).to_string()
+    }
+
+    // Only the focused pane's cursor blinks, the others show a steady hollow block
+    fn cursor_appearance(&self, session: &Session, focused: bool, config: &Config) -> CursorAppearance {
+        let terminal = &session.terminal;
+        let blinking = focused && self.cursor_blinks();
+        CursorAppearance {
+            visible: terminal.cursor_visible() && (self.blink.is_on() || !blinking),
+            style: terminal.cursor_style(),
+            focused: self.focused && focused,
+            color: terminal.cursor_color().unwrap_or(config.palette.cursor),
+        }
+    }
+
+    // Unfocused windows show a steady hollow block instead
+    pub fn cursor_blinks(&self) -> bool {
+        let terminal = &self.session().terminal;
+        self.focused && terminal.cursor_visible() && terminal.cursor_style().is_blinking()
+    }
+
+    pub fn toggle_blink(&mut self) {
+        self.blink.toggle();
+    }
+
+    pub fn reset_blink(&mut self) {
+        self.blink.reset();
+    }
+
+    // The session in the focused pane of the active tab, the one keys go to
+    pub fn session(&self) -> &Session {
+        self.tabs.active().focused()
+    }
+
+    fn session_mut(&mut self) -> &mut Session {
+        self.tabs.active_mut().focused_mut()
+    }
+
+    pub fn tabs(&self) -> &Tabs<Panes<Session>> {
+        &self.tabs
+    }
+
+    pub fn is_focused(&self) -> bool {
+        self.focused
+    }
+
+    pub fn session_with_id_mut(&mut self, id: usize) -> Option<&mut Session> {
+        let (tab, pane) = self.find_session(id)?;
+        self.tabs.get_mut(tab)?.get_mut(pane)
+    }
+
+    pub fn sessions(&self) -> impl Iterator<Item = &Session> {
+        self.tabs.iter().flat_map(Panes::iter)
+    }
+
+    pub fn sessions_mut(&mut self) -> impl Iterator<Item = &mut Session> {
+        self.tabs.iter_mut().flat_map(Panes::iter_mut)
+    }
+
+    // Actions on what is in the window. Those with new sessions or reaching past the window are
+    // up to the application.
+    pub fn perform(&mut self, action: Action, config: &Config) -> Task<Message> {
+        let id = self.id();
+        let session = self.session();
+        let page = session.terminal.size().rows as isize;
+        match action {
+            Action::Copy => match session.selection.filter(|selection| !selection.is_empty()) {
+                Some(selection) => iced::clipboard::write(session.terminal.selected_text(&selection)),
+                None => Task::none(),
+            },
+            Action::Paste => iced::clipboard::read().map(move |text| Message::Paste(id, text)),
+            Action::Search => {
+                self.session_mut().search = Some(Search::default());
+                Task::none()
+            }
+            Action::ScrollLineUp => self.scroll(1),
+            Action::ScrollLineDown => self.scroll(-1),
+            Action::ScrollPageUp => self.scroll(page),
+            Action::ScrollPageDown => self.scroll(-page),
+            Action::ScrollToTop => self.scroll(isize::MAX),
+            Action::ScrollToBottom => self.scroll(isize::MIN),
+            Action::CloseTab => self.close_tab(self.tabs.active_index(), config),
+            Action::NextTab => self.switch_tab(|tabs| tabs.cycle(1), config),
+            Action::PreviousTab => self.switch_tab(|tabs| tabs.cycle(-1), config),
+            Action::MoveTabLeft => {
+                self.tabs.move_active(-1);
+                Task::none()
+            }
+            Action::MoveTabRight => {
+                self.tabs.move_active(1);
+                Task::none()
+            }
+            Action::ClosePane => {
+                let pane = self.tabs.active().focused_pane();
+                self.close_pane(self.tabs.active_index(), pane, config)
+            }
+            Action::FocusPaneLeft => self.focus_neighbour(Direction::Left, config),
+            Action::FocusPaneRight => self.focus_neighbour(Direction::Right, config),
+            Action::FocusPaneUp => self.focus_neighbour(Direction::Up, config),
+            Action::FocusPaneDown => self.focus_neighbour(Direction::Down, config),
+            Action::ZoomPane => {
+                self.tabs.active_mut().toggle_zoom();
+                self.resize_grids(config)
+            }
+            Action::ResizePaneLeft => self.resize_pane(Direction::Left, config),
+            Action::ResizePaneRight => self.resize_pane(Direction::Right, config),
+            Action::ResizePaneUp => self.resize_pane(Direction::Up, config),
+            Action::ResizePaneDown => self.resize_pane(Direction::Down, config),
+            Action::ZoomIn
+            | Action::ZoomOut
+            | Action::ResetZoom
+            | Action::NewTab
+            | Action::NewWindow
+            | Action::SplitRight
+            | Action::SplitDown => Task::none(),
+        }
+    }
+
+    // Whether `action` does anything here. Moving the focus or a split only does with more than one
+    // pane, until then the keys bound to them are left for the shell, like Alt and the arrows.
+    pub fn handles(&self, action: Action) -> bool {
+        let panes = self.tabs.active();
+        match action {
+            Action::FocusPaneLeft => panes.neighbour(Direction::Left).is_some(),
+            Action::FocusPaneRight => panes.neighbour(Direction::Right).is_some(),
+            Action::FocusPaneUp => panes.neighbour(Direction::Up).is_some(),
+            Action::FocusPaneDown => panes.neighbour(Direction::Down).is_some(),
+            Action::ResizePaneLeft | Action::ResizePaneRight | Action::ResizePaneUp | Action::ResizePaneDown => {
+                panes.len() > 1
+            }
+            _ => true,
+        }
+    }
+
+    // Scrolls the view through the scrollback, `lines` up or down for negative `lines`
+    fn scroll(&mut self, lines: isize) -> Task<Message> {
+        self.session_mut().scroll(lines);
+        Task::none()
+    }
+
+    // Keys typed while searching edit the query instead of going to the shell
+    pub fn search_key(&mut self, key: &keyboard::Key, text: Option<&str>) -> Task<Message> {
+        let session = self.session_mut();
+        let Some(search) = &mut session.search else {
+            return Task::none();
+        };
+        match key {
+            keyboard::Key::Named(Named::Escape) => {
+                session.search = None;
+                session.selection = None;
+            }
+            keyboard::Key::Named(Named::Enter) => session.find_previous(),
+            keyboard::Key::Named(Named::Backspace) => {
+                search.query.pop();
+                session.search_again();
+            }
+            _ => {
+                if let Some(text) = text.filter(|text| !text.chars().any(char::is_control)) {
+                    search.query.push_str(text);
+                    session.search_again();
+                }
+            }
+        }
+        Task::none()
+    }
+
+    // Input for the shell brings the view back to the screen, where the shell's output goes
+    pub fn send_input(&mut self, bytes: Vec<u8>) -> Task<Message> {
+        self.blink.reset();
+        let session = self.session_mut();
+        session.scroll_to_bottom();
+        session.terminal.send(term::TermMessage::Bytes(bytes))
+    }
+
+    pub fn paste(&mut self, text: &str) -> Task<Message> {
+        let session = self.session_mut();
+        session.scroll_to_bottom();
+        session.terminal.paste(text)
+    }
+
+    // The tab runs `shell` if given, the configured shell otherwise
+    pub fn new_tab(&mut self, id: usize, shell: Option<Shell>, config: &Config) -> Task<Message> {
+        let session = Session::new(id, self.session().terminal.size(), config).with_shell(shell);
+        self.switch_tab(|tabs| tabs.open(Panes::new(session)), config)
+    }
+
+    // The last tab takes the window with it
+    pub fn close_tab(&mut self, index: usize, config: &Config) -> Task<Message> {
+        if self.tabs.len() == 1 {
+            return iced::window::close(self.id());
+        }
+        let active = self.tabs.active_index();
+        self.tabs.close(index);
+        self.selecting = false;
+        let focus = match index == active {
+            true => self.report_focus(true),
+            false => Task::none(),
+        };
+        // The bar goes away with the second last tab
+        Task::batch([focus, self.resize_grids(config)])
+    }
+
+    pub fn select_tab(&mut self, index: usize, config: &Config) -> Task<Message> {
+        self.switch_tab(|tabs| tabs.select(index), config)
+    }
+
+    // Changes the active tab, telling the shells that asked for focus reports which one has it
+    fn switch_tab(&mut self, switch: impl FnOnce(&mut Tabs<Panes<Session>>), config: &Config) -> Task<Message> {
+        let unfocus = self.report_focus(false);
+        switch(&mut self.tabs);
+        self.selecting = false;
+        for session in self.tabs.active_mut().iter_mut() {
+            session.activity = false;
+            session.bell = false;
+        }
+        self.blink.reset();
+        Task::batch([unfocus, self.report_focus(true), self.resize_grids(config)])
+    }
+
+    pub fn split(&mut self, axis: Axis, id: usize, shell: Option<Shell>, config: &Config) -> Task<Message> {
+        let session = Session::new(id, self.session().terminal.size(), config).with_shell(shell);
+        self.switch_pane(|panes| panes.split(axis, session), config)
+    }
+
+    // The last pane takes the tab with it
+    fn close_pane(&mut self, tab: usize, pane: Pane, config: &Config) -> Task<Message> {
+        let active = tab == self.tabs.active_index();
+        let Some(panes) = self.tabs.get_mut(tab) else {
+            return Task::none();
+        };
+        if panes.len() == 1 {
+            return self.close_tab(tab, config);
+        }
+        let focused = pane == panes.focused_pane();
+        panes.close(pane);
+        self.selecting = false;
+        let focus = match active && focused {
+            true => self.report_focus(true),
+            false => Task::none(),
+        };
+        Task::batch([focus, self.resize_grids(config)])
+    }
+
+    // Shows the session with `id`, focusing its tab and pane
+    pub fn focus_session(&mut self, id: usize, config: &Config) -> Task<Message> {
+        let Some((tab, pane)) = self.find_session(id) else {
+            return Task::none();
+        };
+        let select = match tab == self.tabs.active_index() {
+            true => Task::none(),
+            false => self.select_tab(tab, config),
+        };
+        let focus = self.switch_pane(
+            |panes| {
+                panes.focus(pane);
+            },
+            config,
+        );
+        Task::batch([select, focus])
+    }
+
+    fn focus_neighbour(&mut self, direction: Direction, config: &Config) -> Task<Message> {
+        match self.tabs.active().neighbour(direction) {
+            Some(pane) => self.switch_pane(
+                |panes| {
+                    panes.focus(pane);
+                },
+                config,
+            ),
+            None => Task::none(),
+        }
+    }
+
+    // Moves the focus within the active tab, telling the shells that asked for focus reports
+    fn switch_pane(&mut self, switch: impl FnOnce(&mut Panes<Session>), config: &Config) -> Task<Message> {
+        let unfocus = self.report_focus(false);
+        switch(self.tabs.active_mut());
+        self.selecting = false;
+        self.blink.reset();
+        Task::batch([unfocus, self.report_focus(true), self.resize_grids(config)])
+    }
+
+    // Moves a border of the focused pane by one cell
+    fn resize_pane(&mut self, direction: Direction, config: &Config) -> Task<Message> {
+        let amount = match direction {
+            Direction::Left | Direction::Right => config.cell_size.width,
+            Direction::Up | Direction::Down => config.cell_size.height,
+        };
+        let area = self.grid_area();
+        self.tabs.active_mut().resize(direction, amount, area);
+        self.resize_grids(config)
+    }
+
+    pub fn set_ratio(&mut self, split: pane_grid::Split, ratio: f32, config: &Config) -> Task<Message> {
+        self.tabs.active_mut().set_ratio(split, ratio);
+        self.resize_grids(config)
+    }
+
+    pub fn focus(&mut self, focus: WindowFocus) -> Task<Message> {
+        self.blink.reset();
+        match focus {
+            WindowFocus::Focus => {
+                self.focused = true;
+                self.report_focus(true)
+            }
+            WindowFocus::Unfocus => {
+                let task = self.report_focus(false);
+                self.focused = false;
+                task
+            }
+        }
+    }
+
+    fn report_focus(&self, focused: bool) -> Task<Message> {
+        let terminal = &self.session().terminal;
+        match (self.focused, focused) {
+            (false, _) => Task::none(),
+            (true, true) => terminal.focus(),
+            (true, false) => terminal.unfocus(),
+        }
+    }
+
+    fn tab_bar_height(&self) -> f32 {
+        match self.tabs.len() {
+            1 => 0.0,
+            _ => TAB_BAR_HEIGHT,
+        }
+    }
+
+    // The part of the window the panes share, and where it starts
+    fn grid_area(&self) -> Size {
+        Size::new(
+            self.window.content_width(),
+            self.window.content_height() - self.tab_bar_height(),
+        )
+    }
+
+    fn grid_origin(&self) -> Point {
+        let padding = self.window.padding;
+        Point::new(padding.left, self.tab_bar_height() + padding.top)
+    }
+
+    pub fn mouse(&mut self, event: mouse::Event, config: &Config) -> Task<Message> {
+        match event {
+            mouse::Event::CursorMoved { position } => {
+                self.pointer = position;
+                if self.selecting {
+                    let region = self.tabs.active().focused_region(self.grid_area());
+                    let (row, col) = self.cell_at(region, position, config);
+                    if let Some(selection) = &mut self.session_mut().selection {
+                        selection.extend(row, col);
+                    }
+                }
+            }
+            mouse::Event::ButtonPressed(mouse::Button::Left) => return self.press(config),
+            mouse::Event::ButtonReleased(mouse::Button::Left) => {
+                self.selecting = false;
+                let session = self.session_mut();
+                session.selection = session.selection.filter(|selection| !selection.is_empty());
+            }
+            mouse::Event::WheelScrolled { delta } if !self.session().terminal.modes().alt_screen => {
+                let lines = match delta {
+                    mouse::ScrollDelta::Lines { y, .. } => y * WHEEL_LINES,
+                    mouse::ScrollDelta::Pixels { y, .. } => y / config.cell_size.height,
+                };
+                return self.scroll(lines.round() as isize);
+            }
+            _ => {}
+        }
+        Task::none()
+    }
+
+    // Focuses the pane under the pointer and starts a selection there. Presses on the tab bar
+    // and between panes are for the buttons and borders there.
+    fn press(&mut self, config: &Config) -> Task<Message> {
+        let point = self.pointer - (self.grid_origin() - Point::ORIGIN);
+        let Some((pane, region)) = self.tabs.active().pane_at(self.grid_area(), point) else {
+            return Task::none();
+        };
+        let task = match pane == self.tabs.active().focused_pane() {
+            true => Task::none(),
+            false => self.switch_pane(
+                |panes| {
+                    panes.focus(pane);
+                },
+                config,
+            ),
+        };
+        let (row, col) = self.cell_at(region, self.pointer, config);
+        self.session_mut().selection = Some(TextSelection::new(row, col));
+        self.selecting = true;
+        task
+    }
+
+    // Row and nearest column boundary under a point of the window, in the pane at `region`
+    fn cell_at(&self, region: Rectangle, position: Point, config: &Config) -> (usize, usize) {
+        let size = self.session().terminal.size();
+        let origin = self.grid_origin();
+        let x = (position.x - origin.x - region.x).max(0.0) / config.cell_size.width;
+        let y = (position.y - origin.y - region.y).max(0.0) / config.cell_size.height;
+        ((y as usize).min(size.rows - 1), (x.round() as usize).min(size.cols))
+    }
+
+    // Where the session with `id` is, if it is in this window
+    fn find_session(&self, id: usize) -> Option<(usize, Pane)> {
+        self.tabs
+            .iter()
+            .enumerate()
+            .find_map(|(tab, panes)| Some((tab, panes.find(|session| session.id == id)?)))
+    }
+
+    pub fn has_session(&self, id: usize) -> bool {
+        self.find_session(id).is_some()
+    }
+
+    // The shell of a pane is gone. Without `hold` the pane goes with it, otherwise it stays open
+    // saying how the shell ended.
+    fn ended(&mut self, tab: usize, pane: Pane, notice: String, hold: bool, config: &Config) -> Task<Message> {
+        if !hold {
+            return self.close_pane(tab, pane, config);
+        }
+        let Some(session) = self.tabs.get_mut(tab).and_then(|panes| panes.get_mut(pane)) else {
+            return Task::none();
+        };
+        session.ended = true;
+        session.scroll_to_bottom();
+        session.terminal.end(&format!("{} Press Enter to restart.", notice));
+        session.update_rows();
+        Task::none()
+    }
+
+    // Starts a new shell as session `id` in the focused pane, on a clean screen
+    pub fn restart(&mut self, id: usize, config: &Config) -> Task<Message> {
+        self.session_mut().restart(id, config);
+        Task::none()
+    }
+
+    pub fn output(&mut self, id: usize, event: term::Event, config: &Config) -> Task<Message> {
+        let Some((tab, pane)) = self.find_session(id) else {
+            return Task::none();
+        };
+        match event {
+            term::Event::Exited(status) => self.ended(tab, pane, exit_notice(status), config.hold, config),
+            // Held open regardless, or the error would go unseen
+            term::Event::Failed(error) => self.ended(tab, pane, format!("[{}]", error), true, config),
+            event => {
+                let active = tab == self.tabs.active_index();
+                let Some(session) = self.tabs.get_mut(tab).and_then(|panes| panes.get_mut(pane)) else {
+                    return Task::none();
+                };
+                let task = session.terminal.parse(event);
+                session.update_rows();
+                if active {
+                    session.terminal.take_bell();
+                    self.blink.reset();
+                } else {
+                    session.activity = true;
+                    session.bell |= session.terminal.take_bell();
+                }
+                task
+            }
+        }
+    }
+
+    // Takes over a reloaded config. The font and colors apply right away, the window keeps its
+    // size and new shells only start with new tabs and panes.
+    pub fn apply_config(&mut self, config: &Config) -> Task<Message> {
+        self.window.padding = config.window_config.padding;
+        for session in self.sessions_mut() {
+            session.apply_config(config);
+        }
+        self.resize_grids(config)
+    }
+
+    // After the font size or the scale factor changed
+    pub fn set_shaper(&mut self, config: &Config) -> Task<Message> {
+        for session in self.sessions_mut() {
+            session.set_shaper(config);
+        }
+        self.resize_grids(config)
+    }
+
+    pub fn resize(&mut self, size: Size, config: &Config) -> Task<Message> {
+        self.window.resize(size);
+        self.resize_grids(config)
+    }
+
+    // Fits every pane's grid to its part of the window again, after the window, the cells, the
+    // tab bar or the splits changed size
+    pub fn resize_grids(&mut self, config: &Config) -> Task<Message> {
+        let area = self.grid_area();
+        let mut tasks = Vec::new();
+        for panes in self.tabs.iter_mut() {
+            for (pane, region) in panes.regions(area) {
+                if let Some(session) = panes.get_mut(pane) {
+                    tasks.push(session.resize(grid_size(region.size(), config)));
+                }
+            }
+        }
+        Task::batch(tasks)
+    }
+}
+
+// Rows scrolled per notch of a mouse wheel
+const WHEEL_LINES: f32 = 3.0;
+
+// How the shell ended, as shown in a held pane
+fn exit_notice(status: ExitStatus) -> String {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => format!("[process exited with code {}]", code),
+        (None, Some(signal)) => format!("[process killed by signal {}]", signal),
+        (None, None) => "[process exited]".to_string(),
+    }
+}
+
+// Whole cells fitting into a pane of `size`
+fn grid_size(size: Size, config: &Config) -> TerminalSize {
+    let cols = (size.width / config.cell_size.width) as usize;
+    let rows = (size.height / config.cell_size.height) as usize;
+    let cell = config.device_cell_size();
+    TerminalSize::new(cols.max(1), rows.max(1)).with_cell_size(cell.width, cell.height)
+}
@@ -1,20 +1,22 @@
+#[cfg(any(target_os = "macos", target_os = "linux"))]
+use iced::window::settings::PlatformSpecific;
 use iced::{
-    window::{settings::PlatformSpecific, Id, Settings},
+    window::{Id, Settings},
     Padding, Size, Task,
 };
 
 pub struct Window {
-    _id: Id,
+    id: Id,
     pub size: Size,
     pub padding: Padding,
 }
 
 impl Window {
-    pub fn main_window(config: WindowConfig) -> (Self, Task<Id>) {
-        let (id, task) = iced::window::open(terminal_window_settings(config.size));
+    pub fn open(config: WindowConfig) -> (Self, Task<Id>) {
+        let (id, task) = iced::window::open(terminal_window_settings(&config));
         (
             Self {
-                _id: id,
+                id,
                 size: config.size,
                 padding: config.padding,
             },
@@ -22,6 +24,10 @@ impl Window {
         )
     }
 
+    pub fn id(&self) -> Id {
+        self.id
+    }
+
     pub fn resize(&mut self, size: Size) {
         self.size = size;
     }
@@ -39,6 +45,8 @@ impl Window {
 pub struct WindowConfig {
     pub size: Size,
     pub padding: Padding,
+    // Window class on X11 and application id on Wayland, for window manager rules
+    pub class: Option<String>,
 }
 
 #[derive(Debug, Clone)]
@@ -47,15 +55,21 @@ pub enum WindowFocus {
     Unfocus,
 }
 
-fn terminal_window_settings(size: Size) -> Settings {
+fn terminal_window_settings(config: &WindowConfig) -> Settings {
     Settings {
         decorations: true,
+        #[cfg(target_os = "macos")]
         platform_specific: PlatformSpecific {
             title_hidden: true,
             titlebar_transparent: true,
             fullsize_content_view: true,
         },
-        size,
+        #[cfg(target_os = "linux")]
+        platform_specific: PlatformSpecific {
+            application_id: config.class.clone().unwrap_or_default(),
+            ..PlatformSpecific::default()
+        },
+        size: config.size,
         ..Settings::default()
     }
 }